@@ -0,0 +1,107 @@
+//! `git upstream adopt`, for setting upstreams for branches which already exist on a remote.
+
+use command_error::CommandExt;
+use miette::IntoDiagnostic;
+
+use crate::git;
+use crate::git::Upstream;
+use crate::prompt::choose;
+use crate::status::BranchStatus;
+use crate::Config;
+
+/// Set the upstream of branches which already exist on a remote, without pushing.
+#[derive(Debug, Clone, clap::Args)]
+pub struct AdoptArgs {
+    /// Adopt every local branch without an upstream, instead of just the current branch.
+    #[arg(long)]
+    all: bool,
+
+    /// Fetch from each remote first, so that remote-tracking refs are up to date.
+    #[arg(long)]
+    fetch: bool,
+
+    /// When a branch exists on multiple remotes, ask which one to track instead of picking the
+    /// most preferred one.
+    #[arg(short, long)]
+    interactive: bool,
+}
+
+/// Run `git upstream adopt`.
+pub fn adopt(config: &Config, args: &AdoptArgs) -> miette::Result<()> {
+    let remotes = config.candidate_remotes()?;
+
+    if args.fetch {
+        for remote in &remotes {
+            let mut command = git::command();
+            command.args(["fetch", remote]);
+            git::log_command(&command);
+            if let Err(err) = command.status_checked() {
+                tracing::warn!("Failed to fetch from {remote}: {err}");
+            }
+        }
+    }
+
+    let branches = if args.all {
+        BranchStatus::list()?
+            .into_iter()
+            .map(|status| status.branch)
+            .collect()
+    } else {
+        vec![config.git().current_branch()?]
+    };
+
+    let mut adopted = 0;
+    let mut already_tracking = 0;
+    let mut not_found = 0;
+    for branch in &branches {
+        if let Some(upstream) = Upstream::of(branch)? {
+            tracing::info!("{branch} already tracks {upstream}");
+            already_tracking += 1;
+            continue;
+        }
+
+        let mut found = Vec::new();
+        for remote in &remotes {
+            if git::rev_parse(&format!("refs/remotes/{remote}/{branch}"))?.is_some() {
+                found.push(format!("{remote}/{branch}"));
+            }
+        }
+
+        let upstream = match found.as_slice() {
+            [] => {
+                tracing::info!("{branch} doesn't exist on any remote");
+                not_found += 1;
+                continue;
+            }
+            [upstream] => upstream,
+            [first, ..] => {
+                if args.interactive {
+                    let question = format!("{branch} exists on multiple remotes; track which?");
+                    &found[choose(&question, &found)?]
+                } else {
+                    tracing::info!(
+                        "{branch} exists as {}; picking {first} because it's most preferred",
+                        found.join(", ")
+                    );
+                    first
+                }
+            }
+        };
+
+        git::command()
+            .args(["branch", &format!("--set-upstream-to={upstream}"), branch])
+            .output_checked_utf8()
+            .into_diagnostic()?;
+        tracing::info!("{branch} now tracks {upstream}");
+        adopted += 1;
+    }
+
+    if branches.len() > 1 {
+        tracing::info!(
+            "Adopted {adopted}, {already_tracking} already tracking, {not_found} not found on any \
+             remote"
+        );
+    }
+
+    Ok(())
+}
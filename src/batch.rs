@@ -0,0 +1,136 @@
+//! `--stdin` mode, for pushing many branches at once.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::io::BufRead;
+
+use miette::miette;
+use miette::IntoDiagnostic;
+
+use crate::interrupt::Interrupted;
+use crate::report::AttemptResult;
+use crate::Config;
+
+/// What happened to a single branch.
+enum Outcome {
+    Pushed(String),
+    UpToDate(String),
+    Failed(String),
+}
+
+/// Push the branches named on stdin, one per line.
+///
+/// Each branch is pushed like a single branch would be, with [`Config::try_push`], so it gets the
+/// same checks, flags, and upstream handling. Branches which fail to push are retried on the
+/// next remote.
+pub fn push_stdin(config: &Config) -> miette::Result<()> {
+    let mut outcomes = BTreeMap::new();
+    let mut pending = Vec::new();
+
+    for branch in read_branches()? {
+        if let Some(pattern) = config.never_push_pattern(&branch) {
+            tracing::debug!(%branch, %pattern, "Skipping branch matching `never-push`");
+        } else if config
+            .git()
+            .rev_parse(&format!("refs/heads/{branch}"))?
+            .is_none()
+        {
+            outcomes.insert(branch, Outcome::Failed("not a local branch".to_owned()));
+        } else {
+            pending.push(branch);
+        }
+    }
+
+    for candidate in config.ranked_candidates()? {
+        if pending.is_empty() {
+            break;
+        }
+        let remote = &candidate.remote.name;
+        let _span = tracing::info_span!("push", %remote).entered();
+
+        for branch in std::mem::take(&mut pending) {
+            let attempt = match config.try_push(&branch, &candidate.remote) {
+                Ok(attempt) => attempt,
+                Err(err) if err.downcast_ref::<Interrupted>().is_some() => return Err(err),
+                // Checks like `never-push` and `--scan-secrets` would fail the same way on
+                // every remote.
+                Err(err) => {
+                    outcomes.insert(branch, Outcome::Failed(err.to_string()));
+                    continue;
+                }
+            };
+            match attempt.result {
+                AttemptResult::UpToDate => {
+                    outcomes.insert(branch, Outcome::UpToDate(remote.clone()));
+                }
+                AttemptResult::Pushed => {
+                    outcomes.insert(branch, Outcome::Pushed(remote.clone()));
+                }
+                AttemptResult::Failed
+                    if attempt
+                        .failure
+                        .is_some_and(|failure| !failure.falls_through()) =>
+                {
+                    outcomes.insert(branch, Outcome::Failed(attempt.to_string()));
+                }
+                AttemptResult::Failed => {
+                    tracing::debug!(%branch, "Failed to push to Git remote: {attempt}");
+                    pending.push(branch);
+                }
+            }
+        }
+
+        if config.cli.fail_fast && !pending.is_empty() {
+            break;
+        }
+    }
+
+    for branch in pending {
+        outcomes.insert(
+            branch,
+            Outcome::Failed("failed to push to any remote".to_owned()),
+        );
+    }
+
+    let mut failures = 0;
+    for (branch, outcome) in &outcomes {
+        match outcome {
+            Outcome::Pushed(remote) if config.cli.dry_run => {
+                tracing::info!("Dry run: would push {branch} to {remote}")
+            }
+            Outcome::Pushed(remote) => tracing::info!("Pushed {branch} to {remote}"),
+            Outcome::UpToDate(remote) => {
+                tracing::info!("Already up to date with {remote}/{branch}")
+            }
+            Outcome::Failed(reason) => {
+                failures += 1;
+                tracing::error!("Failed to push {branch}: {reason}");
+            }
+        }
+    }
+
+    if failures > 0 {
+        Err(miette!(
+            "Failed to upstream {failures} of {} branches",
+            outcomes.len()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Read branch names from stdin, ignoring blank lines and duplicates.
+fn read_branches() -> miette::Result<Vec<String>> {
+    let mut seen = BTreeSet::new();
+    let mut branches = Vec::new();
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line.into_diagnostic()?;
+        let branch = line.trim();
+        if !branch.is_empty() && seen.insert(branch.to_owned()) {
+            branches.push(branch.to_owned());
+        }
+    }
+
+    Ok(branches)
+}
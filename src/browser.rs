@@ -0,0 +1,31 @@
+//! Opening URLs in the user's web browser.
+
+use std::process::Command;
+use std::process::Stdio;
+
+use command_error::CommandExt;
+use miette::IntoDiagnostic;
+
+/// Open `url` in the default browser, with `open` on macOS, `start` on Windows, and `xdg-open`
+/// elsewhere.
+pub fn open(url: &str) -> miette::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        // The empty string is the window title; otherwise `start` treats a quoted URL as one.
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+
+    command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status_checked()
+        .into_diagnostic()?;
+    Ok(())
+}
@@ -0,0 +1,258 @@
+//! `--signed` and `--atomic`, which not every server supports.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::failure::FailureKind;
+use crate::Config;
+
+/// When to sign pushes, like `git push --signed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Signed {
+    /// Sign pushes, failing if the server doesn't support it.
+    True,
+    /// Don't sign pushes, even if `push.gpgSign` is set.
+    False,
+    /// Sign pushes if the server supports it.
+    IfAsked,
+}
+
+impl Signed {
+    /// The values in configuration files.
+    pub const NAMES: &[&str] = &["true", "false", "if-asked"];
+
+    /// The `git push` argument.
+    pub fn flag(self) -> &'static str {
+        match self {
+            Self::True => "--signed=true",
+            Self::False => "--signed=false",
+            Self::IfAsked => "--signed=if-asked",
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Signed {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bool(bool),
+            String(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Bool(true) => Ok(Self::True),
+            Raw::Bool(false) => Ok(Self::False),
+            Raw::String(signed) => match signed.as_str() {
+                "true" => Ok(Self::True),
+                "false" => Ok(Self::False),
+                "if-asked" => Ok(Self::IfAsked),
+                _ => Err(serde::de::Error::custom(format!(
+                    "invalid value {signed:?}; use true, false, or \"if-asked\""
+                ))),
+            },
+        }
+    }
+}
+
+/// Settings for one remote, from the `[remote-settings.NAME]` configuration section.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RemoteSettings {
+    /// Overrides `signed` for the remote.
+    #[serde(default)]
+    pub signed: Option<Signed>,
+    /// Overrides `atomic` for the remote.
+    #[serde(default)]
+    pub atomic: Option<bool>,
+}
+
+/// The `[remote-settings]` configuration section, by remote name.
+pub type RemoteSettingsMap = BTreeMap<String, RemoteSettings>;
+
+/// Get whether to sign pushes to `remote`: `--signed`, then the remote's settings, then
+/// `signed`.
+///
+/// `None` leaves it up to Git, which reads `push.gpgSign`.
+pub fn signed(config: &Config, remote: &str) -> Option<Signed> {
+    config.cli.signed.or_else(|| {
+        config
+            .file
+            .remote_settings
+            .get(remote)
+            .and_then(|settings| settings.signed)
+            .or(config.file.signed)
+    })
+}
+
+/// Get whether to push to `remote` atomically: `--atomic`, then the remote's settings, then
+/// `atomic`.
+pub fn atomic(config: &Config, remote: &str) -> bool {
+    config.cli.atomic
+        || config
+            .file
+            .remote_settings
+            .get(remote)
+            .and_then(|settings| settings.atomic)
+            .or(config.file.atomic)
+            .unwrap_or(false)
+}
+
+/// Check if `next` would fail in the same way as `remote` did, because it's pushed to with the
+/// same flag. Otherwise, the failure isn't worth falling through to the next remote for.
+pub fn fails_the_same(config: &Config, failure: FailureKind, remote: &str, next: &str) -> bool {
+    match failure {
+        FailureKind::SignedUnsupported => signed(config, remote) == signed(config, next),
+        FailureKind::AtomicUnsupported => atomic(config, remote) == atomic(config, next),
+        _ => !failure.falls_through(),
+    }
+}
+
+/// Suggest how to stop a failure caused by `--signed` or `--atomic` when pushing to `remote`.
+pub fn help(config: &Config, failure: FailureKind, remote: &str) -> Option<String> {
+    let (flag, from_cli, setting) = match failure {
+        FailureKind::SignedUnsupported => (
+            "--signed",
+            config.cli.signed.is_some(),
+            "signed = \"if-asked\"",
+        ),
+        FailureKind::AtomicUnsupported => ("--atomic", config.cli.atomic, "atomic = false"),
+        _ => return None,
+    };
+    Some(if from_cli {
+        format!("{remote} doesn't support `{flag}`; push to it without `{flag}`")
+    } else {
+        format!(
+            "{remote} doesn't support `{flag}`; drop it for {remote} by setting `{setting}` \
+             under `[remote-settings.{remote}]` in the configuration file"
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::config_from;
+
+    #[test]
+    fn deserializes_signed() {
+        #[derive(Debug, Deserialize)]
+        struct File {
+            signed: Signed,
+        }
+        let signed = |toml: &str| toml::from_str::<File>(toml).map(|file| file.signed);
+
+        let cases = [
+            ("signed = true", Signed::True),
+            ("signed = false", Signed::False),
+            ("signed = \"true\"", Signed::True),
+            ("signed = \"false\"", Signed::False),
+            ("signed = \"if-asked\"", Signed::IfAsked),
+        ];
+        for (toml, expected) in cases {
+            assert_eq!(signed(toml).unwrap(), expected, "{toml}");
+        }
+        for toml in ["signed = \"yes\"", "signed = \"if_asked\"", "signed = 1"] {
+            assert!(signed(toml).is_err(), "{toml}");
+        }
+        let error = signed("signed = \"sometimes\"").unwrap_err().to_string();
+        assert!(
+            error.contains("use true, false, or \"if-asked\""),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn names_match_the_command_line() {
+        use clap::ValueEnum;
+
+        let names: Vec<_> = Signed::value_variants()
+            .iter()
+            .map(|signed| signed.to_possible_value().unwrap().get_name().to_owned())
+            .collect();
+        assert_eq!(names, Signed::NAMES);
+        for signed in Signed::value_variants() {
+            let name = signed.to_possible_value().unwrap().get_name().to_owned();
+            assert_eq!(signed.flag(), format!("--signed={name}"));
+        }
+    }
+
+    #[test]
+    fn settings_precedence() {
+        let file = "signed = true\n\
+                    atomic = true\n\
+                    [remote-settings.fork]\n\
+                    signed = \"if-asked\"\n\
+                    atomic = false\n";
+        let config = config_from(&[], file);
+        assert_eq!(signed(&config, "origin"), Some(Signed::True));
+        assert_eq!(signed(&config, "fork"), Some(Signed::IfAsked));
+        assert!(atomic(&config, "origin"));
+        assert!(!atomic(&config, "fork"));
+
+        let config = config_from(&["--signed=false", "--atomic"], file);
+        assert_eq!(signed(&config, "fork"), Some(Signed::False));
+        assert!(atomic(&config, "fork"));
+
+        // A bare `--signed` means `--signed=true`.
+        let config = config_from(&["--signed"], "");
+        assert_eq!(signed(&config, "origin"), Some(Signed::True));
+
+        let config = config_from(&[], "");
+        assert_eq!(signed(&config, "origin"), None);
+        assert!(!atomic(&config, "origin"));
+    }
+
+    #[test]
+    fn fails_the_same_way() {
+        let config = config_from(
+            &[],
+            "signed = true\n\
+             atomic = true\n\
+             [remote-settings.fork]\n\
+             signed = \"if-asked\"\n\
+             [remote-settings.mirror]\n\
+             atomic = false\n",
+        );
+        let signed = FailureKind::SignedUnsupported;
+        assert!(fails_the_same(&config, signed, "origin", "mirror"));
+        assert!(!fails_the_same(&config, signed, "origin", "fork"));
+        let atomic = FailureKind::AtomicUnsupported;
+        assert!(fails_the_same(&config, atomic, "origin", "fork"));
+        assert!(!fails_the_same(&config, atomic, "origin", "mirror"));
+
+        // Other failures depend only on whether they fall through.
+        assert!(fails_the_same(
+            &config,
+            FailureKind::NonFastForward,
+            "origin",
+            "fork"
+        ));
+        assert!(!fails_the_same(
+            &config,
+            FailureKind::PermissionDenied,
+            "origin",
+            "fork"
+        ));
+    }
+
+    #[test]
+    fn help_names_where_the_flag_came_from() {
+        let config = config_from(&["--atomic"], "signed = true\n");
+        let atomic = help(&config, FailureKind::AtomicUnsupported, "origin").unwrap();
+        assert_eq!(
+            atomic,
+            "origin doesn't support `--atomic`; push to it without `--atomic`"
+        );
+        let signed = help(&config, FailureKind::SignedUnsupported, "origin").unwrap();
+        assert!(
+            signed.contains("setting `signed = \"if-asked\"` under `[remote-settings.origin]`"),
+            "{signed}"
+        );
+        assert_eq!(help(&config, FailureKind::Auth, "origin"), None);
+    }
+}
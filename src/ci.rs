@@ -0,0 +1,251 @@
+//! Following CI after pushing, for `--watch-ci`, with `gh` or `glab`.
+
+use std::fmt::Display;
+use std::process::Stdio;
+use std::time::Duration;
+use std::time::Instant;
+
+use command_error::CommandExt;
+use miette::Diagnostic;
+use miette::IntoDiagnostic;
+
+use crate::git;
+use crate::pr;
+use crate::pr::PrTool;
+use crate::Config;
+
+/// How often to check on CI.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long to wait for CI to start before deciding there isn't any.
+const START_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How `--watch-ci` reports CI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum WatchCi {
+    /// Log a line every time the status changes.
+    Stream,
+    /// Only log the final result.
+    Summary,
+    /// Like `stream`, but exit with code 7 if CI fails.
+    Gate,
+}
+
+/// The state of one CI job, workflow run, or pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Pending,
+    Running,
+    Passed,
+    Failed,
+}
+
+impl State {
+    fn finished(self) -> bool {
+        matches!(self, Self::Passed | Self::Failed)
+    }
+
+    /// Interpret a GitHub Actions run's `status` and `conclusion`.
+    fn from_github(status: &str, conclusion: &str) -> Self {
+        match (status, conclusion) {
+            ("completed", "success" | "neutral" | "skipped") => Self::Passed,
+            ("completed", _) => Self::Failed,
+            ("in_progress", _) => Self::Running,
+            _ => Self::Pending,
+        }
+    }
+
+    /// Interpret a GitLab pipeline's `status`.
+    fn from_gitlab(status: &str) -> Self {
+        match status {
+            "success" | "skipped" => Self::Passed,
+            "failed" | "canceled" => Self::Failed,
+            "running" => Self::Running,
+            // `manual` pipelines wait for someone to start them.
+            "manual" => Self::Passed,
+            _ => Self::Pending,
+        }
+    }
+}
+
+/// CI runs for a commit, by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Runs(Vec<(String, State)>);
+
+impl Runs {
+    fn finished(&self) -> bool {
+        self.0.iter().all(|(_, state)| state.finished())
+    }
+
+    fn failed(&self) -> impl Iterator<Item = &str> {
+        self.0
+            .iter()
+            .filter(|(_, state)| *state == State::Failed)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// A compact description, like `2 passed, 1 running`.
+    fn status(&self) -> String {
+        let count = |wanted: State| self.0.iter().filter(|(_, state)| *state == wanted).count();
+        [
+            (State::Failed, "failed"),
+            (State::Running, "running"),
+            (State::Pending, "pending"),
+            (State::Passed, "passed"),
+        ]
+        .into_iter()
+        .map(|(state, label)| (count(state), label))
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, label)| format!("{count} {label}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+/// Watch CI for `branch`, which was just pushed to `remote`, until it finishes or
+/// `--watch-ci-timeout` runs out.
+///
+/// Failing to watch CI is only a warning, because the push itself succeeded. With
+/// `--watch-ci=gate`, CI failing is a [`CiFailed`] error.
+pub fn watch(config: &Config, mode: WatchCi, branch: &str, remote: &str) -> miette::Result<()> {
+    let manual_url = config
+        .remote_url(remote)?
+        .and_then(|url| url.web(&config.file.host_templates).ci(branch));
+    let check_manually = match &manual_url {
+        Some(url) => format!("; check {url}"),
+        None => String::new(),
+    };
+
+    let Some(tool) = pr::tool(config, remote)? else {
+        tracing::warn!("Don't know how to watch CI on {remote}{check_manually}");
+        return Ok(());
+    };
+    if !tool.installed() {
+        tracing::warn!(
+            "`--watch-ci` needs `{}`, which isn't installed{check_manually}",
+            tool.program()
+        );
+        return Ok(());
+    }
+    let Some(commit) = git::rev_parse(&format!("refs/heads/{branch}"))? else {
+        return Ok(());
+    };
+
+    let timeout = config.watch_ci_timeout();
+    let start = Instant::now();
+    let mut last_status = None;
+    tracing::info!("Watching CI for {branch}");
+    let runs = loop {
+        match runs(tool, &commit) {
+            Ok(runs) if runs.0.is_empty() && start.elapsed() > START_TIMEOUT => {
+                tracing::info!("No CI started for {branch}");
+                return Ok(());
+            }
+            Ok(runs) if runs.0.is_empty() => {}
+            Ok(runs) => {
+                let status = runs.status();
+                if mode != WatchCi::Summary && last_status.as_ref() != Some(&status) {
+                    tracing::info!("CI: {status}");
+                }
+                last_status = Some(status);
+                if runs.finished() {
+                    break runs;
+                }
+            }
+            Err(err) => {
+                tracing::warn!("Failed to check CI for {branch}{check_manually}: {err}");
+                return Ok(());
+            }
+        }
+
+        if start.elapsed() + POLL_INTERVAL > timeout {
+            tracing::warn!(
+                "Stopped watching CI for {branch} after {} seconds{check_manually}",
+                timeout.as_secs()
+            );
+            return Ok(());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    let failed: Vec<_> = runs.failed().collect();
+    if failed.is_empty() {
+        tracing::info!("CI passed for {branch}");
+        return Ok(());
+    }
+    let message = format!("CI failed for {branch}: {}", failed.join(", "));
+    if mode == WatchCi::Gate {
+        Err(CiFailed {
+            message,
+            url: manual_url,
+        }
+        .into())
+    } else {
+        match &manual_url {
+            Some(url) => tracing::warn!("{message}; see {url}"),
+            None => tracing::warn!("{message}"),
+        }
+        Ok(())
+    }
+}
+
+/// Get the CI runs for `commit`.
+fn runs(tool: PrTool, commit: &str) -> miette::Result<Runs> {
+    let mut command = git::repo_command(tool.program());
+    match tool {
+        PrTool::Gh => command
+            .args(["run", "list", "--commit", commit, "--json"])
+            .args(["name,status,conclusion", "--jq"])
+            .arg(r#".[] | "\(.status) \(.conclusion) \(.name)""#),
+        PrTool::Glab => command
+            .arg("api")
+            .arg(format!("projects/:id/pipelines?sha={commit}&per_page=1")),
+    };
+    let output = command
+        .stdin(Stdio::null())
+        .output_checked_utf8()
+        .into_diagnostic()?;
+
+    Ok(Runs(match tool {
+        PrTool::Gh => output
+            .stdout
+            .lines()
+            .filter_map(|line| {
+                let (status, rest) = line.split_once(' ')?;
+                let (conclusion, name) = rest.split_once(' ')?;
+                Some((name.to_owned(), State::from_github(status, conclusion)))
+            })
+            .collect(),
+        // Just the latest pipeline, which covers all of the commit's jobs.
+        PrTool::Glab => output
+            .stdout
+            .split_once(r#""status":"#)
+            .and_then(|(_, rest)| rest.split('"').nth(1))
+            .map(|status| ("pipeline".to_owned(), State::from_gitlab(status)))
+            .into_iter()
+            .collect(),
+    }))
+}
+
+/// An error for when CI failed with `--watch-ci=gate`.
+#[derive(Debug)]
+pub struct CiFailed {
+    message: String,
+    url: Option<String>,
+}
+
+impl Display for CiFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CiFailed {}
+
+impl Diagnostic for CiFailed {
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.url
+            .as_ref()
+            .map(|url| Box::new(format!("See {url}")) as Box<dyn Display>)
+    }
+}
@@ -0,0 +1,107 @@
+//! Copying text to the system clipboard, for `--copy-url`.
+
+use std::io::IsTerminal;
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+
+use command_error::ChildExt;
+use command_error::CommandExt;
+use miette::miette;
+use miette::IntoDiagnostic;
+
+/// Copy `text` to the clipboard, with `pbcopy` on macOS, `clip` on Windows, and `wl-copy`,
+/// `xclip`, or `xsel` elsewhere.
+///
+/// Over SSH without a display, or if none of those work, this asks the terminal to copy it with
+/// an OSC 52 escape sequence instead, which most terminals support.
+pub fn copy(text: &str) -> miette::Result<()> {
+    // Over SSH, the local clipboard is the wrong one, unless X11 is forwarded.
+    let over_ssh = std::env::var_os("SSH_CONNECTION").is_some();
+    let has_display = (cfg!(any(target_os = "macos", windows)) && !over_ssh)
+        || std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var_os("DISPLAY").is_some();
+
+    if has_display {
+        for command in commands() {
+            match pipe(command, text) {
+                Ok(()) => return Ok(()),
+                Err(err) => tracing::debug!("Failed to copy to the clipboard: {err}"),
+            }
+        }
+    }
+
+    osc52(text)
+}
+
+/// The commands which copy their stdin to the clipboard, in the order to try them.
+fn commands() -> Vec<Command> {
+    let command = |program: &str, args: &[&str]| {
+        let mut command = Command::new(program);
+        command.args(args);
+        command
+    };
+    if cfg!(target_os = "macos") {
+        vec![command("pbcopy", &[])]
+    } else if cfg!(windows) {
+        vec![command("clip", &[])]
+    } else {
+        let mut commands = Vec::new();
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.push(command("wl-copy", &[]));
+        }
+        commands.push(command("xclip", &["-selection", "clipboard"]));
+        commands.push(command("xsel", &["--clipboard", "--input"]));
+        commands
+    }
+}
+
+/// Run `command` with `text` on its stdin.
+fn pipe(mut command: Command, text: &str) -> miette::Result<()> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn_checked()
+        .into_diagnostic()?;
+    if let Some(mut stdin) = child.child_mut().stdin.take() {
+        stdin.write_all(text.as_bytes()).into_diagnostic()?;
+    }
+    child.wait_checked().into_diagnostic()?;
+    Ok(())
+}
+
+/// Ask the terminal on stderr to copy `text` with an OSC 52 escape sequence.
+///
+/// There's no way to tell if the terminal supports it, so this only fails if stderr isn't a
+/// terminal.
+fn osc52(text: &str) -> miette::Result<()> {
+    let mut stderr = std::io::stderr();
+    if !stderr.is_terminal() {
+        return Err(miette!(
+            "No clipboard command worked, and stderr isn't a terminal to copy with"
+        ));
+    }
+    write!(stderr, "\x1b]52;c;{}\x07", base64(text.as_bytes())).into_diagnostic()?;
+    stderr.flush().into_diagnostic()
+}
+
+/// Encode `bytes` as standard, padded base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,98 @@
+//! Deciding whether to color output, for `--color`.
+
+use std::fmt::Display;
+use std::io::IsTerminal;
+
+/// When to color output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color output written to a terminal, unless `NO_COLOR` is set. `CLICOLOR_FORCE` forces
+    /// color on.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color output on stderr.
+    pub fn stderr_enabled(self) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                let is_set = |var| std::env::var_os(var).is_some_and(|value| !value.is_empty());
+                if is_set("NO_COLOR") {
+                    false
+                } else if std::env::var_os("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+                    true
+                } else {
+                    std::env::var_os("TERM").is_none_or(|term| term != "dumb")
+                        && std::io::stderr().is_terminal()
+                }
+            }
+        }
+    }
+
+    /// Make `owo-colors` and `miette` follow this choice.
+    ///
+    /// The logging layer is configured separately, in [`crate::Cli::install_tracing`].
+    pub fn install(self) -> miette::Result<()> {
+        // With `auto`, `owo-colors` checks each stream separately, so output piped from stdout
+        // isn't colored just because stderr is a terminal.
+        match self {
+            Self::Always => owo_colors::set_override(true),
+            Self::Never => owo_colors::set_override(false),
+            Self::Auto => {}
+        }
+
+        let color = self.stderr_enabled();
+        miette::set_hook(Box::new(move |_| {
+            Box::new(miette::MietteHandlerOpts::new().color(color).build())
+        }))?;
+        Ok(())
+    }
+}
+
+/// Make `text` a link to `url` with an OSC 8 escape sequence, which terminals which don't support
+/// it display as plain text.
+pub fn hyperlink(text: impl Display, url: &str) -> String {
+    format!("\x1b]8;;{url}\x1b\\{text}\x1b]8;;\x1b\\")
+}
+
+/// Remove ANSI escape sequences from `text`, like colors from `owo-colors` and hyperlinks from
+/// [`hyperlink`].
+pub fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI sequences, like colors: `ESC [ ... <final byte>`.
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC sequences, like hyperlinks: `ESC ] ... ESC \` or `... BEL`.
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
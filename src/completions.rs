@@ -0,0 +1,224 @@
+//! Shell completions.
+//!
+//! The generated shell scripts call back into `git-upstream __complete` to complete each word,
+//! so that remote and branch names come from the repository being worked in.
+
+use clap::CommandFactory;
+use command_error::CommandExt;
+
+use crate::git;
+use crate::Cli;
+
+/// A shell to generate completions for.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Print a completion script for `git-upstream`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct CompletionsArgs {
+    /// The shell to generate completions for.
+    shell: Shell,
+}
+
+/// Complete a partially-typed command line. Used by the completion scripts.
+#[derive(Debug, Clone, clap::Args)]
+pub struct CompleteArgs {
+    /// The words after `git-upstream`, ending with the (possibly empty) word being completed.
+    #[arg(last = true)]
+    words: Vec<String>,
+}
+
+/// Run `git upstream completions`.
+pub fn completions(args: &CompletionsArgs) {
+    let script = match args.shell {
+        Shell::Bash => BASH,
+        Shell::Zsh => ZSH,
+        Shell::Fish => FISH,
+    };
+    print!("{script}");
+}
+
+/// Run `git upstream __complete`, printing one candidate per line.
+pub fn complete(args: &CompleteArgs) {
+    for candidate in candidates(&args.words) {
+        println!("{candidate}");
+    }
+}
+
+fn candidates(words: &[String]) -> Vec<String> {
+    let mut command = Cli::command();
+    command.build();
+
+    let (current, previous) = match words.split_last() {
+        Some((current, previous)) => (current.as_str(), previous),
+        None => ("", &[][..]),
+    };
+
+    // Find the subcommand being completed.
+    let mut positionals = 0;
+    let mut skip_value = false;
+    for word in previous {
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        if word == "--" {
+            // Everything after `--` goes to `git push`.
+            return Vec::new();
+        }
+        if word.starts_with('-') {
+            skip_value = !word.contains('=')
+                && find_option(&command, word).is_some_and(|arg| arg.get_action().takes_values());
+            continue;
+        }
+        let subcommand = (positionals == 0)
+            .then(|| command.find_subcommand(word).cloned())
+            .flatten();
+        match subcommand {
+            Some(subcommand) => command = subcommand,
+            None => positionals += 1,
+        }
+    }
+
+    let mut ret = Vec::new();
+    let mut prefix = "";
+    let mut partial = current;
+
+    if skip_value {
+        // Completing the value of the previous option.
+        if let Some(arg) = previous.last().and_then(|word| find_option(&command, word)) {
+            ret = values(arg);
+        }
+    } else if let Some((option, value)) = current
+        .split_once('=')
+        .filter(|_| current.starts_with("--"))
+    {
+        if let Some(arg) = find_option(&command, option) {
+            ret = values(arg);
+            prefix = option;
+            partial = value;
+        }
+    } else if current.starts_with('-') {
+        for arg in command.get_arguments().filter(|arg| !arg.is_hide_set()) {
+            if let Some(long) = arg.get_long() {
+                ret.push(format!("--{long}"));
+            }
+            ret.extend(
+                arg.get_visible_aliases()
+                    .into_iter()
+                    .flatten()
+                    .map(|alias| format!("--{alias}")),
+            );
+            if let Some(short) = arg.get_short() {
+                ret.push(format!("-{short}"));
+            }
+        }
+    } else {
+        if positionals == 0 {
+            ret.extend(
+                command
+                    .get_subcommands()
+                    .filter(|subcommand| !subcommand.is_hide_set())
+                    .map(|subcommand| subcommand.get_name().to_owned()),
+            );
+        }
+        if let Some(arg) = command
+            .get_positionals()
+            .filter(|arg| !arg.is_last_set())
+            .nth(positionals)
+        {
+            ret.extend(values(arg));
+        }
+    }
+
+    ret.into_iter()
+        .filter(|candidate| candidate.starts_with(partial))
+        .map(|candidate| {
+            if prefix.is_empty() {
+                candidate
+            } else {
+                format!("{prefix}={candidate}")
+            }
+        })
+        .collect()
+}
+
+/// Find the option named by `word`, like `--branch` or `-f`.
+fn find_option<'a>(command: &'a clap::Command, word: &str) -> Option<&'a clap::Arg> {
+    let word = word
+        .split_once('=')
+        .map(|(option, _)| option)
+        .unwrap_or(word);
+    command.get_arguments().find(|arg| {
+        if let Some(long) = word.strip_prefix("--") {
+            arg.get_long() == Some(long)
+                || arg
+                    .get_all_aliases()
+                    .is_some_and(|aliases| aliases.contains(&long))
+        } else if let Some(short) = word.strip_prefix('-') {
+            let mut chars = short.chars();
+            chars.next().is_some_and(|c| arg.get_short() == Some(c)) && chars.next().is_none()
+        } else {
+            false
+        }
+    })
+}
+
+/// Get the values an argument can take.
+fn values(arg: &clap::Arg) -> Vec<String> {
+    let possible_values = arg.get_possible_values();
+    if !possible_values.is_empty() {
+        return possible_values
+            .into_iter()
+            .filter(|value| !value.is_hide_set())
+            .map(|value| value.get_name().to_owned())
+            .collect();
+    }
+
+    match arg.get_id().as_str() {
+        "remote" => git_lines(&["remote"]),
+        "branch" | "rename" => {
+            git_lines(&["for-each-ref", "--format=%(refname:short)", "refs/heads"])
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Run a `git` command and get its output lines, or nothing if it fails (for example, outside
+/// of a Git repository).
+fn git_lines(args: &[&str]) -> Vec<String> {
+    git::command()
+        .args(args)
+        .output_checked_utf8()
+        .map(|output| output.stdout.lines().map(ToOwned::to_owned).collect())
+        .unwrap_or_default()
+}
+
+const BASH: &str = r#"_git_upstream_complete() {
+    local IFS=$'\n'
+    COMPREPLY=($(git-upstream __complete -- "${COMP_WORDS[@]:1:COMP_CWORD}" 2>/dev/null))
+}
+complete -o default -o nosort -F _git_upstream_complete git-upstream
+"#;
+
+const ZSH: &str = r#"#compdef git-upstream
+_git_upstream_complete() {
+    local -a candidates
+    candidates=("${(@f)$(git-upstream __complete -- "${(@)words[2,$CURRENT]}" 2>/dev/null)}")
+    compadd -Q -- "${(@)candidates:#}"
+}
+if [ "$funcstack[1]" = "_git-upstream" ]; then
+    _git_upstream_complete "$@"
+else
+    compdef _git_upstream_complete git-upstream
+fi
+"#;
+
+const FISH: &str = r#"function __git_upstream_complete
+    git-upstream __complete -- (commandline -opc)[2..-1] (commandline -ct) 2>/dev/null
+end
+complete -c git-upstream -f -a '(__git_upstream_complete)'
+"#;
@@ -0,0 +1,907 @@
+//! `git upstream config`, for managing configuration files.
+
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+use command_error::CommandExt;
+use fs_err as fs;
+use miette::miette;
+use miette::IntoDiagnostic;
+use serde::Serialize;
+
+use crate::capabilities::Signed;
+use crate::dirty::WarnDirty;
+use crate::git;
+use crate::json;
+use crate::pr::PrTool;
+use crate::prompt::confirm;
+use crate::remote_url::Forge;
+use crate::sync::SyncStrategy;
+use crate::ticket;
+use crate::wip;
+use crate::Config;
+use crate::ConfigFile;
+
+/// A configuration file with every setting commented out, used for new configuration files.
+pub const TEMPLATE: &str = r#"# Configuration for `git-upstream`.
+#
+# Settings in a repository's `.git/git-upstream.toml` override the user
+# configuration file for that repository.
+
+# Remote names to attempt to push to, highest preference first. Defaults to
+# `origin`; remotes not listed are tried afterwards.
+# remotes = ["fork", "origin"]
+
+# Glob patterns for branches which `git upstream --delete` and `git upstream
+# prune` refuse to delete, and which need their name typed out to force-push.
+# protected-branches = ["main", "master"]
+
+# How `git upstream --sync` integrates remote changes before pushing:
+# "rebase" or "ff-only".
+# sync-strategy = "rebase"
+
+# Allow pushing the remote's default branch (the branch `origin/HEAD` points
+# to). By default, `git-upstream` refuses.
+# allow-default-branch = false
+
+# Ask before force-pushing with `--force` or `--force-unchecked`, like leaving
+# out `--yes`. Protected branches need their name typed out.
+# confirm-force = true
+
+# Fetch the branch before force-pushing, so `--force-with-lease` checks against
+# the remote branch as it is now, like `--fetch-first`.
+# fetch-first = false
+
+# What to do about uncommitted changes before pushing: "off", "warn", or
+# "block" (refuse, unless `--allow-dirty` is given).
+# warn-dirty = "warn"
+
+# Count untracked files as uncommitted changes for `warn-dirty`.
+# warn-dirty-untracked = false
+
+# Refuse to push `fixup!`, `squash!`, `amend!`, and work-in-progress commits
+# (unless `--allow-wip` is given).
+# check-wip = true
+
+# Regexes for the subjects of work-in-progress commits.
+# wip-patterns = ["(?i)^wip\\b"]
+
+# Refuse to push commits which add lines which look like secrets (AWS access
+# keys, private keys, and GitHub tokens), like `--scan-secrets`. Lines with
+# `git-upstream: allow-secret` are skipped.
+# scan-secrets = false
+
+# Glob patterns for files `scan-secrets` skips, like test fixtures.
+# secret-allow-paths = ["tests/fixtures/*"]
+
+# Glob patterns for branches which are never pushed. Pushing one fails unless
+# `--allow-never-push` is given, and `--stack` and `--stdin` skip them.
+# never-push = ["tmp/*", "private/*", "wip-*"]
+
+# Sign pushes, like `--signed`: true, false, or "if-asked" to only sign if the
+# remote supports it. Unset, Git uses `push.gpgSign`.
+# signed = "if-asked"
+
+# Update the branch and any notes refs all at once or not at all, like
+# `--atomic`.
+# atomic = false
+
+# Check the files the outgoing commits add for ones which belong in Git LFS.
+# Files larger than `warn-blob-size` are warned about, and files larger than
+# `max-blob-size` aren't pushed unless `--allow-large-files` is given. Sizes
+# are in bytes, or with a `k`, `m`, or `g` suffix.
+# check-large-files = true
+# warn-blob-size = "10m"
+# max-blob-size = "100m"
+
+# Push `refs/notes/commits` alongside the branch, like `--notes`.
+# push-notes = false
+
+# Open the page to create a pull request after pushing, like `--open`.
+# open = false
+
+# Copy the URL to open a pull request to the clipboard after pushing, like
+# `--copy-url`.
+# copy-url = false
+
+# Append debug logs, with timestamps, to this file, like `--log-file`.
+# log-file = "/tmp/git-upstream.log"
+
+# Send a desktop notification when a slow push finishes, like `--notify`:
+# "always", "on-failure", or "never".
+# notify = "never"
+
+# How many seconds a push must take before `notify` sends a notification.
+# notify-after = 10
+
+# How many seconds to let `git push` run before killing it and trying the next
+# remote, like `--timeout`.
+# timeout = 60
+
+# How many seconds `--watch-ci` follows CI for, like `--watch-ci-timeout`.
+# watch-ci-timeout = 1800
+
+# The tool `--pr` creates pull requests with: "gh" or "glab". By default, this
+# is guessed from the remote's host.
+# pr-tool = "gh"
+
+# Hosts running GitLab, for `--mr` and `--pr`, in addition to `gitlab.com` and
+# hosts named `gitlab.*`.
+# gitlab-hosts = ["git.example.com"]
+
+# A regex for ticket IDs in branch names. With `--pr` and `--mr`, the first
+# capture group which matches is referenced in the description.
+# ticket-pattern = "([A-Z]+-[0-9]+)"
+
+# The text to reference a ticket with, where `{ticket}` is the ticket ID.
+# ticket-template = "Refs {ticket}"
+
+# How to build web URLs for self-hosted forges, by host (or `host:port`). Set
+# `kind` to the forge the host runs ("github", "gitlab", "bitbucket", "gitea",
+# or "sourcehut"), and override its URLs with templates, which can use
+# `{host}`, `{path}`, `{branch}`, and `{default-branch}`.
+# [host-templates]
+# "git.example.com" = { kind = "gitlab" }
+# "git.corp.example" = { kind = "gitlab", repo-url = "https://git.corp.example:8443/x/{path}" }
+
+# More kinds of secrets for `scan-secrets` to look for, as regexes by name.
+# [secret-patterns]
+# "Slack token" = "xox[baprs]-[0-9A-Za-z-]{10,}"
+
+# Override `signed` and `atomic` for particular remotes, by name.
+# [remote-settings.fork]
+# signed = "false"
+"#;
+
+/// Manage configuration files.
+#[derive(Debug, Clone, clap::Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+enum ConfigCommand {
+    /// Open the configuration file in `$VISUAL` or `$EDITOR`, and check it after editing.
+    ///
+    /// The file is created from a commented template if it doesn't exist.
+    Edit(EditArgs),
+    /// Print a JSON Schema for the configuration file, for editors to validate it with.
+    Schema(SchemaArgs),
+}
+
+#[derive(Debug, Clone, clap::Args)]
+struct EditArgs {
+    /// Edit the repository's `git-upstream.toml` instead of the user configuration file.
+    #[arg(long)]
+    repo: bool,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+struct SchemaArgs {
+    /// Write the schema to this file instead of stdout.
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+/// Run `git upstream config`.
+pub fn config(config: &Config, args: &ConfigArgs) -> miette::Result<()> {
+    match &args.command {
+        ConfigCommand::Edit(args) => edit(config, args),
+        ConfigCommand::Schema(args) => schema(args),
+    }
+}
+
+fn edit(config: &Config, args: &EditArgs) -> miette::Result<()> {
+    if !std::io::stdin().is_terminal() {
+        return Err(miette!(
+            "Refusing to open an editor because stdin isn't a terminal"
+        ));
+    }
+
+    let editor = editor()?;
+
+    let path = if args.repo {
+        git::common_dir()?
+            .ok_or_else(|| miette!("Not in a Git repository"))?
+            .join("git-upstream.toml")
+    } else {
+        match (&config.cli.config, &config.dirs) {
+            (Some(path), _) => path.clone(),
+            (None, Some(dirs)) => dirs.place_config_file("config.toml").into_diagnostic()?,
+            (None, None) => {
+                return Err(miette!(
+                    help = "Pass `--config PATH`, or `--repo` to edit the repository's \
+                            configuration file",
+                    "Couldn't find the configuration directory, because the home directory \
+                     is unknown"
+                ))
+            }
+        }
+    };
+
+    if !path.exists() {
+        tracing::info!("Creating {} from a template", path.display());
+        fs::write(&path, TEMPLATE).into_diagnostic()?;
+    }
+
+    loop {
+        run_editor(&editor, &path)?;
+
+        let contents = fs::read_to_string(&path).into_diagnostic()?;
+        match ConfigFile::parse(&path, contents) {
+            Ok(_) => return Ok(()),
+            Err(err) => {
+                eprintln!("{err:?}");
+                if !confirm("Re-open the editor to fix it?", "--yes")? {
+                    return Err(miette!("{} is invalid", path.display()));
+                }
+            }
+        }
+    }
+}
+
+/// Get the user's editor from `$VISUAL` or `$EDITOR`, like Git does.
+fn editor() -> miette::Result<String> {
+    ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .ok_or_else(|| {
+            miette!(
+                help = "For example, `export EDITOR=vim`",
+                "Can't open an editor because neither `$VISUAL` nor `$EDITOR` is set"
+            )
+        })
+}
+
+#[cfg(not(windows))]
+fn run_editor(editor: &str, path: &Path) -> miette::Result<()> {
+    // Run the editor through the shell so that editors with arguments, like `code --wait`,
+    // work. The path is passed as a positional parameter so it doesn't need to be quoted.
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$@\""))
+        .arg(editor)
+        .arg(path)
+        .status_checked()
+        .into_diagnostic()?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn run_editor(editor: &str, path: &Path) -> miette::Result<()> {
+    // There's no `sh` on native Windows, so split the editor into words ourselves.
+    let words = editor_words(editor);
+    let (program, args) = words
+        .split_first()
+        .ok_or_else(|| miette!("`$VISUAL` or `$EDITOR` is empty"))?;
+    Command::new(program)
+        .args(args)
+        .arg(path)
+        .status_checked()
+        .into_diagnostic()?;
+    Ok(())
+}
+
+/// Split an editor command like `"C:\Program Files\Vim\vim.exe" -f` into words.
+///
+/// Words are separated by whitespace and can be quoted with `"` or `'`. Backslashes are kept
+/// as-is, because they're path separators on Windows.
+#[cfg(any(windows, test))]
+fn editor_words(editor: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quote = None;
+    for c in editor.chars() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => word.get_or_insert_with(String::new).push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => words.extend(word.take()),
+            None => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+fn schema(args: &SchemaArgs) -> miette::Result<()> {
+    let schema = json::to_string_pretty(&Schema::new()).into_diagnostic()?;
+    match &args.output {
+        Some(path) => fs::write(path, format!("{schema}\n")).into_diagnostic(),
+        None => {
+            println!("{schema}");
+            Ok(())
+        }
+    }
+}
+
+/// A JSON Schema for [`ConfigFile`].
+///
+/// This is written by hand, so it needs to be updated along with [`ConfigFile`] and
+/// [`TEMPLATE`].
+#[derive(Serialize)]
+struct Schema {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    title: &'static str,
+    #[serde(flatten)]
+    object: Property,
+    properties: BTreeMap<&'static str, Property>,
+    #[serde(rename = "additionalProperties")]
+    additional_properties: bool,
+}
+
+#[derive(Serialize)]
+struct Property {
+    description: &'static str,
+    #[serde(rename = "type")]
+    ty: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    items: Option<Box<Property>>,
+    #[serde(rename = "enum", skip_serializing_if = "Option::is_none")]
+    values: Option<&'static [&'static str]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default: Option<toml::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    properties: Option<BTreeMap<&'static str, Property>>,
+    #[serde(
+        rename = "additionalProperties",
+        skip_serializing_if = "Option::is_none"
+    )]
+    additional_properties: Option<Box<Property>>,
+}
+
+impl Property {
+    fn new(ty: &'static str, description: &'static str) -> Self {
+        Self {
+            description,
+            ty,
+            items: None,
+            values: None,
+            default: None,
+            properties: None,
+            additional_properties: None,
+        }
+    }
+
+    fn array(items: Self, description: &'static str) -> Self {
+        Self {
+            items: Some(Box::new(items)),
+            ..Self::new("array", description)
+        }
+    }
+
+    /// An object with the given properties.
+    fn object(
+        properties: impl IntoIterator<Item = (&'static str, Self)>,
+        description: &'static str,
+    ) -> Self {
+        Self {
+            properties: Some(properties.into_iter().collect()),
+            ..Self::new("object", description)
+        }
+    }
+
+    /// An object whose keys are names, mapping each to a `values`.
+    fn map(values: Self, description: &'static str) -> Self {
+        Self {
+            additional_properties: Some(Box::new(values)),
+            ..Self::new("object", description)
+        }
+    }
+
+    fn values(mut self, values: &'static [&'static str]) -> Self {
+        self.values = Some(values);
+        self
+    }
+
+    fn default(mut self, default: impl Into<toml::Value>) -> Self {
+        self.default = Some(default.into());
+        self
+    }
+}
+
+impl Schema {
+    fn new() -> Self {
+        let properties = BTreeMap::from([
+            (
+                "remotes",
+                Property::array(
+                    Property::new("string", "A remote name."),
+                    "Remotes to attempt to push to, highest preference first. Remotes not listed \
+                     are tried afterwards.",
+                )
+                .default(vec!["origin"]),
+            ),
+            (
+                "protected-branches",
+                Property::array(
+                    Property::new("string", "A glob pattern, where `*` matches anything."),
+                    "Glob patterns for branches which `git upstream --delete` and `git upstream \
+                     prune` refuse to delete.",
+                )
+                .default(vec!["main", "master"]),
+            ),
+            (
+                "sync-strategy",
+                Property::new(
+                    "string",
+                    "How `git upstream --sync` integrates remote changes before pushing.",
+                )
+                .values(SyncStrategy::NAMES)
+                .default("rebase"),
+            ),
+            (
+                "allow-default-branch",
+                Property::new(
+                    "boolean",
+                    "Allow pushing the remote's default branch (the branch `origin/HEAD` points \
+                     to).",
+                )
+                .default(false),
+            ),
+            (
+                "confirm-force",
+                Property::new(
+                    "boolean",
+                    "Ask before force-pushing with `--force` or `--force-unchecked`.",
+                )
+                .default(true),
+            ),
+            (
+                "fetch-first",
+                Property::new(
+                    "boolean",
+                    "Fetch the branch before force-pushing, like `--fetch-first`.",
+                )
+                .default(false),
+            ),
+            (
+                "warn-dirty",
+                Property::new(
+                    "string",
+                    "What to do about uncommitted changes before pushing; `block` refuses, \
+                     unless `--allow-dirty` is given.",
+                )
+                .values(WarnDirty::NAMES)
+                .default("warn"),
+            ),
+            (
+                "warn-dirty-untracked",
+                Property::new(
+                    "boolean",
+                    "Count untracked files as uncommitted changes for `warn-dirty`.",
+                )
+                .default(false),
+            ),
+            (
+                "check-wip",
+                Property::new(
+                    "boolean",
+                    "Refuse to push `fixup!`, `squash!`, `amend!`, and work-in-progress commits, \
+                     unless `--allow-wip` is given.",
+                )
+                .default(true),
+            ),
+            (
+                "wip-patterns",
+                Property::array(
+                    Property::new("string", "A regex."),
+                    "Regexes for the subjects of work-in-progress commits.",
+                )
+                .default(wip::DEFAULT_PATTERNS.to_vec()),
+            ),
+            (
+                "scan-secrets",
+                Property::new(
+                    "boolean",
+                    "Refuse to push commits which add lines which look like secrets, like \
+                     `--scan-secrets`.",
+                )
+                .default(false),
+            ),
+            (
+                "secret-patterns",
+                Property::map(
+                    Property::new("string", "A regex."),
+                    "More kinds of secrets for `scan-secrets` to look for, as regexes by name.",
+                ),
+            ),
+            (
+                "secret-allow-paths",
+                Property::array(
+                    Property::new("string", "A glob pattern, where `*` matches anything."),
+                    "Glob patterns for files `scan-secrets` skips, like test fixtures.",
+                ),
+            ),
+            (
+                "never-push",
+                Property::array(
+                    Property::new("string", "A glob pattern, where `*` matches anything."),
+                    "Glob patterns for branches which are never pushed, unless \
+                     `--allow-never-push` is given. `--stack` and `--stdin` skip them.",
+                ),
+            ),
+            (
+                "signed",
+                Property::new(
+                    "string",
+                    "Sign pushes, like `--signed`: `true`, `false`, or `if-asked` to only sign if \
+                     the remote supports it.",
+                )
+                .values(Signed::NAMES),
+            ),
+            (
+                "atomic",
+                Property::new(
+                    "boolean",
+                    "Update the branch and any notes refs all at once or not at all, like \
+                     `--atomic`.",
+                )
+                .default(false),
+            ),
+            (
+                "check-large-files",
+                Property::new(
+                    "boolean",
+                    "Check the files the outgoing commits add for ones larger than \
+                     `warn-blob-size` and `max-blob-size`.",
+                )
+                .default(true),
+            ),
+            (
+                "warn-blob-size",
+                Property::new(
+                    "string",
+                    "Warn about files larger than this, in bytes or with a `k`, `m`, or `g` \
+                     suffix.",
+                )
+                .default("10m"),
+            ),
+            (
+                "max-blob-size",
+                Property::new(
+                    "string",
+                    "Refuse to push files larger than this, unless `--allow-large-files` is \
+                     given. In bytes or with a `k`, `m`, or `g` suffix.",
+                )
+                .default("100m"),
+            ),
+            (
+                "push-notes",
+                Property::new(
+                    "boolean",
+                    "Push `refs/notes/commits` alongside the branch, like `--notes`.",
+                )
+                .default(false),
+            ),
+            (
+                "open",
+                Property::new(
+                    "boolean",
+                    "Open the page to create a pull request after pushing, like `--open`.",
+                )
+                .default(false),
+            ),
+            (
+                "copy-url",
+                Property::new(
+                    "boolean",
+                    "Copy the URL to open a pull request to the clipboard after pushing, like \
+                     `--copy-url`.",
+                )
+                .default(false),
+            ),
+            (
+                "log-file",
+                Property::new(
+                    "string",
+                    "Append debug logs, with timestamps, to this file, like `--log-file`.",
+                ),
+            ),
+            (
+                "notify",
+                Property::new(
+                    "string",
+                    "Send a desktop notification when a slow push finishes, like `--notify`.",
+                )
+                .values(&["always", "on-failure", "never"])
+                .default("never"),
+            ),
+            (
+                "notify-after",
+                Property::new(
+                    "integer",
+                    "How many seconds a push must take before `notify` sends a notification.",
+                )
+                .default(10),
+            ),
+            (
+                "timeout",
+                Property::new(
+                    "integer",
+                    "How many seconds to let `git push` run before killing it and trying the next \
+                     remote, like `--timeout`.",
+                ),
+            ),
+            (
+                "watch-ci-timeout",
+                Property::new(
+                    "integer",
+                    "How many seconds `--watch-ci` follows CI for, like `--watch-ci-timeout`.",
+                )
+                .default(1800),
+            ),
+            (
+                "pr-tool",
+                Property::new(
+                    "string",
+                    "The tool `--pr` creates pull requests with, instead of guessing from the \
+                     remote's host.",
+                )
+                .values(PrTool::NAMES),
+            ),
+            (
+                "gitlab-hosts",
+                Property::array(
+                    Property::new("string", "A hostname, like `git.example.com`."),
+                    "Hosts running GitLab, for `--mr` and `--pr`, in addition to `gitlab.com` \
+                     and hosts named `gitlab.*`.",
+                ),
+            ),
+            (
+                "ticket-pattern",
+                Property::new(
+                    "string",
+                    "A regex for ticket IDs in branch names. With `--pr` and `--mr`, the first \
+                     capture group which matches is referenced in the description.",
+                ),
+            ),
+            (
+                "ticket-template",
+                Property::new(
+                    "string",
+                    "The text to reference a ticket with, where `{ticket}` is the ticket ID.",
+                )
+                .default(ticket::DEFAULT_TEMPLATE),
+            ),
+            (
+                "host-templates",
+                Property::map(
+                    Property::object(
+                        [
+                            (
+                                "kind",
+                                Property::new("string", "The forge the host runs.")
+                                    .values(Forge::NAMES),
+                            ),
+                            (
+                                "repo-url",
+                                Property::new(
+                                    "string",
+                                    "A template for the repository's main page, with `{host}` \
+                                     and `{path}`.",
+                                ),
+                            ),
+                            (
+                                "branch-url",
+                                Property::new(
+                                    "string",
+                                    "A template for a branch's files, with `{host}`, `{path}`, \
+                                     and `{branch}`.",
+                                ),
+                            ),
+                            (
+                                "compare-url",
+                                Property::new(
+                                    "string",
+                                    "A template for the page to open a pull request, with \
+                                     `{host}`, `{path}`, `{branch}`, and `{default-branch}`.",
+                                ),
+                            ),
+                        ],
+                        "How to build web URLs for the host's repositories.",
+                    ),
+                    "How to build web URLs for self-hosted forges, by host (like \
+                     `git.example.com`) or host and port (like `git.example.com:2222`).",
+                ),
+            ),
+            (
+                "remote-settings",
+                Property::map(
+                    Property::object(
+                        [
+                            (
+                                "signed",
+                                Property::new("string", "Overrides `signed` for the remote.")
+                                    .values(Signed::NAMES),
+                            ),
+                            (
+                                "atomic",
+                                Property::new("boolean", "Overrides `atomic` for the remote."),
+                            ),
+                        ],
+                        "Settings for the remote.",
+                    ),
+                    "Overrides for `signed` and `atomic`, by remote name.",
+                ),
+            ),
+        ]);
+
+        Self {
+            schema: "https://json-schema.org/draft/2020-12/schema",
+            title: "git-upstream configuration",
+            object: Property::new(
+                "object",
+                "Configuration for `git-upstream`, in `~/.config/git-upstream/config.toml` or \
+                 `.git/git-upstream.toml`.",
+            ),
+            properties,
+            additional_properties: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use serde::de::value::Error;
+    use serde::de::Visitor;
+    use serde::Deserialize;
+    use serde::Deserializer;
+
+    use super::*;
+
+    /// A deserializer which records the fields a derived `Deserialize` implementation asks for.
+    struct FieldNames<'a>(&'a Cell<&'static [&'static str]>);
+
+    impl<'de> Deserializer<'de> for FieldNames<'_> {
+        type Error = Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+            Err(serde::de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Error> {
+            self.0.set(fields);
+            Err(serde::de::Error::custom("only recording the fields"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+            ignored_any
+        }
+    }
+
+    /// Uncomment the settings in [`TEMPLATE`], leaving the explanations commented out.
+    fn uncommented_template() -> String {
+        let setting =
+            regex::Regex::new(r#"^# ([a-z][a-z-]* = |"[^"]+" = |\[[a-z.-]+\]$)"#).unwrap();
+        TEMPLATE
+            .lines()
+            .map(|line| match setting.is_match(line) {
+                true => &line[2..],
+                false => line,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Check that `value` at `key` matches `property`, like a JSON Schema validator would.
+    fn check(key: &str, value: &toml::Value, property: &Property) {
+        let matches = matches!(
+            (property.ty, value),
+            ("string", toml::Value::String(_))
+                | ("boolean", toml::Value::Boolean(_))
+                | ("integer", toml::Value::Integer(_))
+                | ("array", toml::Value::Array(_))
+                | ("object", toml::Value::Table(_))
+        );
+        assert!(matches, "`{key}` should be a {}: {value}", property.ty);
+        if let (Some(values), toml::Value::String(value)) = (property.values, value) {
+            assert!(
+                values.contains(&value.as_str()),
+                "`{key}` can't be {value:?}"
+            );
+        }
+        match value {
+            toml::Value::Array(items) => {
+                let item = property.items.as_deref().unwrap();
+                for value in items {
+                    check(&format!("{key}[]"), value, item);
+                }
+            }
+            toml::Value::Table(table) => {
+                for (name, value) in table {
+                    let key = format!("{key}.{name}");
+                    let property = property
+                        .properties
+                        .as_ref()
+                        .and_then(|properties| properties.get(name.as_str()))
+                        .or(property.additional_properties.as_deref())
+                        .unwrap_or_else(|| panic!("`{key}` isn't in the schema"));
+                    check(&key, value, property);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn schema_has_every_setting() {
+        let fields = Cell::new(&[][..]);
+        let _ = ConfigFile::deserialize(FieldNames(&fields));
+        let fields: Vec<&str> = fields.get().to_vec();
+        assert!(!fields.is_empty());
+        let mut fields = fields;
+        fields.sort_unstable();
+        let properties: Vec<&str> = Schema::new().properties.into_keys().collect();
+        assert_eq!(properties, fields);
+    }
+
+    #[test]
+    fn template_matches_the_schema() {
+        let template = uncommented_template();
+        ConfigFile::parse(Path::new("config.toml"), template.clone()).unwrap();
+
+        let table: toml::Table = toml::from_str(&template).unwrap();
+        let schema = Schema::new();
+        for (key, property) in &schema.properties {
+            let value = table
+                .get(*key)
+                .unwrap_or_else(|| panic!("`{key}` isn't in the template"));
+            check(key, value, property);
+        }
+        for key in table.keys() {
+            assert!(
+                schema.properties.contains_key(key.as_str()),
+                "`{key}` isn't in the schema"
+            );
+        }
+    }
+
+    #[test]
+    fn schema_defaults_match_the_schema() {
+        for (key, property) in &Schema::new().properties {
+            if let Some(default) = &property.default {
+                check(key, default, property);
+            }
+        }
+    }
+
+    #[test]
+    fn editor_words() {
+        let cases: &[(&str, &[&str])] = &[
+            ("vim", &["vim"]),
+            ("  code   --wait ", &["code", "--wait"]),
+            (
+                r#""C:\Program Files\Vim\vim.exe" -f"#,
+                &[r"C:\Program Files\Vim\vim.exe", "-f"],
+            ),
+            ("emacs -nw '+set title'", &["emacs", "-nw", "+set title"]),
+            (r#"ed """#, &["ed", ""]),
+            (
+                r"notepad++.exe -multiInst",
+                &["notepad++.exe", "-multiInst"],
+            ),
+        ];
+        for (editor, words) in cases {
+            assert_eq!(super::editor_words(editor), *words, "{editor}");
+        }
+    }
+}
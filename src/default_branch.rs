@@ -0,0 +1,106 @@
+//! Detecting the default branch of a remote.
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use crate::git;
+use crate::snapshot::RepoSnapshot;
+use crate::timeout::output_with_timeout;
+
+/// A remote's default branch, i.e. the branch its `HEAD` points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefaultBranch {
+    /// The branch name, without `refs/heads/`.
+    pub name: String,
+    /// How the default branch was detected.
+    pub source: DefaultBranchSource,
+}
+
+/// How a [`DefaultBranch`] was detected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DefaultBranchSource {
+    /// From the local `refs/remotes/<remote>/HEAD` symbolic ref.
+    SymbolicRef(String),
+    /// From `git ls-remote --symref <remote> HEAD`.
+    LsRemote(String),
+}
+
+impl Display for DefaultBranchSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DefaultBranchSource::SymbolicRef(remote) => {
+                write!(f, "`refs/remotes/{remote}/HEAD`")
+            }
+            DefaultBranchSource::LsRemote(remote) => {
+                write!(f, "`git ls-remote --symref {remote} HEAD`")
+            }
+        }
+    }
+}
+
+impl DefaultBranch {
+    /// Detect the default branch of `remote`.
+    ///
+    /// This checks the local `refs/remotes/<remote>/HEAD` symbolic ref in `snapshot` first, and
+    /// then asks the remote itself, giving up after `timeout`. Returns `None` if neither works.
+    pub fn of(snapshot: &RepoSnapshot, remote: &str, timeout: Duration) -> Option<Self> {
+        let prefix = format!("refs/remotes/{remote}/");
+        if let Some(name) = snapshot
+            .symref(&format!("{prefix}HEAD"))
+            .and_then(|target| target.strip_prefix(&prefix))
+        {
+            return Some(Self {
+                name: name.to_owned(),
+                source: DefaultBranchSource::SymbolicRef(remote.to_owned()),
+            });
+        }
+
+        snapshot
+            .remote_head(remote, || ls_remote(remote, timeout))
+            .map(|name| Self {
+                name,
+                source: DefaultBranchSource::LsRemote(remote.to_owned()),
+            })
+    }
+}
+
+/// Ask `remote` what its `HEAD` points to, or `None` if it doesn't say in time.
+fn ls_remote(remote: &str, timeout: Duration) -> Option<String> {
+    let output = output_with_timeout(
+        git::command()
+            .args(["ls-remote", "--symref", remote, "HEAD"])
+            // Never hang waiting for credentials.
+            .env("GIT_TERMINAL_PROMPT", "0"),
+        timeout,
+    );
+    let output = match output {
+        Ok(Some(output)) if output.status.success() => output,
+        Ok(Some(output)) => {
+            tracing::debug!(
+                %remote,
+                "Failed to detect default branch: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return None;
+        }
+        Ok(None) => {
+            tracing::debug!(%remote, "Detecting the default branch timed out");
+            return None;
+        }
+        Err(err) => {
+            tracing::debug!(%remote, "Failed to detect default branch: {err}");
+            return None;
+        }
+    };
+
+    // Looks like `ref: refs/heads/main\tHEAD`.
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| {
+            let (target, name) = line.strip_prefix("ref: ")?.split_once('\t')?;
+            if name != "HEAD" {
+                return None;
+            }
+            target.strip_prefix("refs/heads/").map(ToOwned::to_owned)
+        })
+}
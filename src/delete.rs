@@ -0,0 +1,72 @@
+//! `--delete` mode, for removing a branch from its remote and unsetting its upstream.
+
+use command_error::CommandExt;
+use miette::miette;
+use miette::IntoDiagnostic;
+
+use crate::exit_code::NoRemotes;
+use crate::git;
+use crate::git::Upstream;
+use crate::prompt::confirm;
+use crate::Config;
+
+/// Delete `branch` from its upstream remote and remove its upstream configuration.
+pub fn delete(config: &Config, branch: &str) -> miette::Result<()> {
+    if let Some(pattern) = config.protected_pattern(branch) {
+        return Err(miette!(
+            "Refusing to delete {branch} because it matches the protected branch pattern \
+             `{pattern}`"
+        ));
+    }
+
+    let upstream = Upstream::of(branch)?;
+    let target = match &upstream {
+        Some(upstream) => upstream.clone(),
+        None => Upstream {
+            remote: fallback_remote(config, branch)?,
+            branch: branch.to_owned(),
+        },
+    };
+    tracing::debug!(%target, "Resolved remote branch to delete");
+
+    if !config.cli.yes && !confirm(&format!("Delete {target} from the remote?"), "--yes")? {
+        return Err(miette!("Not deleting {target}"));
+    }
+
+    let mut command = git::command();
+    command.args(["push", &target.remote, "--delete", &target.branch]);
+    git::log_command(&command);
+    command.status_checked().into_diagnostic()?;
+
+    if upstream.is_some() {
+        git::command()
+            .args(["branch", "--unset-upstream", branch])
+            .status_checked()
+            .into_diagnostic()?;
+    }
+
+    tracing::info!(
+        "Deleted refs/heads/{} from {}",
+        target.branch,
+        target.remote
+    );
+
+    Ok(())
+}
+
+/// Pick a remote to delete `branch` from when it has no upstream: the first candidate remote
+/// with a remote-tracking ref for it, or else the first candidate remote.
+fn fallback_remote(config: &Config, branch: &str) -> miette::Result<String> {
+    let candidates = config.candidate_remotes()?;
+
+    for remote in &candidates {
+        if git::rev_parse(&format!("refs/remotes/{remote}/{branch}"))?.is_some() {
+            return Ok(remote.clone());
+        }
+    }
+
+    candidates
+        .into_iter()
+        .next()
+        .ok_or_else(|| NoRemotes.into())
+}
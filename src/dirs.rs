@@ -0,0 +1,195 @@
+//! Finding the user's configuration and state directories: the XDG directories on Unix, and
+//! `%APPDATA%` and `%LOCALAPPDATA%` on Windows.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use fs_err as fs;
+
+/// The name of the directories inside the user's configuration and state directories.
+const PREFIX: &str = "git-upstream";
+
+/// The user's configuration and state directories for `git-upstream`.
+#[derive(Debug, Clone)]
+pub struct UserDirs {
+    config: PathBuf,
+    state: PathBuf,
+    /// The XDG directories, where versions before native Windows support kept everything, on
+    /// Windows. Files there are copied over the first time they're needed.
+    legacy: Option<(PathBuf, PathBuf)>,
+}
+
+impl UserDirs {
+    /// Find the user directories, or `None` if there's no home directory.
+    ///
+    /// Like Git, this doesn't look the home directory up when `$HOME` isn't set on Unix,
+    /// because that usually means there isn't a useful one, like in a minimal container.
+    pub fn find() -> Option<Self> {
+        if cfg!(windows) {
+            Some(Self {
+                config: known_folder("APPDATA")?.join(PREFIX),
+                state: known_folder("LOCALAPPDATA")?.join(PREFIX),
+                legacy: known_folder("USERPROFILE").map(|home| xdg_dirs(&home)),
+            })
+        } else {
+            let (config, state) = xdg_dirs(&known_folder("HOME")?);
+            Some(Self {
+                config,
+                state,
+                legacy: None,
+            })
+        }
+    }
+
+    /// The configuration directory, like `~/.config/git-upstream`.
+    pub fn config_dir(&self) -> &Path {
+        &self.config
+    }
+
+    /// The state directory, like `~/.local/state/git-upstream`.
+    pub fn state_dir(&self) -> &Path {
+        &self.state
+    }
+
+    /// Get the path of the configuration file `name`, which may not exist.
+    pub fn config_file(&self, name: &str) -> PathBuf {
+        migrate(
+            self.config.join(name),
+            self.legacy.as_ref().map(|(config, _)| config.join(name)),
+        )
+    }
+
+    /// Get the path of the configuration file `name`, creating its directory.
+    pub fn place_config_file(&self, name: &str) -> std::io::Result<PathBuf> {
+        place(self.config_file(name))
+    }
+
+    /// Get the path of the state file `name`, creating its directory.
+    pub fn place_state_file(&self, name: &str) -> std::io::Result<PathBuf> {
+        place(migrate(
+            self.state.join(name),
+            self.legacy.as_ref().map(|(_, state)| state.join(name)),
+        ))
+    }
+}
+
+/// Get a directory from the environment variable `name`, if it's set to an absolute path.
+fn known_folder(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+}
+
+/// Get the XDG configuration and state directories for `home`, respecting `$XDG_CONFIG_HOME`
+/// and `$XDG_STATE_HOME`.
+fn xdg_dirs(home: &Path) -> (PathBuf, PathBuf) {
+    let config = known_folder("XDG_CONFIG_HOME").unwrap_or_else(|| home.join(".config"));
+    let state = known_folder("XDG_STATE_HOME").unwrap_or_else(|| home.join(".local/state"));
+    (config.join(PREFIX), state.join(PREFIX))
+}
+
+/// If `path` doesn't exist but `legacy` does, copy `legacy` to `path`, so it's only found in
+/// the old place once.
+///
+/// If copying fails, the legacy file is used where it is.
+fn migrate(path: PathBuf, legacy: Option<PathBuf>) -> PathBuf {
+    let Some(legacy) = legacy.filter(|legacy| !path.exists() && legacy.is_file()) else {
+        return path;
+    };
+    let copied = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::copy(&legacy, &path));
+    match copied {
+        Ok(_) => {
+            tracing::debug!("Copied {} to {}", legacy.display(), path.display());
+            path
+        }
+        Err(err) => {
+            tracing::debug!(
+                "Failed to copy {} to {}: {err}",
+                legacy.display(),
+                path.display()
+            );
+            legacy
+        }
+    }
+}
+
+/// Create the directory `path` is in, returning `path`.
+fn place(path: PathBuf) -> std::io::Result<PathBuf> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_dir::tests::Scratch;
+
+    fn dirs(root: &Path) -> UserDirs {
+        UserDirs {
+            config: root.join("AppData/Roaming/git-upstream"),
+            state: root.join("AppData/Local/git-upstream"),
+            legacy: Some(legacy_dirs(root)),
+        }
+    }
+
+    fn legacy_dirs(root: &Path) -> (PathBuf, PathBuf) {
+        (
+            root.join(".config/git-upstream"),
+            root.join(".local/state/git-upstream"),
+        )
+    }
+
+    #[test]
+    fn copies_legacy_files() {
+        let scratch = Scratch::new();
+        let dirs = dirs(&scratch.0);
+        let (legacy_config, legacy_state) = legacy_dirs(&scratch.0);
+        fs::create_dir_all(&legacy_config).unwrap();
+        fs::write(legacy_config.join("config.toml"), "remotes = []\n").unwrap();
+        fs::create_dir_all(&legacy_state).unwrap();
+        fs::write(legacy_state.join("journal.jsonl"), "{}\n").unwrap();
+
+        let config = dirs.config_file("config.toml");
+        assert_eq!(config, dirs.config_dir().join("config.toml"));
+        assert_eq!(fs::read_to_string(&config).unwrap(), "remotes = []\n");
+
+        let journal = dirs.place_state_file("journal.jsonl").unwrap();
+        assert_eq!(journal, dirs.state_dir().join("journal.jsonl"));
+        assert_eq!(fs::read_to_string(&journal).unwrap(), "{}\n");
+
+        // Once it's been copied, the new file wins.
+        fs::write(&config, "remotes = ['origin']\n").unwrap();
+        let config = dirs.config_file("config.toml");
+        assert_eq!(
+            fs::read_to_string(config).unwrap(),
+            "remotes = ['origin']\n"
+        );
+    }
+
+    #[test]
+    fn missing_files() {
+        let scratch = Scratch::new();
+        let dirs = dirs(&scratch.0);
+
+        // Nothing to copy, so nothing is created.
+        let config = dirs.config_file("config.toml");
+        assert_eq!(config, dirs.config_dir().join("config.toml"));
+        assert!(!dirs.config_dir().exists());
+
+        // Placing a file creates its directory, but not the file.
+        let config = dirs.place_config_file("config.toml").unwrap();
+        assert!(dirs.config_dir().is_dir());
+        assert!(!config.exists());
+
+        // A legacy directory isn't a file to copy.
+        fs::create_dir_all(legacy_dirs(&scratch.0).1.join("journal.jsonl")).unwrap();
+        let journal = dirs.place_state_file("journal.jsonl").unwrap();
+        assert_eq!(journal, dirs.state_dir().join("journal.jsonl"));
+        assert!(!journal.exists());
+    }
+}
@@ -0,0 +1,74 @@
+//! Warning about uncommitted changes before pushing, which usually belong on the branch.
+
+use command_error::CommandExt;
+use miette::miette;
+use serde::Deserialize;
+
+use crate::git;
+use crate::Config;
+
+/// What to do about uncommitted changes before pushing, from `warn-dirty`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WarnDirty {
+    /// Don't check.
+    Off,
+    /// Log a warning.
+    #[default]
+    Warn,
+    /// Refuse to push, unless `--allow-dirty` is given.
+    Block,
+}
+
+impl WarnDirty {
+    /// The names of the modes in configuration files.
+    pub const NAMES: &[&str] = &["off", "warn", "block"];
+}
+
+/// Check the work tree for uncommitted changes, and warn about them or refuse to push, depending
+/// on `warn-dirty`.
+///
+/// Untracked files only count with `warn-dirty-untracked = true`.
+pub fn check(config: &Config) -> miette::Result<()> {
+    let mode = config.file.warn_dirty.unwrap_or_default();
+    if mode == WarnDirty::Off {
+        return Ok(());
+    }
+
+    let untracked = if config.file.warn_dirty_untracked.unwrap_or(false) {
+        "--untracked-files=normal"
+    } else {
+        "--untracked-files=no"
+    };
+    let output = match git::command()
+        .args(["status", "--porcelain=v2", untracked])
+        .output_checked_utf8()
+    {
+        Ok(output) => output.stdout,
+        Err(err) => {
+            // Without a work tree, there's nothing to be dirty.
+            tracing::debug!("Not checking for uncommitted changes: {err}");
+            return Ok(());
+        }
+    };
+    let files = output.lines().filter(|line| !line.starts_with('#')).count();
+    if files == 0 {
+        return Ok(());
+    }
+
+    let changes = if files == 1 {
+        "1 file has uncommitted changes".to_owned()
+    } else {
+        format!("{files} files have uncommitted changes")
+    };
+    match mode {
+        WarnDirty::Block if !config.cli.allow_dirty => Err(miette!(
+            help = "Commit or stash them, or pass `--allow-dirty` to push anyway",
+            "Refusing to push because {changes}"
+        )),
+        _ => {
+            tracing::warn!("{changes}; did you forget to commit something?");
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! `git upstream doctor`, for diagnosing problems (and filing useful bug reports).
+
+use std::process::Command;
+use std::time::Duration;
+
+use command_error::CommandExt;
+use miette::IntoDiagnostic;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stdout;
+use owo_colors::Style;
+use serde::Serialize;
+
+use crate::git;
+use crate::git::Upstream;
+use crate::git_version::GitVersion;
+use crate::json;
+use crate::timeout::output_with_timeout;
+use crate::Config;
+use crate::ConfigFile;
+
+/// How long to wait for each remote to respond to `git ls-remote`.
+const LS_REMOTE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Options for `git upstream doctor`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct DoctorArgs {
+    /// Print the results as JSON instead of a list.
+    #[arg(long)]
+    json: bool,
+}
+
+/// The result of a check, worst last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// A single diagnostic check.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    /// What was checked.
+    pub name: String,
+    pub status: CheckStatus,
+    /// What was found.
+    pub message: String,
+    /// How to fix a warning or failure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn pass(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Pass, message)
+    }
+
+    fn warn(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Warn, message)
+    }
+
+    fn fail(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Fail, message)
+    }
+
+    fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+/// Run `git upstream doctor`, returning the worst result of the checks.
+pub fn doctor(config: &Config, args: &DoctorArgs) -> miette::Result<CheckStatus> {
+    let mut checks = Vec::new();
+
+    checks.push(check_git_version());
+    let work_tree = check_work_tree();
+    let in_work_tree = work_tree.status == CheckStatus::Pass;
+    checks.push(work_tree);
+    checks.extend(check_config_files(config)?);
+    checks.extend(check_user_dirs(config));
+    // The remaining repository checks would only repeat the work tree failure.
+    if in_work_tree {
+        checks.extend(check_remotes(config));
+        checks.push(check_upstream());
+    }
+    for program in ["gh", "glab"] {
+        checks.push(check_installed(program));
+    }
+
+    if args.json {
+        println!("{}", json::to_string_pretty(&checks).into_diagnostic()?);
+    } else {
+        for check in &checks {
+            print_check(check);
+        }
+    }
+
+    Ok(checks
+        .iter()
+        .map(|check| check.status)
+        .max()
+        .unwrap_or(CheckStatus::Pass))
+}
+
+fn print_check(check: &Check) {
+    let (label, style) = match check.status {
+        CheckStatus::Pass => ("pass", Style::new().green()),
+        CheckStatus::Warn => ("warn", Style::new().yellow()),
+        CheckStatus::Fail => ("fail", Style::new().red().bold()),
+    };
+    println!(
+        "{} {}: {}",
+        label.if_supports_color(Stdout, |text| style.style(text)),
+        check
+            .name
+            .if_supports_color(Stdout, |text| Style::new().bold().style(text)),
+        check.message,
+    );
+    if let Some(hint) = &check.hint {
+        println!(
+            "     {}",
+            hint.if_supports_color(Stdout, |text| Style::new().dimmed().style(text))
+        );
+    }
+}
+
+fn check_git_version() -> Check {
+    let name = "git version";
+    match GitVersion::detect() {
+        Err(err) => Check::fail(name, err.to_string())
+            .hint("Install Git and make sure `git` is on your `PATH`"),
+        Ok(version) if version < GitVersion::FORCE_WITH_LEASE => Check::fail(
+            name,
+            format!("{version} doesn't support `--force-with-lease`"),
+        )
+        .hint(format!("Upgrade to Git {}", GitVersion::FORCE_IF_INCLUDES)),
+        Ok(version) if version < GitVersion::FORCE_IF_INCLUDES => Check::warn(
+            name,
+            format!("{version} supports `--force-with-lease` but not `--force-if-includes`"),
+        )
+        .hint(format!("Upgrade to Git {}", GitVersion::FORCE_IF_INCLUDES)),
+        Ok(version) => Check::pass(
+            name,
+            format!("{version} supports `--force-with-lease` and `--force-if-includes`"),
+        ),
+    }
+}
+
+fn check_work_tree() -> Check {
+    let name = "work tree";
+    let inside = git::command()
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .output_checked_utf8()
+        .map(|output| output.stdout.trim() == "true");
+    match inside {
+        Ok(true) => Check::pass(name, "inside a Git work tree"),
+        _ => Check::fail(name, "not inside a Git work tree")
+            .hint("`cd` into a Git repository, or create one with `git init`"),
+    }
+}
+
+fn check_config_files(config: &Config) -> miette::Result<Vec<Check>> {
+    Ok(config
+        .config_paths()?
+        .into_iter()
+        .map(|path| {
+            let name = format!("config file {}", path.display());
+            if !path.exists() {
+                return Check::pass(name, "not present; using defaults");
+            }
+            match ConfigFile::load(&path) {
+                Ok(_) => Check::pass(name, "loaded"),
+                Err(err) => Check::fail(name, format!("{err:?}"))
+                    .hint("Fix the configuration file, or move it out of the way"),
+            }
+        })
+        .collect())
+}
+
+fn check_user_dirs(config: &Config) -> Vec<Check> {
+    match &config.dirs {
+        Some(dirs) => vec![
+            Check::pass("config directory", dirs.config_dir().display().to_string()),
+            Check::pass("state directory", dirs.state_dir().display().to_string()),
+        ],
+        None => vec![Check::warn(
+            "user directories",
+            "the home directory is unknown, so the user configuration file isn't read and pushes \
+             aren't recorded for `git upstream undo`",
+        )
+        .hint(if cfg!(windows) {
+            "Set `%APPDATA%` and `%LOCALAPPDATA%`"
+        } else {
+            "Set `$HOME`"
+        })],
+    }
+}
+
+fn check_remotes(config: &Config) -> Vec<Check> {
+    let candidates = match config.candidate_remotes() {
+        Ok(candidates) => candidates,
+        Err(err) => {
+            return vec![Check::fail("remotes", err.to_string())
+                .hint("Add a remote with `git remote add origin URL`")];
+        }
+    };
+
+    let mut checks = vec![Check::pass(
+        "remote order",
+        candidates
+            .iter()
+            .enumerate()
+            .map(|(i, remote)| format!("{}. {remote}", i + 1))
+            .collect::<Vec<_>>()
+            .join(", "),
+    )];
+
+    for remote in candidates {
+        checks.push(check_remote(&remote));
+    }
+
+    checks
+}
+
+fn check_remote(remote: &str) -> Check {
+    let name = format!("remote {remote}");
+
+    let url = match git::command()
+        .args(["remote", "get-url", "--push", remote])
+        .output_checked_utf8()
+    {
+        Ok(output) => output.stdout.trim().to_owned(),
+        Err(err) => return Check::fail(name, err.to_string()),
+    };
+
+    let output = output_with_timeout(
+        git::command()
+            .args(["ls-remote", "--exit-code", remote, "HEAD"])
+            // Never hang waiting for credentials.
+            .env("GIT_TERMINAL_PROMPT", "0"),
+        LS_REMOTE_TIMEOUT,
+    );
+
+    match output {
+        Ok(Some(output)) if output.status.success() || output.status.code() == Some(2) => {
+            Check::pass(name, format!("{url} is reachable"))
+        }
+        Ok(Some(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr
+                .lines()
+                .find(|line| !line.trim().is_empty())
+                .unwrap_or("`git ls-remote` failed")
+                .trim()
+                .to_owned();
+            Check::fail(name, format!("{url}: {reason}"))
+                .hint("Check the remote's URL and your credentials for it")
+        }
+        Ok(None) => Check::warn(
+            name,
+            format!(
+                "{url} didn't respond within {}s",
+                LS_REMOTE_TIMEOUT.as_secs()
+            ),
+        )
+        .hint("Check your network connection or VPN"),
+        Err(err) => Check::fail(name, format!("Failed to run `git ls-remote`: {err}")),
+    }
+}
+
+fn check_upstream() -> Check {
+    let name = "upstream";
+    let branch = match git::current_branch() {
+        Ok(branch) if branch != "HEAD" => branch,
+        Ok(_) => return Check::warn(name, "no branch is checked out (detached `HEAD`)"),
+        Err(err) => return Check::fail(name, err.to_string()),
+    };
+
+    match Upstream::of(&branch) {
+        Ok(Some(upstream)) => Check::pass(name, format!("{branch} tracks {upstream}")),
+        Ok(None) => Check::warn(name, format!("{branch} has no upstream"))
+            .hint("Run `git upstream` to push it and set one"),
+        Err(err) => Check::fail(name, err.to_string()),
+    }
+}
+
+fn check_installed(program: &str) -> Check {
+    match Command::new(program).arg("--version").output_checked_utf8() {
+        Ok(output) => Check::pass(
+            program,
+            output.stdout.lines().next().unwrap_or("installed").trim(),
+        ),
+        Err(_) => Check::warn(program, "not installed").hint(format!(
+            "Install `{program}` to use the GitHub and GitLab integrations"
+        )),
+    }
+}
@@ -0,0 +1,150 @@
+//! Exit codes, so scripts can tell why `git upstream` failed.
+
+use std::fmt::Display;
+
+use miette::Diagnostic;
+
+use crate::ci::CiFailed;
+use crate::interrupt::Interrupted;
+use crate::report::PushFailed;
+
+/// The exit codes, for `--help`. Keep this in sync with [`Exit`].
+pub const HELP: &str = "\
+Exit codes:
+  0    The branch was pushed, or was already up to date
+  1    Something else went wrong
+  2    There were no remotes to push to, or the arguments couldn't be parsed
+  3    Every remote rejected the push because of authentication or permissions
+  4    The push was rejected as a non-fast-forward
+  5    The configuration was invalid, or the arguments can't be used together
+  6    The branch was already up to date, with `--exit-code-up-to-date`
+  7    CI failed after pushing, with `--watch-ci=gate`
+  8    `git upstream doctor` found problems worth a warning
+  9    `git upstream doctor` found failures
+  130  Interrupted with Ctrl-C";
+
+/// Why `git upstream` exited.
+///
+/// Argument parsing errors exit with code 2, from `clap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Success = 0,
+    Failure = 1,
+    /// There were no remotes to push to.
+    NoRemotes = 2,
+    /// Every remote rejected our credentials.
+    Rejected = 3,
+    /// The remote has commits the branch doesn't.
+    NonFastForward = 4,
+    /// The configuration or arguments were invalid. See `UsageError`.
+    Usage = 5,
+    /// The branch was already up to date, with `--exit-code-up-to-date`.
+    UpToDate = 6,
+    /// CI failed after pushing, with `--watch-ci=gate`.
+    CiFailed = 7,
+    /// `git upstream doctor` found problems worth a warning, but no failures.
+    DoctorWarnings = 8,
+    /// `git upstream doctor` found failures.
+    DoctorFailures = 9,
+    /// The user pressed Ctrl-C, like a shell reports for `SIGINT`.
+    Interrupted = 130,
+}
+
+impl Exit {
+    /// Get the exit code for an error.
+    pub fn of(error: &miette::Report) -> Self {
+        if error.downcast_ref::<UsageError>().is_some() {
+            Self::Usage
+        } else if error.downcast_ref::<Interrupted>().is_some() {
+            Self::Interrupted
+        } else if error.downcast_ref::<NoRemotes>().is_some() {
+            Self::NoRemotes
+        } else if error.downcast_ref::<CiFailed>().is_some() {
+            Self::CiFailed
+        } else if let Some(failed) = error.downcast_ref::<PushFailed>() {
+            failed.exit()
+        } else {
+            Self::Failure
+        }
+    }
+}
+
+impl From<Exit> for std::process::ExitCode {
+    fn from(exit: Exit) -> Self {
+        Self::from(exit as u8)
+    }
+}
+
+/// An error for when the repository has no remotes to push to.
+#[derive(Debug)]
+pub struct NoRemotes;
+
+impl Display for NoRemotes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No Git remotes found")
+    }
+}
+
+impl std::error::Error for NoRemotes {}
+
+impl Diagnostic for NoRemotes {
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new("Add one with `git remote add origin URL`"))
+    }
+}
+
+/// Mark an error as being caused by invalid configuration or arguments, so it exits with
+/// [`Exit::Usage`].
+pub fn usage(error: miette::Report) -> miette::Report {
+    miette::Report::new(UsageError(error))
+}
+
+/// An error caused by invalid configuration or arguments. Displayed like the error it wraps.
+#[derive(Debug)]
+pub struct UsageError(miette::Report);
+
+impl Display for UsageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UsageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+impl Diagnostic for UsageError {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.0.code()
+    }
+
+    fn severity(&self) -> Option<miette::Severity> {
+        self.0.severity()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.0.help()
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.0.url()
+    }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        self.0.source_code()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        self.0.labels()
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        self.0.related()
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        self.0.diagnostic_source()
+    }
+}
@@ -0,0 +1,499 @@
+//! Classifying why a `git` command talking to a remote failed.
+
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::remote_url::RemoteUrl;
+
+/// Why a `git` command talking to a remote failed, from its stderr.
+///
+/// Some failures are specific to a remote, so it makes sense to try the next one; others would
+/// happen with every remote, so it doesn't. See [`FailureKind::falls_through`]. Network failures
+/// may be transient, so it makes sense to retry them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureKind {
+    /// The remote rejected our credentials, or we didn't have any.
+    Auth,
+    /// We're signed in, but aren't allowed to push to the repository.
+    PermissionDenied,
+    /// The SSH server didn't accept any of our keys.
+    SshKey,
+    /// The SSH server's host key isn't known, or has changed.
+    HostKey,
+    /// The remote repository doesn't exist, or we can't see it.
+    NotFound,
+    /// The remote has commits the branch doesn't.
+    NonFastForward,
+    /// `--force-if-includes` found commits on the remote branch which were fetched, but never
+    /// integrated into the local branch.
+    IfIncludes,
+    /// A `pre-push` hook, or the remote's `pre-receive` hook, rejected the push.
+    Hook,
+    /// The refspec names a ref which doesn't exist, or is malformed.
+    InvalidRefspec,
+    /// The repository is a shallow clone, and the remote doesn't have the missing history.
+    Shallow,
+    /// The remote doesn't support push options, like GitLab's `merge_request.create`.
+    PushOptions,
+    /// The remote doesn't support signed pushes, for `--signed`.
+    SignedUnsupported,
+    /// The remote doesn't support atomic pushes, for `--atomic`.
+    AtomicUnsupported,
+    /// GPG (or whatever `gpg.program` is) couldn't sign the push, for `--signed`.
+    Gpg,
+    /// The remote couldn't be reached.
+    Network,
+    /// The command was killed because it ran for longer than `--timeout`.
+    Timeout,
+    /// Anything else.
+    Other,
+}
+
+impl FailureKind {
+    /// Classify a failure from the stderr of a `git` command.
+    pub fn classify(stderr: &str) -> Self {
+        // Checked in order, because messages about the push itself are more specific than the
+        // boilerplate about access rights which Git prints after them.
+        const PATTERNS: &[(FailureKind, &[&str])] = &[
+            (FailureKind::Shallow, &["shallow update not allowed"]),
+            (
+                FailureKind::SignedUnsupported,
+                &["the receiving end does not support --signed push"],
+            ),
+            (
+                FailureKind::AtomicUnsupported,
+                &["the receiving end does not support --atomic push"],
+            ),
+            (
+                FailureKind::Gpg,
+                &[
+                    "gpg failed to sign the data",
+                    "failed to sign the push certificate",
+                ],
+            ),
+            // GitLab checks permissions in its `pre-receive` hook.
+            (
+                FailureKind::PermissionDenied,
+                &[
+                    "Permission to ",
+                    "not allowed to push",
+                    "You are not allowed to push code",
+                    // Bitbucket.
+                    "Permission denied to update branch",
+                    "access via a deployment key is read-only",
+                    "The requested URL returned error: 403",
+                ],
+            ),
+            (
+                FailureKind::Hook,
+                &[
+                    "pre-receive hook declined",
+                    "hook declined",
+                    "pre-push hook",
+                ],
+            ),
+            (
+                FailureKind::IfIncludes,
+                &[
+                    "(remote ref updated since checkout)",
+                    "tip of the remote-tracking branch has been updated",
+                ],
+            ),
+            (
+                FailureKind::NonFastForward,
+                &[
+                    "(non-fast-forward)",
+                    "(fetch first)",
+                    // `--force-with-lease` found the remote branch somewhere unexpected.
+                    "(stale info)",
+                    "tip of your current branch is behind",
+                ],
+            ),
+            (
+                FailureKind::InvalidRefspec,
+                &["src refspec", "invalid refspec", "is not a valid ref name"],
+            ),
+            (FailureKind::SshKey, &["Permission denied (publickey"]),
+            (FailureKind::PushOptions, &["does not support push options"]),
+            (FailureKind::HostKey, &["Host key verification failed"]),
+            (
+                FailureKind::Auth,
+                &[
+                    "Permission denied",
+                    "Authentication failed",
+                    "could not read Username",
+                    "could not read Password",
+                    "terminal prompts disabled",
+                    "The requested URL returned error: 401",
+                ],
+            ),
+            (
+                FailureKind::NotFound,
+                &[
+                    "Repository not found",
+                    "repository not found",
+                    "does not appear to be a git repository",
+                    "The requested URL returned error: 404",
+                    "The project you were looking for could not be found",
+                ],
+            ),
+            (
+                FailureKind::Network,
+                &[
+                    "Could not resolve host",
+                    "Could not resolve hostname",
+                    "Connection timed out",
+                    "Connection refused",
+                    "Connection reset",
+                    "Network is unreachable",
+                    "Operation timed out",
+                    "The remote end hung up unexpectedly",
+                    "early EOF",
+                ],
+            ),
+        ];
+
+        PATTERNS
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| stderr.contains(pattern)))
+            .map_or(Self::Other, |(kind, _)| *kind)
+    }
+
+    /// Whether the failure may be transient, so it's worth retrying with `--retry`.
+    pub fn is_transient(self) -> bool {
+        matches!(self, Self::Network | Self::Timeout)
+    }
+
+    /// Whether it's worth trying the next remote after this failure.
+    ///
+    /// Rejections because of the branch's history, hooks, refspecs, or a shallow clone would
+    /// happen with every remote, so they stop the run. So do unknown host keys, which need a
+    /// person to check them, and failures to sign the push.
+    ///
+    /// Servers which don't support `--signed` or `--atomic` are likely to be the same server,
+    /// so those stop the run too, unless the next remote is pushed to with different flags;
+    /// see [`crate::capabilities::fails_the_same`].
+    pub fn falls_through(self) -> bool {
+        match self {
+            Self::Auth
+            | Self::PermissionDenied
+            | Self::SshKey
+            | Self::PushOptions
+            | Self::NotFound
+            | Self::Network
+            | Self::Timeout
+            | Self::Other => true,
+            // The next remote may well be on the same host.
+            Self::HostKey => false,
+            Self::NonFastForward
+            | Self::IfIncludes
+            | Self::Hook
+            | Self::InvalidRefspec
+            | Self::Shallow
+            | Self::SignedUnsupported
+            | Self::AtomicUnsupported
+            | Self::Gpg => false,
+        }
+    }
+}
+
+/// Suggest how to fix an SSH or DNS failure when talking to the remote at `url`.
+pub fn ssh_help(kind: FailureKind, url: &RemoteUrl, stderr: &str) -> Option<String> {
+    let host = &url.host;
+    let login = match &url.user {
+        Some(user) => format!("{user}@{host}"),
+        None => host.clone(),
+    };
+    match kind {
+        FailureKind::SshKey => Some(format!(
+            "Check that `ssh-add -l` lists a key {host} accepts, and that the remote's URL signs \
+             in as the right user (usually `git` for forges); try `ssh -T {login}`"
+        )),
+        FailureKind::HostKey => Some(format!(
+            "SSH doesn't recognize {host}'s host key. Check the key is genuine, then accept it by \
+             running `ssh {login}`, or fix its entry in `~/.ssh/known_hosts` if it changed"
+        )),
+        FailureKind::Network if stderr.contains("Could not resolve host") => Some(format!(
+            "Check that {host} is spelled correctly in the remote's URL and that you're online; \
+             pass `--retry` to try again"
+        )),
+        _ => None,
+    }
+}
+
+/// Summarize why a `git` command talking to a remote failed in one line, from its stderr.
+///
+/// Prefers rejected refs (like `main -> main (pre-receive hook declined)`), then the first
+/// line which isn't boilerplate.
+pub fn summarize(stderr: &str) -> Option<String> {
+    let lines = || {
+        stderr
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+    };
+
+    if let Some(rejected) = lines().find_map(|line| {
+        line.strip_prefix("! [rejected]")
+            .or_else(|| line.strip_prefix("! [remote rejected]"))
+    }) {
+        return Some(rejected.trim().to_owned());
+    }
+
+    const BOILERPLATE: &[&str] = &[
+        "To ",
+        "Please make sure you have the correct access rights",
+        "and the repository exists.",
+        "fatal: Could not read from remote repository.",
+        "error: failed to push some refs to",
+    ];
+    lines()
+        .find(|line| !BOILERPLATE.iter().any(|prefix| line.starts_with(prefix)))
+        .or_else(|| lines().next_back())
+        .map(|line| {
+            line.trim_start_matches("fatal: ")
+                .trim_start_matches("error: ")
+                .to_owned()
+        })
+}
+
+impl Display for FailureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auth => write!(f, "authentication failed"),
+            Self::PermissionDenied => write!(f, "permission denied"),
+            Self::SshKey => write!(f, "SSH key rejected"),
+            Self::HostKey => write!(f, "host key verification failed"),
+            Self::NotFound => write!(f, "repository not found"),
+            Self::NonFastForward => write!(f, "rejected as a non-fast-forward"),
+            Self::IfIncludes => write!(f, "rejected by `--force-if-includes`"),
+            Self::Hook => write!(f, "rejected by a hook"),
+            Self::InvalidRefspec => write!(f, "invalid refspec"),
+            Self::Shallow => write!(f, "rejected because the clone is shallow"),
+            Self::PushOptions => write!(f, "push options not supported"),
+            Self::SignedUnsupported => write!(f, "signed pushes not supported"),
+            Self::AtomicUnsupported => write!(f, "atomic pushes not supported"),
+            Self::Gpg => write!(f, "signing the push failed"),
+            Self::Network => write!(f, "network error"),
+            Self::Timeout => write!(f, "timed out"),
+            Self::Other => write!(f, "failed"),
+        }
+    }
+}
+
+/// Get the last `count` lines of what a `git` command printed, without Git's boilerplate, for
+/// showing what a hook said after it failed.
+pub fn tail(output: &str, count: usize) -> Vec<&str> {
+    let lines = output
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty() && !line.starts_with("error: failed to push some refs"))
+        .collect::<Vec<_>>();
+    lines[lines.len().saturating_sub(count)..].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_real_stderr() {
+        let cases = [
+            (
+                "GitHub over HTTPS",
+                "remote: Permission to owner/repo.git denied to someone.\n\
+                 fatal: unable to access 'https://github.com/owner/repo.git/': The requested URL \
+                 returned error: 403\n",
+                FailureKind::PermissionDenied,
+            ),
+            (
+                "GitHub over SSH",
+                "ERROR: Permission to owner/repo.git denied to someone.\n\
+                 fatal: Could not read from remote repository.\n\n\
+                 Please make sure you have the correct access rights\n\
+                 and the repository exists.\n",
+                FailureKind::PermissionDenied,
+            ),
+            (
+                "GitLab",
+                "remote: \n\
+                 remote: ========================================================================\n\
+                 remote: \n\
+                 remote: You are not allowed to push code to this project.\n\
+                 remote: \n\
+                 remote: ========================================================================\n\
+                 remote: \n\
+                 fatal: Could not read from remote repository.\n",
+                FailureKind::PermissionDenied,
+            ),
+            (
+                "GitLab protected branch",
+                "remote: GitLab: You are not allowed to push code to protected branches on this \
+                 project.\n\
+                 To gitlab.com:group/project.git\n \
+                 ! [remote rejected] main -> main (pre-receive hook declined)\n\
+                 error: failed to push some refs to 'gitlab.com:group/project.git'\n",
+                FailureKind::PermissionDenied,
+            ),
+            (
+                "Bitbucket",
+                "remote: Forbidden: You don't have write access to this repository.\n\
+                 fatal: unable to access 'https://bitbucket.org/team/repo.git/': The requested \
+                 URL returned error: 403\n",
+                FailureKind::PermissionDenied,
+            ),
+            (
+                "Bitbucket branch permissions",
+                "remote: Permission denied to update branch main.\n\
+                 To bitbucket.org:team/repo.git\n \
+                 ! [remote rejected] main -> main (pre-receive hook declined)\n",
+                FailureKind::PermissionDenied,
+            ),
+            (
+                "Bitbucket deploy key",
+                "repository access denied. access via a deployment key is read-only.\n\
+                 fatal: Could not read from remote repository.\n",
+                FailureKind::PermissionDenied,
+            ),
+            (
+                "HTTPS without credentials",
+                "fatal: could not read Username for 'https://github.com': terminal prompts \
+                 disabled\n",
+                FailureKind::Auth,
+            ),
+            (
+                "non-fast-forward",
+                "To github.com:owner/repo.git\n \
+                 ! [rejected]        main -> main (non-fast-forward)\n\
+                 error: failed to push some refs to 'github.com:owner/repo.git'\n\
+                 hint: Updates were rejected because the tip of your current branch is behind\n",
+                FailureKind::NonFastForward,
+            ),
+            (
+                "fetch first",
+                " ! [rejected]        main -> main (fetch first)\n",
+                FailureKind::NonFastForward,
+            ),
+            (
+                "stale lease",
+                " ! [rejected]        main -> main (stale info)\n",
+                FailureKind::NonFastForward,
+            ),
+            // The push loop looks for the hook itself.
+            (
+                "pre-push hook",
+                "nope\nerror: failed to push some refs to 'origin'\n",
+                FailureKind::Other,
+            ),
+            (
+                "remote hook",
+                "remote: error: commit message must reference a ticket\n\
+                 To example.com:repo.git\n \
+                 ! [remote rejected] feature -> feature (pre-receive hook declined)\n",
+                FailureKind::Hook,
+            ),
+            (
+                "DNS over HTTPS",
+                "fatal: unable to access 'https://github.invalid/owner/repo.git/': Could not \
+                 resolve host: github.invalid\n",
+                FailureKind::Network,
+            ),
+            (
+                "DNS over SSH",
+                "ssh: Could not resolve hostname github.invalid: Name or service not known\n\
+                 fatal: Could not read from remote repository.\n",
+                FailureKind::Network,
+            ),
+            (
+                "SSH key",
+                "git@github.com: Permission denied (publickey).\n\
+                 fatal: Could not read from remote repository.\n\n\
+                 Please make sure you have the correct access rights\n\
+                 and the repository exists.\n",
+                FailureKind::SshKey,
+            ),
+            (
+                "SSH key and password",
+                "git@example.com: Permission denied (publickey,password).\n",
+                FailureKind::SshKey,
+            ),
+            (
+                "host key",
+                "No ED25519 host key is known for github.com and you have requested strict \
+                 checking.\nHost key verification failed.\n\
+                 fatal: Could not read from remote repository.\n",
+                FailureKind::HostKey,
+            ),
+            (
+                "not found",
+                "ERROR: Repository not found.\nfatal: Could not read from remote repository.\n",
+                FailureKind::NotFound,
+            ),
+            (
+                "shallow",
+                " ! [remote rejected] main -> main (shallow update not allowed)\n",
+                FailureKind::Shallow,
+            ),
+            ("nothing", "", FailureKind::Other),
+        ];
+        for (name, stderr, expected) in cases {
+            assert_eq!(FailureKind::classify(stderr), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn ssh_hints_name_the_host() {
+        let url = RemoteUrl::parse("git@github.com:owner/repo.git").unwrap();
+        let help = |kind, stderr| ssh_help(kind, &url, stderr).unwrap();
+
+        let key = help(FailureKind::SshKey, "");
+        assert!(key.contains("`ssh-add -l`"), "{key}");
+        assert!(key.contains("`ssh -T git@github.com`"), "{key}");
+
+        let host_key = help(FailureKind::HostKey, "");
+        assert!(host_key.contains("github.com's host key"), "{host_key}");
+        assert!(host_key.contains("`ssh git@github.com`"), "{host_key}");
+
+        let dns = help(
+            FailureKind::Network,
+            "ssh: Could not resolve hostname github.com: Name or service not known",
+        );
+        assert!(dns.contains("github.com is spelled correctly"), "{dns}");
+        assert_eq!(
+            ssh_help(FailureKind::Network, &url, "Connection refused"),
+            None
+        );
+    }
+
+    #[test]
+    fn ssh_failures_retry_and_fall_through() {
+        assert!(FailureKind::SshKey.falls_through());
+        assert!(!FailureKind::SshKey.is_transient());
+        // The next remote may be on the same host, so someone needs to check the key.
+        assert!(!FailureKind::HostKey.falls_through());
+        assert!(FailureKind::Network.is_transient());
+    }
+
+    #[test]
+    fn summarizes() {
+        assert_eq!(
+            summarize(
+                "To example.com:repo.git\n \
+                 ! [remote rejected] feature -> feature (pre-receive hook declined)\n\
+                 error: failed to push some refs to 'example.com:repo.git'\n"
+            )
+            .as_deref(),
+            Some("feature -> feature (pre-receive hook declined)")
+        );
+        assert_eq!(
+            summarize(
+                "ERROR: Repository not found.\nfatal: Could not read from remote repository.\n"
+            )
+            .as_deref(),
+            Some("ERROR: Repository not found.")
+        );
+        assert_eq!(summarize(""), None);
+    }
+}
@@ -0,0 +1,116 @@
+//! `git upstream fetch`, for fetching from the best reachable remote.
+
+use std::process::Stdio;
+
+use command_error::CommandExt;
+use command_error::OutputContext;
+use miette::miette;
+use miette::IntoDiagnostic;
+use utf8_command::Utf8Output;
+
+use crate::failure::FailureKind;
+use crate::git;
+use crate::Config;
+
+/// Fetch from the first remote that works, trying remotes in the same order as when pushing.
+#[derive(Debug, Clone, clap::Args)]
+pub struct FetchArgs {
+    /// The remote to try first.
+    remote: Option<String>,
+
+    /// Fetch from every remote instead of stopping after the first one that works.
+    #[arg(long, conflicts_with = "remote")]
+    all_remotes: bool,
+
+    /// Remove remote-tracking refs which no longer exist on the remote.
+    #[arg(long)]
+    prune: bool,
+
+    /// Fetch all tags.
+    #[arg(long)]
+    tags: bool,
+}
+
+/// Run `git upstream fetch`.
+pub fn fetch(config: &Config, args: &FetchArgs) -> miette::Result<()> {
+    let mut candidates = config.candidate_remotes()?;
+    if let Some(remote) = &args.remote {
+        candidates.retain(|candidate| candidate != remote);
+        candidates.insert(0, remote.clone());
+    }
+
+    let mut results = Vec::new();
+    for remote in &candidates {
+        let result = fetch_remote(args, remote)?;
+        if let Err(kind) = result {
+            if config.cli.fail_fast {
+                return Err(miette!("Failed to fetch from {remote}: {kind}"));
+            }
+            tracing::debug!("Failed to fetch from {remote}: {kind}");
+        } else if !args.all_remotes {
+            tracing::info!("Fetched from {remote}");
+            return Ok(());
+        }
+        results.push((remote, result));
+    }
+
+    if args.all_remotes {
+        for (remote, result) in &results {
+            match result {
+                Ok(()) => tracing::info!("Fetched from {remote}"),
+                Err(kind) => tracing::warn!("Failed to fetch from {remote}: {kind}"),
+            }
+        }
+        if results.iter().any(|(_, result)| result.is_ok()) {
+            return Ok(());
+        }
+    }
+
+    Err(miette!(
+        "Failed to fetch from any remote; tried {}",
+        candidates.join(", ")
+    ))
+}
+
+/// Fetch from `remote`, retrying once if it fails because of a network error.
+fn fetch_remote(args: &FetchArgs, remote: &str) -> miette::Result<Result<(), FailureKind>> {
+    let result = fetch_once(args, remote)?;
+    if result == Err(FailureKind::Network) {
+        tracing::info!("Fetching from {remote} failed because of a network error; retrying");
+        return fetch_once(args, remote);
+    }
+    Ok(result)
+}
+
+fn fetch_once(args: &FetchArgs, remote: &str) -> miette::Result<Result<(), FailureKind>> {
+    let mut command = git::command();
+    command.arg("fetch");
+    if args.prune {
+        command.arg("--prune");
+    }
+    if args.tags {
+        command.arg("--tags");
+    }
+    command
+        .arg(remote)
+        // Never hang waiting for credentials; if they're missing, try the next remote.
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::null());
+    git::log_command(&command);
+
+    let (success, stderr) = command
+        .output_checked_as(|context: OutputContext<Utf8Output>| {
+            Ok::<_, command_error::Error>((
+                context.status().success(),
+                context.output().stderr.clone(),
+            ))
+        })
+        .into_diagnostic()?;
+    eprint!("{stderr}");
+
+    if success {
+        Ok(Ok(()))
+    } else {
+        Ok(Err(FailureKind::classify(&stderr)))
+    }
+}
@@ -0,0 +1,90 @@
+//! Finding and creating forks to push to when we can't push to a repository.
+
+use command_error::CommandExt;
+use miette::miette;
+use miette::IntoDiagnostic;
+
+use crate::git;
+use crate::remote_url::Forge;
+use crate::Config;
+
+/// The name of the remote `--fork` adds for the fork.
+pub const FORK_REMOTE: &str = "fork";
+
+/// Suggest what to do after `remote` refused to let us push, given what `git push` printed.
+///
+/// Returns `None` for hosts we don't know how to fork on.
+pub fn permission_help(config: &Config, remote: &str, output: &str) -> Option<String> {
+    let url = config.remote_url(remote).ok()??;
+    let forge = url.web(&config.file.host_templates).forge?;
+
+    let user = denied_user(output).or_else(|| {
+        let key = match forge {
+            Forge::GitHub => "github.user",
+            Forge::GitLab => "gitlab.user",
+            Forge::Gitea => "gitea.user",
+            Forge::Bitbucket => "bitbucket.user",
+            Forge::Sourcehut => "sourcehut.user",
+        };
+        git::config_get(key).ok().flatten()
+    });
+    let fork = user.and_then(|user| {
+        config.list_remotes().ok()?.into_iter().find(|other| {
+            other.name != remote
+                && other
+                    .resolved_push_url
+                    .as_ref()
+                    .is_some_and(|other| other.host == url.host && other.owner() == user)
+        })
+    });
+
+    Some(match (fork, forge) {
+        (Some(fork), _) => format!("Push to your fork with `git upstream {fork}`"),
+        (None, Forge::GitHub) => {
+            "Fork the repository and push to the fork with `git upstream --fork`".to_owned()
+        }
+        (None, _) => format!(
+            "Fork the repository on {}, add the fork as a remote with `git remote add \
+             {FORK_REMOTE} URL`, and push to it with `git upstream {FORK_REMOTE}`",
+            url.host
+        ),
+    })
+}
+
+/// Get the user GitHub says was denied access, from a message like
+/// `remote: Permission to owner/repo.git denied to user.`
+fn denied_user(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let (_, rest) = line.split_once("Permission to ")?;
+        let (_, user) = rest.split_once(" denied to ")?;
+        Some(user.trim().trim_end_matches('.').to_owned())
+    })
+}
+
+/// Fork the current repository with `gh repo fork` for `--fork`, adding it as the
+/// [`FORK_REMOTE`] remote, unless that remote already exists.
+pub fn create(config: &Config) -> miette::Result<()> {
+    if config.has_remote(FORK_REMOTE)? {
+        tracing::info!("Using the existing {FORK_REMOTE} remote");
+        return Ok(());
+    }
+
+    if git::repo_command("gh")
+        .arg("--version")
+        .output_checked()
+        .is_err()
+    {
+        return Err(miette!(
+            help = "Install it from https://cli.github.com/",
+            "`--fork` needs the GitHub CLI, `gh`"
+        ));
+    }
+
+    let mut command = git::repo_command("gh");
+    command.args(["repo", "fork", "--remote", "--remote-name", FORK_REMOTE]);
+    // Keep stdout for `git upstream`'s result.
+    command.stdout(std::io::stderr());
+    git::log_command(&command);
+    command.status_checked().into_diagnostic()?;
+    Ok(())
+}
@@ -0,0 +1,332 @@
+//! Small wrappers around `git` invocations.
+
+use std::ffi::OsStr;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::PoisonError;
+use std::sync::RwLock;
+
+use command_error::CommandExt;
+use command_error::OutputContext;
+use command_error::Utf8ProgramAndArgs;
+use miette::miette;
+use miette::IntoDiagnostic;
+use owo_colors::OwoColorize;
+use owo_colors::Style;
+use utf8_command::Utf8Output;
+
+use crate::git_dir;
+
+/// The repository to run commands in, from `-C`, or `None` for the current directory.
+static DIRECTORY: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Run commands in `path` instead of the current directory, like `git -C`.
+///
+/// A relative `path` is resolved against the current directory now, rather than each time a
+/// command runs.
+pub fn set_directory(path: Option<&Path>) -> miette::Result<()> {
+    let path = match path {
+        Some(path) => {
+            let absolute = std::path::absolute(path).into_diagnostic()?;
+            let is_repo = absolute.is_dir()
+                && Command::new("git")
+                    .args(["rev-parse", "--git-dir"])
+                    .current_dir(&absolute)
+                    .output()
+                    .is_ok_and(|output| output.status.success());
+            if !is_repo {
+                return Err(miette!(
+                    help = "Pass `-C` a path inside a Git repository",
+                    "{} isn't a Git repository",
+                    path.display()
+                ));
+            }
+            Some(absolute)
+        }
+        None => None,
+    };
+    *DIRECTORY.write().unwrap_or_else(PoisonError::into_inner) = path;
+    Ok(())
+}
+
+/// Get the directory commands run in, from `-C`, or `None` for the current directory.
+pub fn directory() -> Option<PathBuf> {
+    DIRECTORY
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Start a `git` command, which runs in the `-C` directory if there is one.
+///
+/// Every `git` invocation should start here, so that `-C` applies to all of them.
+pub fn command() -> Command {
+    repo_command("git")
+}
+
+/// Start a command for a tool which reads the repository it runs in, like `gh` or `jj`, in the
+/// `-C` directory if there is one.
+pub fn repo_command(program: impl AsRef<OsStr>) -> Command {
+    let mut command = Command::new(program);
+    if let Some(directory) = directory() {
+        command.current_dir(directory);
+    }
+    command
+}
+
+/// Resolve a path printed by `git`, which is relative to the directory it ran in.
+fn resolve(path: &str) -> PathBuf {
+    match directory() {
+        Some(directory) => directory.join(path),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Resolve `rev` to an object name with `git rev-parse --verify`.
+///
+/// Returns `None` if `rev` doesn't exist.
+pub fn rev_parse(rev: &str) -> miette::Result<Option<String>> {
+    command()
+        .args(["rev-parse", "--verify", "--quiet", rev])
+        .output_checked_as(|context: OutputContext<Utf8Output>| {
+            if context.status().success() {
+                Ok(Some(context.output().stdout.trim().to_owned()))
+            } else if context.status().code() == Some(1) {
+                Ok(None)
+            } else {
+                Err(context.error())
+            }
+        })
+        .into_diagnostic()
+}
+
+/// Get a Git configuration value with `git config --get`.
+///
+/// Returns `None` if `key` isn't set.
+pub fn config_get(key: &str) -> miette::Result<Option<String>> {
+    command()
+        .args(["config", "--get", key])
+        .output_checked_as(|context: OutputContext<Utf8Output>| {
+            if context.status().success() {
+                Ok(Some(context.output().stdout.trim().to_owned()))
+            } else if context.status().code() == Some(1) {
+                Ok(None)
+            } else {
+                Err(context.error())
+            }
+        })
+        .into_diagnostic()
+}
+
+/// Check if `ancestor` is an ancestor of (or the same commit as) `descendant`.
+pub fn is_ancestor(ancestor: &str, descendant: &str) -> miette::Result<bool> {
+    command()
+        .args(["merge-base", "--is-ancestor", ancestor, descendant])
+        .status_checked_as(|context| match context.status().code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => Err(context.error()),
+        })
+        .into_diagnostic()
+}
+
+/// Log a command that's about to be run, in bold.
+pub fn log_command(command: &Command) {
+    tracing::info!(
+        "{}",
+        format!("$ {}", Utf8ProgramAndArgs::from(command)).if_supports_color(
+            owo_colors::Stream::Stderr,
+            |text| Style::new().bold().underline().style(text)
+        )
+    );
+}
+
+/// Add `--progress` after the subcommand of a `git` command, so that it reports progress even
+/// when its stderr is captured.
+pub fn with_progress(command: &Command) -> Command {
+    let mut args = command.get_args();
+    let mut ret = without_args(command);
+    ret.args(args.next());
+    ret.arg("--progress");
+    ret.args(args);
+    ret
+}
+
+/// Copy `command`'s program, working directory, and environment, including variables it
+/// removes, but not its arguments.
+pub fn without_args(command: &Command) -> Command {
+    let mut ret = Command::new(command.get_program());
+    if let Some(dir) = command.get_current_dir() {
+        ret.current_dir(dir);
+    }
+    for (key, value) in command.get_envs() {
+        match value {
+            Some(value) => ret.env(key, value),
+            None => ret.env_remove(key),
+        };
+    }
+    ret
+}
+
+/// A branch's upstream, as recorded in `branch.<name>.remote` and `branch.<name>.merge`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upstream {
+    /// The remote name.
+    pub remote: String,
+    /// The branch name on the remote, without `refs/heads/`.
+    pub branch: String,
+}
+
+impl Upstream {
+    /// Get the upstream configured for `branch`, if any.
+    pub fn of(branch: &str) -> miette::Result<Option<Self>> {
+        let remote = config_get(&format!("branch.{branch}.remote"))?;
+        let merge = config_get(&format!("branch.{branch}.merge"))?;
+        Ok(match (remote, merge) {
+            (Some(remote), Some(merge)) => Some(Self {
+                remote,
+                branch: merge
+                    .strip_prefix("refs/heads/")
+                    .map(ToOwned::to_owned)
+                    .unwrap_or(merge),
+            }),
+            _ => None,
+        })
+    }
+}
+
+impl std::fmt::Display for Upstream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.remote, self.branch)
+    }
+}
+
+/// Get the name of the current branch with `git rev-parse --abbrev-ref HEAD`, or by reading
+/// `HEAD` directly when that gives the same answer.
+///
+/// This is `HEAD` if no branch is checked out.
+pub fn current_branch() -> miette::Result<String> {
+    if let Some(branch) = git_dir::current_branch() {
+        return Ok(branch);
+    }
+    Ok(command()
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .output_checked_utf8()
+        .into_diagnostic()?
+        .stdout
+        .trim()
+        .to_owned())
+}
+
+/// Resolve a path inside the Git directory with `git rev-parse --git-path`.
+pub fn git_path(path: &str) -> miette::Result<PathBuf> {
+    Ok(git_paths(&[path])?.remove(0))
+}
+
+/// Like [`git_path`], but resolve several paths with one `git rev-parse`.
+pub fn git_paths(paths: &[&str]) -> miette::Result<Vec<PathBuf>> {
+    let mut command = command();
+    command.arg("rev-parse");
+    for path in paths {
+        command.args(["--git-path", path]);
+    }
+    let output = command.output_checked_utf8().into_diagnostic()?.stdout;
+    let resolved: Vec<_> = output.lines().map(resolve).collect();
+    if resolved.len() != paths.len() {
+        return Err(miette!(
+            "`git rev-parse` resolved {} paths, not {}",
+            resolved.len(),
+            paths.len()
+        ));
+    }
+    Ok(resolved)
+}
+
+/// Check if the repository is a shallow clone with `git rev-parse --is-shallow-repository`.
+pub fn is_shallow() -> miette::Result<bool> {
+    Ok(command()
+        .args(["rev-parse", "--is-shallow-repository"])
+        .output_checked_utf8()
+        .into_diagnostic()?
+        .stdout
+        .trim()
+        == "true")
+}
+
+/// Run `git rev-parse FLAG` for a boolean flag like `--is-bare-repository`.
+///
+/// Returns `None` outside of a Git repository.
+fn rev_parse_bool(flag: &str) -> miette::Result<Option<bool>> {
+    command()
+        .args(["rev-parse", flag])
+        .output_checked_as(|context: OutputContext<Utf8Output>| {
+            Ok::<_, command_error::Error>(
+                context
+                    .status()
+                    .success()
+                    .then(|| context.output().stdout.trim() == "true"),
+            )
+        })
+        .into_diagnostic()
+}
+
+/// The directory Git runs in, for error messages.
+fn cwd_display() -> String {
+    directory()
+        .map(Ok)
+        .unwrap_or_else(std::env::current_dir)
+        .map(|cwd| cwd.display().to_string())
+        .unwrap_or_else(|_| "The current directory".to_owned())
+}
+
+fn not_a_repository() -> miette::Report {
+    miette!(
+        help = "`cd` into a Git repository, or create one with `git init`",
+        "{} isn't inside a Git work tree",
+        cwd_display()
+    )
+}
+
+/// Check that we're in a Git repository, which may be bare, with a friendly error if we're not.
+pub fn require_repository() -> miette::Result<()> {
+    match rev_parse_bool("--is-inside-work-tree")? {
+        Some(_) => Ok(()),
+        None => Err(not_a_repository()),
+    }
+}
+
+/// Check that we're in a Git work tree, with a friendly error if we're not.
+pub fn require_work_tree() -> miette::Result<()> {
+    match rev_parse_bool("--is-inside-work-tree")? {
+        Some(true) => Ok(()),
+        None => Err(not_a_repository()),
+        Some(false) if rev_parse_bool("--is-bare-repository")? == Some(true) => Err(miette!(
+            help = "Run `git upstream` in a clone with a checked-out branch; to publish a bare \
+                    repository's branches, use `git push --all` or `git push --mirror`",
+            "{} is a bare repository, so there's no checked-out branch to push",
+            cwd_display()
+        )),
+        Some(false) => Err(miette!(
+            help = "`cd` out of the `.git` directory into the work tree",
+            "{} is inside a Git directory, not a work tree",
+            cwd_display()
+        )),
+    }
+}
+
+/// Get the Git directory shared by all worktrees with `git rev-parse --git-common-dir`.
+///
+/// Returns `None` outside of a Git repository.
+pub fn common_dir() -> miette::Result<Option<PathBuf>> {
+    command()
+        .args(["rev-parse", "--git-common-dir"])
+        .output_checked_as(|context: OutputContext<Utf8Output>| {
+            if context.status().success() {
+                Ok(Some(resolve(context.output().stdout.trim())))
+            } else {
+                Ok::<_, command_error::Error>(None)
+            }
+        })
+        .into_diagnostic()
+}
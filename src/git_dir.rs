@@ -0,0 +1,295 @@
+//! Reading the Git directory directly, for queries which are common enough that spawning
+//! `git` for them adds noticeable latency.
+//!
+//! These only handle ordinary repositories and worktrees. Anything unusual returns `None`, so
+//! callers can fall back to asking `git`, which stays the source of truth.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use fs_err as fs;
+
+use crate::git;
+
+/// Environment variables which change how Git finds the repository.
+const DISCOVERY_VARIABLES: &[&str] = &[
+    "GIT_DIR",
+    "GIT_WORK_TREE",
+    "GIT_COMMON_DIR",
+    "GIT_CEILING_DIRECTORIES",
+    "GIT_DISCOVERY_ACROSS_FILESYSTEM",
+];
+
+/// A repository's Git directories.
+struct GitDir {
+    /// The directory with `HEAD`, which is per-worktree.
+    git_dir: PathBuf,
+    /// The directory with the refs, shared between worktrees.
+    common_dir: PathBuf,
+}
+
+impl GitDir {
+    /// Find the Git directory for `cwd`, the way `git` does for ordinary repositories.
+    fn discover(cwd: &Path) -> Option<Self> {
+        if DISCOVERY_VARIABLES
+            .iter()
+            .any(|variable| std::env::var_os(variable).is_some())
+        {
+            return None;
+        }
+
+        let (work_tree, dot_git) = cwd.ancestors().find_map(|dir| {
+            let dot_git = dir.join(".git");
+            dot_git.exists().then(|| (dir.to_owned(), dot_git))
+        })?;
+        // A bare repository between here and the work tree would be found first by `git`.
+        if cwd
+            .ancestors()
+            .take_while(|dir| *dir != work_tree)
+            .any(looks_like_git_dir)
+        {
+            return None;
+        }
+        if !owned_by_us(&work_tree) {
+            // `git` may refuse to work in it, depending on `safe.directory`.
+            return None;
+        }
+
+        let git_dir = if dot_git.is_dir() {
+            dot_git
+        } else {
+            // A worktree or submodule, where `.git` is a file like `gitdir: PATH`.
+            let contents = fs::read_to_string(&dot_git).ok()?;
+            let path = Path::new(contents.strip_prefix("gitdir: ")?.trim());
+            work_tree.join(path)
+        };
+        if !looks_like_git_dir(&git_dir) || git_dir.join("reftable").exists() {
+            return None;
+        }
+
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common_dir) => git_dir.join(common_dir.trim()),
+            Err(_) => git_dir.clone(),
+        };
+        Some(Self {
+            git_dir,
+            common_dir,
+        })
+    }
+
+    /// Check if `name` (like `refs/heads/main`) exists, as a loose or packed ref.
+    fn has_ref(&self, name: &str) -> bool {
+        if self.common_dir.join(name).is_file() {
+            return true;
+        }
+        fs::read_to_string(self.common_dir.join("packed-refs")).is_ok_and(|packed| {
+            packed.lines().any(|line| {
+                line.split_once(' ')
+                    .is_some_and(|(_, packed)| packed == name)
+            })
+        })
+    }
+}
+
+/// Check if `dir` looks like a Git directory, like `git` does.
+fn looks_like_git_dir(dir: &Path) -> bool {
+    dir.join("HEAD").is_file() && dir.join("objects").is_dir() && dir.join("refs").is_dir()
+        || dir.join("commondir").is_file() && dir.join("HEAD").is_file()
+}
+
+#[cfg(unix)]
+fn owned_by_us(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    // SAFETY: `geteuid` can't fail.
+    let uid = unsafe { libc::geteuid() };
+    fs::metadata(path).is_ok_and(|metadata| metadata.uid() == uid)
+}
+
+#[cfg(not(unix))]
+fn owned_by_us(_path: &Path) -> bool {
+    // Checking ownership on Windows needs the security APIs, so always ask `git`.
+    false
+}
+
+/// Get the current branch like `git rev-parse --abbrev-ref HEAD` does: the branch name, or
+/// `HEAD` if no branch is checked out.
+///
+/// Returns `None` if `git` should be asked instead, including when the branch doesn't have any
+/// commits yet (which `git` reports as an error) or when `git` would disambiguate the name,
+/// like `heads/main` if there's also a tag named `main`.
+pub fn current_branch() -> Option<String> {
+    let cwd = match git::directory() {
+        Some(directory) => directory,
+        None => std::env::current_dir().ok()?,
+    };
+    current_branch_in(&cwd)
+}
+
+/// Get the current branch of the repository `cwd` is in, like [`current_branch`].
+fn current_branch_in(cwd: &Path) -> Option<String> {
+    let git_dir = GitDir::discover(cwd)?;
+    let head = fs::read_to_string(git_dir.git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+
+    let Some(target) = head.strip_prefix("ref: ") else {
+        // A detached `HEAD` is a commit hash.
+        return head
+            .bytes()
+            .all(|byte| byte.is_ascii_hexdigit())
+            .then(|| "HEAD".to_owned());
+    };
+    let branch = target.strip_prefix("refs/heads/")?;
+    if !git_dir.has_ref(target) {
+        return None;
+    }
+    let ambiguous = [
+        format!("refs/{branch}"),
+        format!("refs/tags/{branch}"),
+        format!("refs/remotes/{branch}"),
+        format!("refs/remotes/{branch}/HEAD"),
+    ];
+    if branch == "HEAD" || ambiguous.iter().any(|name| git_dir.has_ref(name)) {
+        return None;
+    }
+    Some(branch.to_owned())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::process::Command;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+
+    /// A scratch directory, deleted when dropped.
+    pub(crate) struct Scratch(pub(crate) PathBuf);
+
+    impl Scratch {
+        pub(crate) fn new() -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "git-upstream-unit-{}-{}",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            Self(path.canonicalize().unwrap())
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Run `git` in `dir`, panicking if it fails.
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .current_dir(dir)
+            .args(args)
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_AUTHOR_NAME", "Test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "Test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap().trim().to_owned()
+    }
+
+    /// Create a repository in a scratch directory with one commit on `main`.
+    fn repo() -> Scratch {
+        let scratch = Scratch::new();
+        git(&scratch.0, &["init", "--quiet", "--initial-branch=main"]);
+        git(
+            &scratch.0,
+            &["commit", "--quiet", "--allow-empty", "--message=Initial"],
+        );
+        scratch
+    }
+
+    /// Check that reading the Git directory agrees with `git rev-parse --abbrev-ref HEAD`.
+    fn assert_agrees_with_git(cwd: &Path, expected: &str) {
+        assert_eq!(git(cwd, &["rev-parse", "--abbrev-ref", "HEAD"]), expected);
+        assert_eq!(current_branch_in(cwd).as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn branch() {
+        let repo = repo();
+        assert_agrees_with_git(&repo.0, "main");
+    }
+
+    #[test]
+    fn detached_head() {
+        let repo = repo();
+        git(&repo.0, &["switch", "--quiet", "--detach"]);
+        assert_agrees_with_git(&repo.0, "HEAD");
+    }
+
+    #[test]
+    fn linked_worktree() {
+        let repo = repo();
+        let worktree = repo.0.join("worktree");
+        git(
+            &repo.0,
+            &["worktree", "add", "--quiet", "-b", "feature", "worktree"],
+        );
+        assert!(worktree.join(".git").is_file());
+
+        let git_dir = GitDir::discover(&worktree).unwrap();
+        assert!(git_dir.git_dir.join("commondir").is_file());
+        assert_eq!(
+            git_dir.common_dir.canonicalize().unwrap(),
+            repo.0.join(".git")
+        );
+        assert_agrees_with_git(&worktree, "feature");
+    }
+
+    #[test]
+    fn packed_ref() {
+        let repo = repo();
+        git(&repo.0, &["pack-refs", "--all"]);
+        assert!(!repo.0.join(".git/refs/heads/main").exists());
+        assert_agrees_with_git(&repo.0, "main");
+    }
+
+    #[test]
+    fn unborn_branch() {
+        let repo = Scratch::new();
+        git(&repo.0, &["init", "--quiet", "--initial-branch=main"]);
+        // `git` fails here, so it has to be asked for its error.
+        let output = Command::new("git")
+            .current_dir(&repo.0)
+            .args(["rev-parse", "--abbrev-ref", "HEAD"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert_eq!(current_branch_in(&repo.0), None);
+    }
+
+    #[test]
+    fn ambiguous_branch() {
+        let repo = repo();
+        git(&repo.0, &["tag", "main"]);
+        assert_eq!(current_branch_in(&repo.0), None);
+    }
+
+    #[test]
+    fn subdirectory() {
+        let repo = repo();
+        let subdirectory = repo.0.join("a/b");
+        fs::create_dir_all(&subdirectory).unwrap();
+        assert_agrees_with_git(&subdirectory, "main");
+    }
+}
@@ -0,0 +1,233 @@
+//! The `git` operations the push logic depends on, behind a trait so they can be faked.
+//!
+//! [`SubprocessGit`] runs `git`, and [`FakeGit`] is programmed with what each remote does when
+//! pushed to, for testing which remotes get tried without a network.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+
+use miette::IntoDiagnostic;
+
+use crate::git;
+use crate::snapshot::RepoSnapshot;
+use crate::tee;
+
+/// The `git` operations the push logic depends on.
+pub trait GitRunner: std::fmt::Debug + Send + Sync {
+    /// Read the repository's remotes, refs, and configuration.
+    fn snapshot(&self) -> miette::Result<RepoSnapshot>;
+
+    /// Get the current branch, or `HEAD` if no branch is checked out.
+    fn current_branch(&self) -> miette::Result<String>;
+
+    /// Run `git push`, showing its output as it runs.
+    fn push(&self, request: &PushRequest) -> miette::Result<PushResult>;
+
+    /// Resolve `rev` to a commit hash, like [`git::rev_parse`], or `None` if it doesn't exist.
+    fn rev_parse(&self, rev: &str) -> miette::Result<Option<String>>;
+
+    /// Check if the repository is a shallow clone, like [`git::is_shallow`].
+    fn is_shallow(&self) -> miette::Result<bool>;
+
+    /// Resolve a path in the Git directory, like [`git::git_path`].
+    fn git_path(&self, path: &str) -> miette::Result<PathBuf>;
+}
+
+/// A `git push` to run.
+#[derive(Debug)]
+pub struct PushRequest {
+    pub remote: String,
+    pub branch: String,
+    /// The full command, from [`crate::Config::push_command`].
+    pub command: Command,
+    /// How long to let `git push` run before killing it.
+    pub timeout: Option<Duration>,
+}
+
+/// What happened when running `git push`.
+#[derive(Debug, Clone, Default)]
+pub struct PushResult {
+    /// The exit code, or `None` if `git push` was killed.
+    pub exit_code: Option<i32>,
+    /// Whether `git push` was killed because it ran for too long.
+    pub timed_out: bool,
+    /// Whether the user pressed Ctrl-C while `git push` ran.
+    pub interrupted: bool,
+    /// The `--porcelain` output.
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl PushResult {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Runs `git`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SubprocessGit;
+
+impl GitRunner for SubprocessGit {
+    fn snapshot(&self) -> miette::Result<RepoSnapshot> {
+        RepoSnapshot::read()
+    }
+
+    fn current_branch(&self) -> miette::Result<String> {
+        git::current_branch()
+    }
+
+    fn push(&self, request: &PushRequest) -> miette::Result<PushResult> {
+        let captured = tee::run(&request.command, false, request.timeout).into_diagnostic()?;
+        Ok(PushResult {
+            exit_code: captured.status.code(),
+            timed_out: captured.timed_out,
+            interrupted: captured.interrupted,
+            stdout: captured.stdout,
+            stderr: captured.stderr,
+        })
+    }
+
+    fn rev_parse(&self, rev: &str) -> miette::Result<Option<String>> {
+        git::rev_parse(rev)
+    }
+
+    fn is_shallow(&self) -> miette::Result<bool> {
+        git::is_shallow()
+    }
+
+    fn git_path(&self, path: &str) -> miette::Result<PathBuf> {
+        git::git_path(path)
+    }
+}
+
+/// What a [`FakeGit`] remote does when it's pushed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FakeOutcome {
+    /// Accept the push.
+    Success,
+    /// Reject the push because the user doesn't have access, like GitHub's `403`.
+    AuthFailure,
+    /// Reject the push because the remote branch has commits the local branch doesn't.
+    NonFastForward,
+    /// Take longer than the timeout, without waiting for it.
+    Hang,
+}
+
+/// A fake repository on `branch` with remotes which do what they're programmed to, recording
+/// which remotes were pushed to.
+///
+/// Its branch points to [`FakeGit::COMMIT`], it isn't shallow, and its Git directory doesn't
+/// exist, so it has no hooks. The checks before pushing, like `check-wip`, and the push journal
+/// still run `git`, so tests should run in a (local) repository.
+#[derive(Debug)]
+pub struct FakeGit {
+    branch: String,
+    remotes: Vec<(String, FakeOutcome)>,
+    pushes: Mutex<Vec<String>>,
+}
+
+impl FakeGit {
+    /// The commit the fake branch points to.
+    pub const COMMIT: &str = "1111111111111111111111111111111111111111";
+
+    /// A fake with no remotes, on `branch`.
+    pub fn new(branch: &str) -> Self {
+        Self {
+            branch: branch.to_owned(),
+            remotes: Vec::new(),
+            pushes: Mutex::default(),
+        }
+    }
+
+    /// Add a remote which does `outcome` when pushed to. Its default branch is `main`.
+    pub fn remote(mut self, name: &str, outcome: FakeOutcome) -> Self {
+        self.remotes.push((name.to_owned(), outcome));
+        self
+    }
+
+    /// The remotes pushed to so far, in order, including retries.
+    pub fn pushes(&self) -> Vec<String> {
+        self.pushes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn url(remote: &str) -> String {
+        format!("https://example.com/{remote}/repo.git")
+    }
+}
+
+impl GitRunner for FakeGit {
+    fn snapshot(&self) -> miette::Result<RepoSnapshot> {
+        let mut snapshot = RepoSnapshot::default();
+        for (name, _) in &self.remotes {
+            snapshot.insert_remote(name, &Self::url(name));
+        }
+        Ok(snapshot)
+    }
+
+    fn current_branch(&self) -> miette::Result<String> {
+        Ok(self.branch.clone())
+    }
+
+    fn push(&self, request: &PushRequest) -> miette::Result<PushResult> {
+        self.pushes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(request.remote.clone());
+        let outcomes: BTreeMap<_, _> = self.remotes.iter().cloned().collect();
+        let outcome = outcomes
+            .get(&request.remote)
+            .copied()
+            .ok_or_else(|| miette::miette!("FakeGit has no remote named {}", request.remote))?;
+
+        let url = Self::url(&request.remote);
+        let refspec = format!("refs/heads/{0}:refs/heads/{0}", request.branch);
+        Ok(match outcome {
+            FakeOutcome::Success => PushResult {
+                exit_code: Some(0),
+                stdout: format!("To {url}\n*\t{refspec}\t[new branch]\nDone\n"),
+                ..Default::default()
+            },
+            FakeOutcome::AuthFailure => PushResult {
+                exit_code: Some(128),
+                stderr: format!(
+                    "remote: Permission to {}/repo.git denied to someone.\n\
+                     fatal: unable to access '{url}/': The requested URL returned error: 403\n",
+                    request.remote
+                ),
+                ..Default::default()
+            },
+            FakeOutcome::NonFastForward => PushResult {
+                exit_code: Some(1),
+                stdout: format!("To {url}\n!\t{refspec}\t[rejected] (non-fast-forward)\nDone\n"),
+                stderr: format!("error: failed to push some refs to '{url}'\n"),
+                ..Default::default()
+            },
+            FakeOutcome::Hang => PushResult {
+                exit_code: None,
+                timed_out: true,
+                ..Default::default()
+            },
+        })
+    }
+
+    fn rev_parse(&self, rev: &str) -> miette::Result<Option<String>> {
+        Ok((rev == format!("refs/heads/{}", self.branch)).then(|| Self::COMMIT.to_owned()))
+    }
+
+    fn is_shallow(&self) -> miette::Result<bool> {
+        Ok(false)
+    }
+
+    fn git_path(&self, path: &str) -> miette::Result<PathBuf> {
+        Ok(Path::new("/nonexistent/.git").join(path))
+    }
+}
@@ -0,0 +1,148 @@
+//! Parsing `git --version` output.
+
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use command_error::CommandExt;
+use miette::miette;
+use miette::IntoDiagnostic;
+
+use crate::git;
+
+/// A Git version, like `2.39.2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GitVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl GitVersion {
+    /// Git 1.8.5 added `git push --force-with-lease`.
+    pub const FORCE_WITH_LEASE: Self = Self::new(1, 8, 5);
+    /// Git 2.30 added `git push --force-if-includes`.
+    pub const FORCE_IF_INCLUDES: Self = Self::new(2, 30, 0);
+
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Get the version of the `git` on `PATH`, with installation hints if there isn't one.
+    pub fn detect() -> miette::Result<Self> {
+        let output = match git::command().arg("--version").output_checked_utf8() {
+            Ok(output) => output,
+            Err(_) if which_git().is_none() => {
+                let hint = if cfg!(target_os = "macos") {
+                    "Install Git with `xcode-select --install` or `brew install git`"
+                } else if cfg!(windows) {
+                    "Install Git from https://git-scm.com/download/win"
+                } else {
+                    "Install Git with your package manager, like `apt install git` or `dnf \
+                     install git`"
+                };
+                return Err(miette!(
+                    help = hint,
+                    "`git-upstream` needs Git, but there's no `git` on `$PATH`"
+                ));
+            }
+            Err(err) => return Err(err).into_diagnostic(),
+        };
+        let version = output.stdout.trim();
+        Self::parse(version).ok_or_else(|| miette!("Failed to parse Git version: {version:?}"))
+    }
+
+    /// Check that this version of Git supports `flag`, which was added in `since`.
+    pub fn require(self, since: Self, flag: &str) -> miette::Result<()> {
+        if self >= since {
+            Ok(())
+        } else {
+            Err(miette!(
+                help = format!("Upgrade to Git {since} or newer"),
+                "Git {self} doesn't support `{flag}`, which was added in Git {since}"
+            ))
+        }
+    }
+
+    /// Parse `git --version` output, like `git version 2.39.2 (Apple Git-143)` or
+    /// `git version 2.45.1.windows.1`.
+    pub fn parse(output: &str) -> Option<Self> {
+        let version = output
+            .trim()
+            .strip_prefix("git version ")?
+            .split_whitespace()
+            .next()?;
+        // Pre-releases look like `2.30.0-rc1` or `2.30.0.rc1`, so only read leading digits.
+        let mut components = version.split('.').map(|component| {
+            let end = component
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(component.len());
+            component[..end].parse::<u32>().ok()
+        });
+        let major = components.next()??;
+        let minor = components.next().flatten().unwrap_or(0);
+        let patch = components.next().flatten().unwrap_or(0);
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl Display for GitVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Find `git` on `$PATH`.
+fn which_git() -> Option<PathBuf> {
+    let names: &[&str] = if cfg!(windows) {
+        &["git.exe", "git.cmd", "git"]
+    } else {
+        &["git"]
+    };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let cases = [
+            ("git version 2.39.3 (Apple Git-146)", Some((2, 39, 3))),
+            ("git version 2.45.1.windows.1", Some((2, 45, 1))),
+            ("git version 2.30.0-rc1", Some((2, 30, 0))),
+            ("git version 2.45.0.rc0", Some((2, 45, 0))),
+            ("git version 2.30-rc0", Some((2, 30, 0))),
+            ("git version 2.43.0\n", Some((2, 43, 0))),
+            ("git version 1.8.5.6", Some((1, 8, 5))),
+            ("git version 3", Some((3, 0, 0))),
+            ("git version", None),
+            ("git version x.y.z", None),
+            ("hub version 2.14.2", None),
+            ("", None),
+        ];
+        for (output, expected) in cases {
+            assert_eq!(
+                GitVersion::parse(output),
+                expected.map(|(major, minor, patch)| GitVersion::new(major, minor, patch)),
+                "{output:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn compares() {
+        assert!(GitVersion::new(2, 30, 0) >= GitVersion::FORCE_IF_INCLUDES);
+        assert!(GitVersion::new(2, 29, 9) < GitVersion::FORCE_IF_INCLUDES);
+        assert!(GitVersion::new(10, 0, 0) > GitVersion::new(9, 99, 99));
+        assert!(GitVersion::new(2, 29, 0)
+            .require(GitVersion::FORCE_IF_INCLUDES, "--force-if-includes")
+            .is_err());
+    }
+}
@@ -0,0 +1,81 @@
+//! Minimal glob matching for branch-name patterns.
+
+/// Check if `text` matches the glob `pattern`.
+///
+/// `*` matches any sequence of characters (including `/`) and `?` matches any single
+/// character. All other characters match themselves.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    let mut p = 0;
+    let mut t = 0;
+    // The position of the last `*` in the pattern, and the position in the text it was matched
+    // at, for backtracking.
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(&c) if c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    // Let the last `*` consume one more character and try again.
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches() {
+        let cases = [
+            ("main", "main", true),
+            ("main", "mainline", false),
+            ("main", "", false),
+            ("", "", true),
+            ("*", "", true),
+            ("*", "release/1.0", true),
+            ("release/*", "release/1.0", true),
+            ("release/*", "release/1.0/hotfix", true),
+            ("release/*", "release", false),
+            ("release/*", "prerelease/1.0", false),
+            ("*/wip", "me/wip", true),
+            ("*/wip", "me/wip/more", false),
+            ("*-wip-*", "feature-wip-login", true),
+            ("*-wip-*", "feature-wip", false),
+            ("v?.?", "v1.2", true),
+            ("v?.?", "v1.23", false),
+            ("v?.?", "v1.", false),
+            ("a*b*c", "aXbYbZc", true),
+            ("a*b*c", "aXcYb", false),
+            ("**", "anything", true),
+            ("é*", "école", true),
+            ("[abc]", "[abc]", true),
+            ("[abc]", "a", false),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob_match(pattern, text), expected, "{pattern:?} {text:?}");
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Refusing to push while a rebase, merge, or other operation is stopped partway through.
+
+use std::fmt::Display;
+
+use miette::miette;
+
+use crate::git;
+use crate::Config;
+
+/// An operation which stops partway through when there are conflicts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Rebase,
+    Am,
+    Merge,
+    CherryPick,
+    Revert,
+}
+
+impl Operation {
+    /// The command which continues or aborts the operation, like `rebase`.
+    fn command(self) -> &'static str {
+        match self {
+            Self::Rebase => "rebase",
+            Self::Am => "am",
+            Self::Merge => "merge",
+            Self::CherryPick => "cherry-pick",
+            Self::Revert => "revert",
+        }
+    }
+
+    /// Find the operation in progress, if there is one, from the files it leaves in the Git
+    /// directory. In worktrees, these are per-worktree, so they're found with
+    /// `git rev-parse --git-path`.
+    pub fn in_progress() -> miette::Result<Option<Self>> {
+        let markers = [
+            ("rebase-merge", Self::Rebase),
+            ("rebase-apply", Self::Rebase),
+            ("MERGE_HEAD", Self::Merge),
+            ("CHERRY_PICK_HEAD", Self::CherryPick),
+            ("REVERT_HEAD", Self::Revert),
+        ];
+        let paths = git::git_paths(&markers.map(|(path, _)| path))?;
+        Ok(paths
+            .into_iter()
+            .zip(markers)
+            .find(|(path, _)| path.exists())
+            .map(|(path, (_, operation))| {
+                // `git am` uses the same directory as `git rebase --apply`, and marks it with
+                // `applying`.
+                if path.join("applying").exists() {
+                    Self::Am
+                } else {
+                    operation
+                }
+            }))
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rebase => write!(f, "a rebase"),
+            Self::Am => write!(f, "`git am`"),
+            Self::Merge => write!(f, "a merge"),
+            Self::CherryPick => write!(f, "a cherry-pick"),
+            Self::Revert => write!(f, "a revert"),
+        }
+    }
+}
+
+/// Refuse to push while an operation is in progress, because the branch may be half-rewritten,
+/// unless `--allow-in-progress` was given.
+pub fn check(config: &Config) -> miette::Result<()> {
+    if config.cli.allow_in_progress {
+        return Ok(());
+    }
+    match Operation::in_progress()? {
+        Some(operation) => {
+            let command = operation.command();
+            Err(miette!(
+                help = format!(
+                    "Finish it with `git {command} --continue`, or give up with `git {command} \
+                     --abort`; pass `--allow-in-progress` to push anyway"
+                ),
+                "Refusing to push while {operation} is in progress"
+            ))
+        }
+        None => Ok(()),
+    }
+}
@@ -1,18 +1,319 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use fs_err as fs;
 use miette::IntoDiagnostic;
+use serde::Serialize;
+use tracing::field::Field;
+use tracing::field::Visit;
+use tracing::span;
+use tracing::Event;
+use tracing::Level;
+use tracing::Subscriber;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::fmt::time::SystemTime;
+use tracing_subscriber::layer::Context;
 use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::Layer;
+use tracing_subscriber::Registry;
+
+use crate::color::strip_ansi;
+use crate::json;
+
+/// How logs are formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable logs.
+    #[default]
+    Pretty,
+    /// Human-readable logs, one line per event, with timestamps.
+    Compact,
+    /// One JSON object per line, without colors.
+    Json,
+}
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
 
-pub fn install_tracing(filter_directives: &str) -> miette::Result<()> {
+/// Install the logging layers: logs on stderr, filtered by `filter_directives`, and debug logs
+/// with timestamps for the returned [`LogFile`], once it's opened.
+///
+/// The log file is opened separately because the configuration file can set it, and logs from
+/// before the configuration file is loaded should still reach stderr.
+pub fn install_tracing(
+    filter_directives: &str,
+    format: LogFormat,
+    color: bool,
+) -> miette::Result<LogFile> {
     let env_filter = tracing_subscriber::EnvFilter::try_new(filter_directives).into_diagnostic()?;
 
-    let human_layer = tracing_human_layer::HumanLayer::new()
-        .with_output_writer(std::io::stderr())
-        .with_filter(env_filter);
+    let console_layer: BoxedLayer = match format {
+        LogFormat::Pretty => tracing_human_layer::HumanLayer::new()
+            .with_output_writer(std::io::stderr())
+            .with_color_output(color)
+            .with_filter(env_filter)
+            // Spans, like the remote being pushed to, are for structured logs; messages already
+            // say what they're about.
+            .with_filter(filter_fn(|metadata| !metadata.is_span()))
+            .boxed(),
+        LogFormat::Compact => tracing_subscriber::fmt::layer()
+            .compact()
+            .with_ansi(color)
+            .with_target(false)
+            .with_writer(std::io::stderr)
+            .with_filter(env_filter)
+            .boxed(),
+        LogFormat::Json => JsonLayer::new(std::io::stderr())
+            .with_filter(env_filter)
+            .boxed(),
+    };
+
+    let log_file = LogFile::default();
+    // Until the log file is opened, don't bother formatting events for it.
+    let file_filter = {
+        let log_file = log_file.clone();
+        filter_fn(move |metadata| *metadata.level() <= Level::DEBUG && log_file.is_open())
+            .with_max_level_hint(LevelFilter::DEBUG)
+    };
+    let file_layer: BoxedLayer = match format {
+        LogFormat::Json => JsonLayer::new(log_file.clone())
+            .with_filter(file_filter)
+            .boxed(),
+        LogFormat::Pretty | LogFormat::Compact => tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_target(false)
+            .with_writer(Mutex::new(StripAnsi(log_file.clone())))
+            .with_filter(file_filter)
+            .boxed(),
+    };
+
+    tracing_subscriber::registry()
+        .with(vec![console_layer, file_layer])
+        .init();
+
+    Ok(log_file)
+}
+
+/// The file debug logs are appended to, from `--log-file` or `log-file`. Logs are dropped until
+/// it's opened.
+#[derive(Debug, Clone, Default)]
+pub struct LogFile(Arc<Mutex<Option<fs::File>>>);
+
+impl LogFile {
+    /// Start appending logs to `path`, unless a log file is already open.
+    ///
+    /// Failing to open the log file is only a warning.
+    pub fn open(&self, path: &Path) {
+        let mut file = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if file.is_some() {
+            return;
+        }
+        match open_log_file(path) {
+            Ok(opened) => *file = Some(opened),
+            Err(err) => {
+                drop(file);
+                tracing::warn!("Failed to open log file {}: {err}", path.display());
+            }
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut *self.0.lock().unwrap_or_else(PoisonError::into_inner) {
+            Some(file) => file.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut *self.0.lock().unwrap_or_else(PoisonError::into_inner) {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn open_log_file(path: &Path) -> std::io::Result<fs::File> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    fs::OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// A writer which removes ANSI escape sequences, because log messages may include text styled
+/// with `owo-colors` or hyperlinks.
+///
+/// Each event is written in a single call, so sequences are never split across writes.
+struct StripAnsi<W>(W);
+
+impl<W: Write> Write for StripAnsi<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .write_all(strip_ansi(&String::from_utf8_lossy(buf)).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// A field value in JSON logs.
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+enum Value {
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(String),
+}
+
+/// The fields of an event or span.
+#[derive(Clone, Default, Serialize)]
+struct Fields(BTreeMap<&'static str, Value>);
+
+impl Visit for Fields {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name(), Value::Bool(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name(), Value::I64(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name(), Value::U64(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name(), Value::F64(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0
+            .insert(field.name(), Value::String(strip_ansi(value)));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(
+            field.name(),
+            Value::String(strip_ansi(&format!("{value:?}"))),
+        );
+    }
+}
+
+/// A span in a JSON log line.
+#[derive(Serialize)]
+struct SpanLine<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    fields: Fields,
+}
+
+/// A JSON log line, shaped like `tracing-subscriber`'s JSON output.
+#[derive(Serialize)]
+struct LogLine<'a> {
+    timestamp: String,
+    level: String,
+    target: &'a str,
+    fields: Fields,
+    spans: Vec<SpanLine<'a>>,
+}
+
+/// A layer which writes each event as a line of JSON.
+struct JsonLayer<W> {
+    writer: Mutex<W>,
+}
+
+impl<W> JsonLayer<W> {
+    fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl<S, W> Layer<S> for JsonLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: Write + Send + 'static,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            let mut extensions = span.extensions_mut();
+            // With JSON logs on stderr and in the log file, the other layer may have already
+            // recorded the same fields.
+            if extensions.get_mut::<Fields>().is_none() {
+                extensions.insert(fields);
+            }
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(fields) = span.extensions_mut().get_mut::<Fields>() {
+                values.record(fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
 
-    let registry = tracing_subscriber::registry();
+        let mut timestamp = String::new();
+        let _ = SystemTime.format_time(&mut Writer::new(&mut timestamp));
 
-    registry.with(human_layer).init();
+        let spans = ctx
+            .event_scope(event)
+            .map(|scope| {
+                scope
+                    .from_root()
+                    .map(|span| SpanLine {
+                        name: span.name(),
+                        fields: span
+                            .extensions()
+                            .get::<Fields>()
+                            .cloned()
+                            .unwrap_or_default(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
 
-    Ok(())
+        let line = LogLine {
+            timestamp,
+            level: event.metadata().level().to_string(),
+            target: event.metadata().target(),
+            fields,
+            spans,
+        };
+        if let Ok(line) = json::to_string(&line) {
+            if let Ok(mut writer) = self.writer.lock() {
+                let _ = writeln!(writer, "{line}");
+            }
+        }
+    }
 }
@@ -0,0 +1,86 @@
+//! Handling Ctrl-C while `git` runs, so that an interrupted push stops the run instead of
+//! falling through to the next remote.
+
+use std::fmt::Display;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use miette::Diagnostic;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether Ctrl-C was pressed while a [`Catch`] was active.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// While this is alive, Ctrl-C (and Ctrl-Break on Windows) sets [`interrupted`] instead of
+/// killing us, so we can clean up after the child process. The child gets the signal too,
+/// because it's in the same process group or console.
+///
+/// Outside of this, Ctrl-C kills us as usual, so prompts can still be interrupted.
+pub struct Catch(());
+
+impl Catch {
+    pub fn new() -> Self {
+        #[cfg(unix)]
+        // SAFETY: The handler only stores to an atomic, which is async-signal-safe.
+        unsafe {
+            libc::signal(libc::SIGINT, on_sigint as *const () as libc::sighandler_t);
+        }
+        #[cfg(windows)]
+        // SAFETY: The handler only stores to an atomic.
+        unsafe {
+            windows_sys::Win32::System::Console::SetConsoleCtrlHandler(Some(on_ctrl), 1);
+        }
+        Self(())
+    }
+}
+
+impl Drop for Catch {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        // SAFETY: Restoring the default disposition.
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+        }
+        #[cfg(windows)]
+        // SAFETY: Removing the handler added in `Catch::new`.
+        unsafe {
+            windows_sys::Win32::System::Console::SetConsoleCtrlHandler(Some(on_ctrl), 0);
+        }
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_sigint(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn on_ctrl(event: u32) -> windows_sys::Win32::Foundation::BOOL {
+    use windows_sys::Win32::System::Console::CTRL_BREAK_EVENT;
+    use windows_sys::Win32::System::Console::CTRL_C_EVENT;
+
+    if event == CTRL_C_EVENT || event == CTRL_BREAK_EVENT {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        1
+    } else {
+        0
+    }
+}
+
+/// An error for when the user pressed Ctrl-C. It's not printed, because the user knows what
+/// happened; `git upstream` exits with code 130, like a shell does.
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+impl Diagnostic for Interrupted {}
@@ -0,0 +1,118 @@
+//! Pushing from Jujutsu (`jj`) repositories colocated with Git.
+//!
+//! `git push` works in a colocated repository, but leaves `jj`'s view of bookmarks and remotes
+//! stale, so bookmarks are pushed with `jj git push` instead.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use command_error::CommandExt;
+use miette::miette;
+use miette::IntoDiagnostic;
+
+use crate::git;
+use crate::Config;
+
+/// Check if `jj` should push, because the repository is colocated with `jj` and `--use-git`
+/// wasn't given.
+pub fn active(config: &Config) -> miette::Result<bool> {
+    static ACTIVE: OnceLock<bool> = OnceLock::new();
+
+    if config.cli.use_git {
+        return Ok(false);
+    }
+    if let Some(active) = ACTIVE.get() {
+        return Ok(*active);
+    }
+    let active = if !root()?.join(".jj").is_dir() {
+        false
+    } else if git::repo_command("jj")
+        .arg("--version")
+        .output_checked()
+        .is_err()
+    {
+        tracing::warn!("This is a Jujutsu repository, but `jj` isn't installed; pushing with Git");
+        false
+    } else {
+        true
+    };
+    Ok(*ACTIVE.get_or_init(|| active))
+}
+
+/// Get the root of the work tree with `git rev-parse --show-toplevel`.
+fn root() -> miette::Result<PathBuf> {
+    Ok(PathBuf::from(
+        git::command()
+            .args(["rev-parse", "--show-toplevel"])
+            .output_checked_utf8()
+            .into_diagnostic()?
+            .stdout
+            .trim(),
+    ))
+}
+
+/// Get the bookmark to push: the one on the working-copy commit `@`, or on its parent `@-`,
+/// where bookmarks usually are while the working copy is being edited.
+///
+/// Git's `HEAD` is detached in `jj` repositories, so it can't tell us.
+pub fn current_bookmark() -> miette::Result<String> {
+    for revision in ["@", "@-"] {
+        let output = git::repo_command("jj")
+            .args(["log", "--no-graph", "--revisions", revision, "--template"])
+            .arg(r#"local_bookmarks.map(|bookmark| bookmark.name()).join(" ")"#)
+            .output_checked_utf8()
+            .into_diagnostic()?;
+        if let Some(bookmark) = output.stdout.split_whitespace().next() {
+            return Ok(bookmark.to_owned());
+        }
+    }
+    Err(miette!(
+        help = "Create one with `jj bookmark create NAME`, pass `--branch`, or pass `--use-git` \
+                to push with Git",
+        "There's no bookmark on `@` or `@-` to push"
+    ))
+}
+
+/// Push `bookmark` with `jj git push` to the first remote which accepts it, and print
+/// `REMOTE/BOOKMARK`.
+pub fn push(config: &Config, bookmark: &str) -> miette::Result<()> {
+    tracing::info!(
+        "This is a Jujutsu repository, so pushing with `jj git push`; pass `--use-git` to push \
+         with Git"
+    );
+    let candidates = config.candidate_remotes()?;
+    for (i, remote) in candidates.iter().enumerate() {
+        let mut command = git::repo_command("jj");
+        command.args([
+            "git",
+            "push",
+            "--allow-new",
+            "--bookmark",
+            bookmark,
+            "--remote",
+            remote,
+        ]);
+        if config.cli.dry_run {
+            command.arg("--dry-run");
+        }
+        // Keep stdout for `git upstream`'s result.
+        command.stdout(std::io::stderr());
+        git::log_command(&command);
+        match command.status_checked() {
+            Ok(_) => {
+                if config.cli.quiet < 2 {
+                    println!("{remote}/{bookmark}");
+                }
+                return Ok(());
+            }
+            Err(err) if config.cli.fail_fast => return Err(err).into_diagnostic(),
+            Err(err) => match candidates.get(i + 1) {
+                Some(next) => {
+                    tracing::info!("{remote} rejected the push, trying {next} next: {err}")
+                }
+                None => tracing::debug!("{remote} rejected the push: {err}"),
+            },
+        }
+    }
+    Err(miette!("Failed to upstream {bookmark} to any remote"))
+}
@@ -0,0 +1,183 @@
+//! A journal of pushes, for `git upstream log` and `git upstream undo`.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use fs_err as fs;
+use miette::miette;
+use miette::Context;
+use miette::IntoDiagnostic;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::git;
+use crate::Config;
+
+/// How many pushes to remember.
+const MAX_ENTRIES: usize = 1000;
+
+/// The pushes `git-upstream` has made, oldest first.
+///
+/// This is stored with one push per line, as a TOML inline table, so that a corrupt line (from a
+/// crash mid-write, say) only loses one entry.
+#[derive(Debug, Clone, Default)]
+pub struct Journal {
+    pub pushes: Vec<PushRecord>,
+}
+
+/// A successful push, with enough information to revert it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PushRecord {
+    /// The repository's common Git directory.
+    pub repo: PathBuf,
+    pub branch: String,
+    pub remote: String,
+    /// The remote branch's commit before the push, or `None` if the push created it.
+    pub old: Option<String>,
+    /// The commit pushed.
+    pub new: String,
+    /// The branch's `branch.<name>.remote` before the push.
+    pub previous_remote: Option<String>,
+    /// The branch's `branch.<name>.merge` before the push.
+    pub previous_merge: Option<String>,
+    /// Was the push forced?
+    #[serde(default)]
+    pub force: bool,
+    /// When the push happened, in seconds since the Unix epoch.
+    pub time: u64,
+}
+
+/// A line of the journal, which is parsed as `push = LINE`.
+#[derive(Deserialize)]
+struct JournalLine {
+    push: PushRecord,
+}
+
+impl Journal {
+    /// Get the journal's path, or `None` if there's no state directory to keep it in.
+    fn path(config: &Config) -> miette::Result<Option<PathBuf>> {
+        config
+            .dirs
+            .as_ref()
+            .map(|dirs| {
+                dirs.place_state_file("journal")
+                    .into_diagnostic()
+                    .wrap_err("Failed to create state directory")
+            })
+            .transpose()
+    }
+
+    /// Load the journal, skipping corrupt lines with a warning.
+    pub fn load(config: &Config) -> miette::Result<Self> {
+        let Some(path) = Self::path(config)?.filter(|path| path.exists()) else {
+            return Ok(Self::default());
+        };
+        let contents = fs::read_to_string(&path).into_diagnostic()?;
+
+        let mut pushes = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match toml::from_str::<JournalLine>(&format!("push = {line}")) {
+                Ok(line) => pushes.push(line.push),
+                Err(err) => tracing::warn!(
+                    "Skipping corrupt line {} of {}: {}",
+                    i + 1,
+                    path.display(),
+                    err.message()
+                ),
+            }
+        }
+
+        Ok(Self { pushes })
+    }
+
+    pub fn save(&self, config: &Config) -> miette::Result<()> {
+        let Some(path) = Self::path(config)? else {
+            return Ok(());
+        };
+        let mut contents = String::new();
+        for record in &self.pushes {
+            // `toml::Value` formats tables inline.
+            let line = toml::Value::try_from(record).into_diagnostic()?;
+            contents.push_str(&format!("{line}\n"));
+        }
+        fs::write(&path, contents).into_diagnostic()
+    }
+
+    /// Get the index of the most recent push in `repo`.
+    pub fn last_in(&self, repo: &Path) -> Option<usize> {
+        self.pushes.iter().rposition(|record| record.repo == repo)
+    }
+}
+
+/// The state of a branch before pushing it, to be recorded if the push succeeds.
+#[derive(Debug, Clone)]
+pub struct PendingPush {
+    repo: PathBuf,
+    branch: String,
+    remote: String,
+    old: Option<String>,
+    force: bool,
+    previous_remote: Option<String>,
+    previous_merge: Option<String>,
+}
+
+impl PendingPush {
+    /// Record the state of `branch` before pushing it to `remote`.
+    ///
+    /// The remote branch's commit comes from its remote-tracking ref. `--force-with-lease` checks
+    /// the same ref, so this is only wrong for a fast-forward onto a stale remote-tracking ref.
+    pub fn new(branch: &str, remote: &str, force: bool) -> miette::Result<Self> {
+        Ok(Self {
+            repo: repo()?,
+            branch: branch.to_owned(),
+            remote: remote.to_owned(),
+            old: git::rev_parse(&format!("refs/remotes/{remote}/{branch}"))?,
+            force,
+            previous_remote: git::config_get(&format!("branch.{branch}.remote"))?,
+            previous_merge: git::config_get(&format!("branch.{branch}.merge"))?,
+        })
+    }
+
+    /// Correct the remote branch's commit before the push, from what `git push` reported. `None`
+    /// means the push created the branch.
+    pub fn set_old(&mut self, old: Option<String>) {
+        self.old = old;
+    }
+
+    /// Add the push to the journal.
+    pub fn record(self, config: &Config) -> miette::Result<()> {
+        let new = git::rev_parse(&format!("refs/heads/{}", self.branch))?
+            .ok_or_else(|| miette!("{} doesn't exist", self.branch))?;
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        let mut journal = Journal::load(config)?;
+        journal.pushes.push(PushRecord {
+            repo: self.repo,
+            branch: self.branch,
+            remote: self.remote,
+            old: self.old,
+            new,
+            previous_remote: self.previous_remote,
+            previous_merge: self.previous_merge,
+            force: self.force,
+            time,
+        });
+        let excess = journal.pushes.len().saturating_sub(MAX_ENTRIES);
+        journal.pushes.drain(..excess);
+        journal.save(config)
+    }
+}
+
+/// Get the current repository's common Git directory, which identifies it in the journal.
+pub fn repo() -> miette::Result<PathBuf> {
+    let git_dir = git::common_dir()?.ok_or_else(|| miette!("Not in a Git repository"))?;
+    fs::canonicalize(git_dir).into_diagnostic()
+}
@@ -0,0 +1,318 @@
+//! Warning about large files before pushing, which usually belong in Git LFS.
+
+use std::fmt::Display;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::process::Stdio;
+use std::time::Duration;
+use std::time::Instant;
+
+use miette::miette;
+use miette::IntoDiagnostic;
+use serde::Deserialize;
+
+use crate::git;
+use crate::Config;
+
+/// The `warn-blob-size` if none is configured.
+const DEFAULT_WARN: ByteSize = ByteSize(10 << 20);
+
+/// The `max-blob-size` if none is configured.
+const DEFAULT_MAX: ByteSize = ByteSize(100 << 20);
+
+/// How many objects to look at, at most, so pushing a huge history doesn't take forever.
+const MAX_OBJECTS: usize = 200_000;
+
+/// How long to spend listing objects, at most.
+const TIME_LIMIT: Duration = Duration::from_secs(5);
+
+/// A size in bytes, from an integer or a string with a suffix like `10M`, like Git's
+/// `core.bigFileThreshold`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    /// Parse a size like `512`, `100k`, `10M`, or `1g`, where the suffixes are powers of 1024.
+    pub fn parse(size: &str) -> Option<Self> {
+        let size = size.trim();
+        let (number, shift) = match size.char_indices().last()? {
+            (i, 'k' | 'K') => (&size[..i], 10),
+            (i, 'm' | 'M') => (&size[..i], 20),
+            (i, 'g' | 'G') => (&size[..i], 30),
+            _ => (size, 0),
+        };
+        number
+            .trim()
+            .parse::<u64>()
+            .ok()?
+            .checked_mul(1 << shift)
+            .map(Self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bytes(u64),
+            String(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Bytes(bytes) => Ok(Self(bytes)),
+            Raw::String(size) => Self::parse(&size).ok_or_else(|| {
+                serde::de::Error::custom(format!(
+                    "invalid size {size:?}; use a number of bytes, or a number with `k`, `m`, or \
+                     `g`, like \"10m\""
+                ))
+            }),
+        }
+    }
+}
+
+impl Display for ByteSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bytes = self.0 as f64;
+        match self.0 {
+            0..1024 => write!(f, "{} B", self.0),
+            1024..0x10_0000 => write!(f, "{:.1} KiB", bytes / 1024.0),
+            0x10_0000..0x4000_0000 => write!(f, "{:.1} MiB", bytes / f64::from(1 << 20)),
+            _ => write!(f, "{:.1} GiB", bytes / f64::from(1 << 30)),
+        }
+    }
+}
+
+/// A file in the outgoing commits which is larger than `warn-blob-size`.
+#[derive(Debug, Clone)]
+struct LargeFile {
+    path: String,
+    size: ByteSize,
+}
+
+impl Display for LargeFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.path, self.size)
+    }
+}
+
+/// Warn about files larger than `warn-blob-size` which pushing `branch` to `remote` would
+/// upload, and refuse to push files larger than `max-blob-size` unless `--allow-large-files` is
+/// given.
+///
+/// Skipped with `check-large-files = false`.
+pub fn check(config: &Config, branch: &str, remote: &str) -> miette::Result<()> {
+    if !config.file.check_large_files.unwrap_or(true) {
+        return Ok(());
+    }
+    let Some(outgoing) = config.outgoing(branch, remote)? else {
+        return Ok(());
+    };
+    let warn = config.file.warn_blob_size.unwrap_or(DEFAULT_WARN);
+    let max = config.file.max_blob_size.unwrap_or(DEFAULT_MAX);
+
+    let (objects, complete) = list_objects(&outgoing)?;
+    if !complete {
+        tracing::warn!(
+            "Only checked the first {} objects for large files, because there are so many",
+            objects.len()
+        );
+    }
+    let mut large = sizes(&objects)?
+        .into_iter()
+        .filter(|file| file.size > warn)
+        .collect::<Vec<_>>();
+    if large.is_empty() {
+        return Ok(());
+    }
+    large.sort_by_key(|file| std::cmp::Reverse(file.size));
+
+    let listing = large
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let help = "Track large files with Git LFS (`git lfs track PATH`), and move ones which are \
+                already committed with `git lfs migrate import --include=PATH`";
+    if large[0].size > max && !config.cli.allow_large_files {
+        return Err(miette!(
+            help = format!("{help}, or pass `--allow-large-files` to push anyway"),
+            "Refusing to push {branch} to {remote}, because it adds files larger than {max}:\n\
+             {listing}"
+        ));
+    }
+    tracing::warn!("{branch} adds files larger than {warn}:\n{listing}");
+    tracing::info!("{help}");
+    Ok(())
+}
+
+/// List the objects in the outgoing commits, with `git rev-list --objects`, as `OBJECT PATH`
+/// lines. Stops after [`MAX_OBJECTS`] objects or [`TIME_LIMIT`].
+///
+/// Returns whether every object was listed.
+fn list_objects(outgoing: &[String]) -> miette::Result<(Vec<String>, bool)> {
+    let mut child = git::command()
+        .args(["rev-list", "--objects"])
+        .args(outgoing)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .into_diagnostic()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+
+    let start = Instant::now();
+    let mut objects = Vec::new();
+    let mut complete = true;
+    for line in BufReader::new(stdout).lines() {
+        let line = line.into_diagnostic()?;
+        objects.push(line);
+        if objects.len() >= MAX_OBJECTS || start.elapsed() > TIME_LIMIT {
+            complete = false;
+            let _ = child.kill();
+            break;
+        }
+    }
+    let status = child.wait().into_diagnostic()?;
+    if complete && !status.success() {
+        return Err(miette!("`git rev-list --objects` failed with {status}"));
+    }
+    Ok((objects, complete))
+}
+
+/// Get the sizes of the blobs among `objects` with `git cat-file --batch-check`.
+fn sizes(objects: &[String]) -> miette::Result<Vec<LargeFile>> {
+    let mut child = git::command()
+        .args([
+            "cat-file",
+            "--batch-check=%(objecttype) %(objectsize) %(rest)",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .into_diagnostic()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = objects.join("\n");
+    // Write in the background, so `git cat-file` can't block on a full stdout pipe.
+    let writer = std::thread::spawn(move || {
+        let _ = stdin.write_all(input.as_bytes());
+        let _ = stdin.write_all(b"\n");
+    });
+    let output = child.wait_with_output().into_diagnostic()?;
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(miette!(
+            "`git cat-file --batch-check` failed with {}",
+            output.status
+        ));
+    }
+
+    Ok(parse_batch_check(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Get the blobs from `git cat-file --batch-check='%(objecttype) %(objectsize) %(rest)'` output.
+fn parse_batch_check(output: &str) -> Vec<LargeFile> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ' ');
+            if parts.next()? != "blob" {
+                return None;
+            }
+            let size = parts.next()?.parse().ok()?;
+            let path = parts.next().unwrap_or_default();
+            Some(LargeFile {
+                path: path.to_owned(),
+                size: ByteSize(size),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes() {
+        let cases = [
+            ("0", Some(0)),
+            ("512", Some(512)),
+            ("100k", Some(100 << 10)),
+            ("10M", Some(10 << 20)),
+            ("1g", Some(1 << 30)),
+            ("2G", Some(2 << 30)),
+            (" 10 m ", Some(10 << 20)),
+            ("", None),
+            ("m", None),
+            ("10 MB", None),
+            ("1.5m", None),
+            ("-1", None),
+            ("10t", None),
+            ("18446744073709551615", Some(u64::MAX)),
+            ("18446744073709551615k", None),
+        ];
+        for (size, expected) in cases {
+            assert_eq!(ByteSize::parse(size), expected.map(ByteSize), "{size:?}");
+        }
+    }
+
+    #[test]
+    fn deserializes() {
+        #[derive(Debug, Deserialize)]
+        struct File {
+            size: ByteSize,
+        }
+        let size = |toml: &str| toml::from_str::<File>(toml).map(|file| file.size);
+
+        assert_eq!(size("size = 1024").unwrap(), ByteSize(1024));
+        assert_eq!(size("size = \"50m\"").unwrap(), ByteSize(50 << 20));
+        let error = size("size = \"big\"").unwrap_err().to_string();
+        assert!(error.contains("invalid size \"big\""), "{error}");
+        assert!(size("size = -1").is_err());
+    }
+
+    #[test]
+    fn displays() {
+        let cases = [
+            (0, "0 B"),
+            (1023, "1023 B"),
+            (1024, "1.0 KiB"),
+            (1536, "1.5 KiB"),
+            (10 << 20, "10.0 MiB"),
+            ((1 << 30) - 1, "1024.0 MiB"),
+            (3 << 30, "3.0 GiB"),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(ByteSize(bytes).to_string(), expected, "{bytes}");
+        }
+    }
+
+    #[test]
+    fn parses_batch_check() {
+        let output = "commit 240 \n\
+                      tree 37 \n\
+                      blob 12 README.md\n\
+                      blob 209715200 assets/video with spaces.mp4\n\
+                      blob 5 \n\
+                      0123456789abcdef missing\n";
+        let files: Vec<_> = parse_batch_check(output)
+            .into_iter()
+            .map(|file| (file.path, file.size.0))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                ("README.md".to_owned(), 12),
+                ("assets/video with spaces.mp4".to_owned(), 200 << 20),
+                // `git rev-list --objects` lists some objects without a path.
+                (String::new(), 5),
+            ]
+        );
+    }
+}
@@ -0,0 +1,2551 @@
+//! A shortcut for `git push --set-upstream REMOTE BRANCH`, which tries each remote in order
+//! until one accepts the push.
+//!
+//! The binary is a thin wrapper around [`run`]. Other programs can push a branch without
+//! printing anything with [`Config::plan`] and [`push_upstream`], which return structured
+//! results:
+//!
+//! ```no_run
+//! use clap::Parser;
+//! use git_upstream::Cli;
+//! use git_upstream::Config;
+//!
+//! let config = Config::with_cli(Cli::parse_from(["git-upstream", "--fail-fast"]))?;
+//! let plan = config.plan(&config.branch()?)?;
+//! let report = git_upstream::push_upstream(&config, &plan)?;
+//! println!("{}", report.outcome.success);
+//! # Ok::<_, miette::Report>(())
+//! ```
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::Instant;
+
+use clap::Parser;
+use command_error::CommandExt;
+use fs_err as fs;
+use miette::miette;
+use miette::Context;
+use miette::IntoDiagnostic;
+use miette::LabeledSpan;
+use miette::MietteDiagnostic;
+use miette::NamedSource;
+use owo_colors::OwoColorize;
+use owo_colors::Stream;
+use owo_colors::Style;
+use serde::Deserialize;
+
+mod adopt;
+mod batch;
+mod browser;
+mod capabilities;
+mod ci;
+mod clipboard;
+mod color;
+mod completions;
+mod config;
+mod default_branch;
+mod delete;
+mod dirs;
+mod dirty;
+mod doctor;
+mod exit_code;
+mod failure;
+mod fetch;
+mod fork;
+mod git;
+mod git_dir;
+mod git_runner;
+mod git_version;
+mod glob;
+mod in_progress;
+mod install_tracing;
+mod interrupt;
+mod jj;
+mod journal;
+mod json;
+mod large_files;
+mod log;
+mod notify;
+mod porcelain;
+mod pr;
+mod preflight;
+mod prompt;
+mod prune;
+mod pull;
+mod pull_request;
+mod push_args;
+mod remote_url;
+mod remotes;
+mod rename;
+mod rename_remote;
+mod report;
+mod secrets;
+mod shell;
+mod snapshot;
+mod stack;
+mod status;
+mod summary;
+mod switch;
+mod sync;
+mod tee;
+mod ticket;
+mod timeout;
+mod undo;
+mod unset;
+mod wip;
+
+use adopt::AdoptArgs;
+use capabilities::RemoteSettingsMap;
+use capabilities::Signed;
+use ci::CiFailed;
+use ci::WatchCi;
+use color::ColorChoice;
+use completions::CompleteArgs;
+use completions::CompletionsArgs;
+use config::ConfigArgs;
+use default_branch::DefaultBranch;
+use dirs::UserDirs;
+use dirty::WarnDirty;
+use doctor::CheckStatus;
+use doctor::DoctorArgs;
+pub use exit_code::Exit;
+use exit_code::NoRemotes;
+pub use failure::FailureKind;
+use fetch::FetchArgs;
+pub use git_runner::FakeGit;
+pub use git_runner::FakeOutcome;
+pub use git_runner::GitRunner;
+pub use git_runner::PushRequest;
+pub use git_runner::PushResult;
+pub use git_runner::SubprocessGit;
+use git_version::GitVersion;
+use glob::glob_match;
+use install_tracing::install_tracing;
+pub use install_tracing::LogFile;
+use install_tracing::LogFormat;
+use interrupt::Interrupted;
+use large_files::ByteSize;
+use log::LogArgs;
+use notify::NotifyWhen;
+use porcelain::parse_porcelain;
+use porcelain::RefUpdate;
+use porcelain::RefUpdateFlag;
+use pr::PrTool;
+use prompt::confirm;
+use prompt::confirm_typed;
+use prune::PruneArgs;
+use pull::PullArgs;
+use pull_request::OpenTarget;
+use remote_url::Forge;
+use remote_url::HostTemplates;
+use remote_url::RemoteUrl;
+pub use remotes::Remote;
+pub use remotes::RemoteSource;
+use remotes::RemotesArgs;
+use rename_remote::RenameRemoteArgs;
+pub use report::Attempt;
+pub use report::AttemptResult;
+pub use report::Candidate;
+use report::JsonFormat;
+pub use report::Outcome;
+use report::PushFailed;
+pub use report::PushPlan;
+pub use report::PushReport;
+use secrets::SecretPattern;
+use snapshot::RemoteHeads;
+use snapshot::RepoSnapshot;
+use status::StatusArgs;
+use summary::SummaryMode;
+use switch::SwitchArgs;
+use sync::SyncStrategy;
+use ticket::Ticket;
+use ticket::TicketPattern;
+use undo::UndoArgs;
+use unset::UnsetArgs;
+use wip::WipPattern;
+
+/// Configuration, both from the command-line and user configuration files.
+#[derive(Debug)]
+pub struct Config {
+    /// User directories, or `None` if they couldn't be found, like when `$HOME` isn't set.
+    /// Then only the repository's configuration file is read, and state like the push journal
+    /// isn't saved.
+    pub dirs: Option<UserDirs>,
+    /// User configuration file, with repository-local overrides merged in.
+    pub file: ConfigFile,
+    /// Command-line options.
+    pub cli: Cli,
+    /// The version of the `git` on `$PATH`.
+    pub git_version: GitVersion,
+    /// Runs `git` for the push logic. See [`Config::with_git`].
+    git: Arc<dyn GitRunner>,
+    /// The repository's remotes, refs, and configuration, read when first needed. See
+    /// [`Config::snapshot`].
+    snapshot: Mutex<Option<Arc<RepoSnapshot>>>,
+    /// What remotes' `HEAD`s point to, shared between snapshots.
+    remote_heads: RemoteHeads,
+}
+
+impl Config {
+    /// Load the configuration files and parse the command-line arguments.
+    pub fn new() -> miette::Result<Self> {
+        Self::with_cli(Cli::parse())
+    }
+
+    /// Load the configuration files, with already-parsed command-line options like
+    /// `Cli::parse_from(["git-upstream", "--fail-fast"])`.
+    pub fn with_cli(cli: Cli) -> miette::Result<Self> {
+        // Before anything runs `git`.
+        git::set_directory(cli.directory.as_deref()).map_err(exit_code::usage)?;
+        let git_version = GitVersion::detect()?;
+        tracing::debug!("Using Git {git_version}");
+        let dirs = UserDirs::find();
+        if dirs.is_none() {
+            tracing::debug!(
+                "Couldn't find the home directory, so the user configuration file isn't read and \
+                 pushes aren't recorded for `git upstream undo`"
+            );
+        }
+        if let Some(path) = &cli.config {
+            if !path.exists() {
+                return Err(exit_code::usage(miette!(
+                    "Configuration file {} doesn't exist",
+                    path.display()
+                )));
+            }
+        }
+        let mut file = ConfigFile::default();
+        for path in ConfigFile::paths(cli.config.as_deref(), dirs.as_ref())? {
+            file = file.merge(ConfigFile::load(&path)?);
+        }
+        Ok(Self {
+            dirs,
+            file,
+            cli,
+            git_version,
+            git: Arc::new(SubprocessGit),
+            snapshot: Mutex::default(),
+            remote_heads: RemoteHeads::default(),
+        })
+    }
+
+    /// Use `git` to read the repository and push, instead of running `git`, like a [`FakeGit`]
+    /// in tests.
+    pub fn with_git(mut self, git: Arc<dyn GitRunner>) -> Self {
+        self.git = git;
+        self.refresh_snapshot();
+        self
+    }
+
+    /// Get what runs `git` for the push logic.
+    pub fn git(&self) -> &dyn GitRunner {
+        &*self.git
+    }
+
+    /// Interpret a `REMOTE/BRANCH` positional argument, if the part before the first `/` is the
+    /// name of a remote.
+    ///
+    /// Branch names often contain slashes, so the remote name has to match for the argument to
+    /// be split; otherwise, it's treated as a remote name. Splitting it conflicts with
+    /// `--branch`, which is a usage error.
+    pub fn split_remote_branch(&mut self) -> miette::Result<()> {
+        let Some(arg) = &self.cli.remote else {
+            return Ok(());
+        };
+        let Some((remote, branch)) = arg.split_once('/') else {
+            return Ok(());
+        };
+
+        let remotes = self.list_remotes()?;
+        let exists = |name: &str| remotes.iter().any(|remote| remote.name == name);
+        if exists(arg) || !exists(remote) {
+            tracing::debug!("Interpreting {arg:?} as a remote name");
+            return Ok(());
+        }
+
+        if self.cli.branch.is_some() {
+            return Err(exit_code::usage(miette!(
+                help = format!("Pass `{remote}` and `--branch` separately"),
+                "`{arg}` names a remote and a branch, which conflicts with `--branch`"
+            )));
+        }
+
+        tracing::debug!("Interpreting {arg:?} as remote {remote:?} and branch {branch:?}");
+        self.cli.branch = Some(branch.to_owned());
+        self.cli.remote = Some(remote.to_owned());
+        Ok(())
+    }
+
+    /// Get the remote names to push to, if they exist, highest preferences first.
+    pub fn remote_preferences(&self) -> Vec<String> {
+        self.remote_preference_sources()
+            .into_iter()
+            .map(|(remote, _)| remote)
+            .collect()
+    }
+
+    /// Like [`Config::remote_preferences`], but also get where each preference came from.
+    pub fn remote_preference_sources(&self) -> Vec<(String, RemoteSource)> {
+        let mut ret = Vec::new();
+
+        if let Some(remote) = &self.cli.remote {
+            ret.push((remote.clone(), RemoteSource::CommandLine));
+        }
+
+        if self.file.remotes.is_empty() {
+            tracing::trace!("No remotes in the configuration file; preferring origin");
+        }
+
+        if !self.file.remotes.is_empty() {
+            ret.extend(
+                self.file
+                    .remotes
+                    .iter()
+                    .map(|remote| (remote.clone(), RemoteSource::ConfigFile)),
+            );
+        } else {
+            ret.push(("origin".into(), RemoteSource::Default));
+        }
+
+        ret
+    }
+
+    /// Get the remotes to attempt to push to, in order.
+    ///
+    /// This is the [`Config::remote_preferences`] which exist, followed by the rest of the
+    /// remotes.
+    pub fn candidate_remotes(&self) -> miette::Result<Vec<String>> {
+        Ok(self
+            .ranked_candidates()?
+            .into_iter()
+            .map(|candidate| candidate.remote.name)
+            .collect())
+    }
+
+    /// Like [`Config::candidate_remotes`], but also get why each remote was ranked where it was.
+    pub fn ranked_candidates(&self) -> miette::Result<Vec<Candidate>> {
+        let mut remotes = self.list_remotes()?;
+        let mut ret = Vec::new();
+
+        for (remote, source) in self.remote_preference_sources() {
+            if let Some(index) = remotes.iter().position(|other| other.name == remote) {
+                ret.push(Candidate {
+                    remote: remotes.remove(index),
+                    source,
+                });
+            } else if ret.iter().any(|candidate| candidate.remote.name == remote) {
+                tracing::trace!("{remote} ({source}) is already listed");
+            } else if source == RemoteSource::CommandLine
+                || (source == RemoteSource::ConfigFile && self.cli.strict_remotes)
+            {
+                // A typo shouldn't push somewhere else entirely.
+                return Err(unknown_remote(&remote, &self.list_remotes()?));
+            } else {
+                tracing::debug!("Skipping {remote} ({source}) because there's no such remote");
+            }
+        }
+
+        // Try rest of remotes (not listed on CLI or in config file or `origin`), in the order
+        // Git lists them, which is alphabetical.
+        ret.extend(remotes.into_iter().map(|remote| Candidate {
+            remote,
+            source: RemoteSource::Fallback,
+        }));
+
+        tracing::debug!(
+            "Remotes to try, in order: {}",
+            ret.iter()
+                .map(|candidate| format!("{} ({})", candidate.remote, candidate.source))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        Ok(ret)
+    }
+
+    /// Get the protected branch patterns.
+    pub fn protected_branches(&self) -> Vec<String> {
+        match &self.file.protected_branches {
+            Some(patterns) => patterns.clone(),
+            None => vec!["main".into(), "master".into()],
+        }
+    }
+
+    /// If `branch` is protected, get the first pattern it matches.
+    pub fn protected_pattern(&self, branch: &str) -> Option<String> {
+        self.protected_branches()
+            .into_iter()
+            .find(|pattern| glob_match(pattern, branch))
+    }
+
+    /// Get the repository's remotes, in the order Git lists them.
+    pub fn list_remotes(&self) -> miette::Result<Vec<Remote>> {
+        let remotes = self.snapshot()?.remotes().to_vec();
+        if remotes.is_empty() {
+            Err(NoRemotes.into())
+        } else {
+            Ok(remotes)
+        }
+    }
+
+    /// Check if there's a remote named `name`.
+    pub fn has_remote(&self, name: &str) -> miette::Result<bool> {
+        Ok(self
+            .list_remotes()?
+            .iter()
+            .any(|remote| remote.name == name))
+    }
+
+    /// Get the remote named `name`.
+    pub fn remote(&self, name: &str) -> miette::Result<Remote> {
+        let remotes = self.list_remotes()?;
+        match remotes.iter().find(|remote| remote.name == name) {
+            Some(remote) => Ok(remote.clone()),
+            None => Err(unknown_remote(name, &remotes)),
+        }
+    }
+
+    /// Get the snapshot of the repository's remotes, refs, and configuration, reading it if it
+    /// hasn't been read yet (or since [`Config::refresh_snapshot`]).
+    pub fn snapshot(&self) -> miette::Result<Arc<RepoSnapshot>> {
+        let mut snapshot = self.snapshot.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(snapshot) = &*snapshot {
+            return Ok(Arc::clone(snapshot));
+        }
+        let read = Arc::new(
+            self.git
+                .snapshot()?
+                .with_remote_heads(Arc::clone(&self.remote_heads)),
+        );
+        *snapshot = Some(Arc::clone(&read));
+        Ok(read)
+    }
+
+    /// Forget the snapshot after changing the repository, so it's read again when it's next
+    /// needed.
+    pub fn refresh_snapshot(&self) {
+        *self.snapshot.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Get the URL `git push` uses for `remote`, from the snapshot.
+    ///
+    /// Returns `None` if it doesn't have a host, like a local path.
+    pub fn remote_url(&self, remote: &str) -> miette::Result<Option<RemoteUrl>> {
+        Ok(self
+            .snapshot()?
+            .remote(remote)
+            .and_then(|remote| remote.resolved_push_url.clone()))
+    }
+
+    /// Detect the default branch of `remote`, with `DefaultBranch::of`.
+    ///
+    /// Asking the remote is bounded by `--timeout`, or [`DEFAULT_BRANCH_TIMEOUT`].
+    pub fn default_branch(&self, remote: &str) -> miette::Result<Option<DefaultBranch>> {
+        let timeout = self.timeout().unwrap_or(DEFAULT_BRANCH_TIMEOUT);
+        Ok(DefaultBranch::of(&*self.snapshot()?, remote, timeout))
+    }
+
+    /// Check that `remote` exists.
+    pub fn require_remote(&self, remote: &str) -> miette::Result<()> {
+        self.remote(remote).map(|_| ())
+    }
+
+    /// Get the branch to push, refusing branches which match a `never-push` pattern unless
+    /// `--allow-never-push` is given.
+    pub fn branch(&self) -> miette::Result<String> {
+        let branch = match &self.cli.branch {
+            Some(branch) => branch.to_owned(),
+            None if jj::active(self)? => jj::current_bookmark()?,
+            None => self.git.current_branch()?,
+        };
+        if let Some(pattern) = self.never_push_pattern(&branch) {
+            return Err(miette!(
+                help = "Pass `--allow-never-push` to push it anyway",
+                "Refusing to push {branch}, which matches the `never-push` pattern `{pattern}`"
+            ));
+        }
+        Ok(branch)
+    }
+
+    /// If `branch` matches a `never-push` pattern, get the first one it matches.
+    ///
+    /// Always `None` with `--allow-never-push`.
+    pub fn never_push_pattern(&self, branch: &str) -> Option<&str> {
+        if self.cli.allow_never_push {
+            return None;
+        }
+        self.file
+            .never_push
+            .iter()
+            .map(String::as_str)
+            .find(|pattern| glob_match(pattern, branch))
+    }
+
+    /// Get the strategy `--sync` uses to integrate remote changes.
+    pub fn sync_strategy(&self) -> SyncStrategy {
+        self.file.sync_strategy.unwrap_or_default()
+    }
+
+    /// Check if `branch` already tracks `remote` and matches its remote-tracking ref, in which
+    /// case pushing would be a no-op.
+    ///
+    /// This only consults local refs, so it may be fooled by a stale remote-tracking ref.
+    pub fn is_up_to_date(&self, branch: &str, remote: &str) -> miette::Result<bool> {
+        if self.cli.no_skip_up_to_date
+            || self.forcing()
+            || !self.cli.git_push_args.is_empty()
+            || !self.notes_refs().is_empty()
+        {
+            // Extra arguments may push other refs, force-push, or set push options, so we can't
+            // tell if the push would be a no-op.
+            return Ok(false);
+        }
+
+        let snapshot = self.snapshot()?;
+        let upstream_remote = snapshot.config_get(&format!("branch.{branch}.remote"));
+        let upstream_merge = snapshot.config_get(&format!("branch.{branch}.merge"));
+        if upstream_remote != Some(remote)
+            || upstream_merge != Some(format!("refs/heads/{branch}").as_str())
+        {
+            return Ok(false);
+        }
+
+        let local = snapshot.rev(&format!("refs/heads/{branch}"));
+        let tracking = snapshot.rev(&format!("refs/remotes/{remote}/{branch}"));
+        Ok(local.is_some() && local == tracking)
+    }
+
+    /// Refuse to push `branch` to `remote` if it's the remote's default branch, unless that's
+    /// allowed.
+    pub fn check_default_branch(&self, branch: &str, remote: &str) -> miette::Result<()> {
+        if self.cli.allow_default_branch || self.file.allow_default_branch.unwrap_or(false) {
+            return Ok(());
+        }
+
+        if let Some(default_branch) = self.default_branch(remote)? {
+            if default_branch.name == branch {
+                return Err(miette!(
+                    help = format!(
+                        "Pass `--allow-default-branch`, or set `allow-default-branch = true` in \
+                         `.git/git-upstream.toml` if pushing to {branch} is normal in this \
+                         repository"
+                    ),
+                    "Refusing to push {branch} to {remote} because it's the remote's default \
+                     branch (detected from {})",
+                    default_branch.source
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the revisions to pass to `git log` for the commits pushing `branch` to `remote` would
+    /// publish: the ones after `REMOTE/BRANCH`, or if that doesn't exist, the ones which aren't
+    /// on any remote.
+    ///
+    /// Returns `None` if `REMOTE/BRANCH` is already up to date, without running `git`.
+    pub fn outgoing(&self, branch: &str, remote: &str) -> miette::Result<Option<Vec<String>>> {
+        let local = format!("refs/heads/{branch}");
+        let tracking = format!("refs/remotes/{remote}/{branch}");
+        let snapshot = self.snapshot()?;
+        let base = match snapshot.rev(&tracking) {
+            Some(rev) if Some(rev) == snapshot.rev(&local) => return Ok(None),
+            Some(_) => tracking,
+            None => "--remotes".to_owned(),
+        };
+        Ok(Some(vec![local, "--not".to_owned(), base]))
+    }
+
+    /// Build the `git push` command to push `branch` to `remote`.
+    pub fn push_command(&self, branch: &str, remote: &str) -> miette::Result<Command> {
+        let mut command = git::command();
+        // `--porcelain` output is parsed to summarize the push afterwards.
+        command.args(["push", "--porcelain"]);
+
+        let snapshot = self.snapshot()?;
+        let upstream = snapshot.upstream(branch);
+        let action = UpstreamAction::new(
+            upstream.as_ref().map(|upstream| upstream.remote.as_str()),
+            remote,
+            self.cli.retarget_upstream,
+            snapshot
+                .config_get_bool("push.autoSetupRemote")
+                .unwrap_or(false),
+        );
+        let mut refspec = true;
+        match (action, &upstream) {
+            (UpstreamAction::Keep, Some(upstream)) => tracing::warn!(
+                "{branch} tracks {upstream}; pushing to {remote} without changing its upstream \
+                 (pass `--retarget-upstream` to change it)"
+            ),
+            (UpstreamAction::Retarget, Some(upstream)) => {
+                tracing::warn!(
+                    "Changing the upstream of {branch} from {upstream} to {remote}/{branch}"
+                );
+                command.arg("--set-upstream");
+            }
+            (UpstreamAction::Tracking, _) => tracing::debug!(
+                "`push.autoSetupRemote` is set and {branch} already tracks {remote}; not passing \
+                 `--set-upstream`"
+            ),
+            (UpstreamAction::Auto, _) if self.can_auto_setup_remote(branch)? => {
+                // Git only sets the upstream automatically for pushes without a refspec.
+                tracing::debug!(
+                    "`push.autoSetupRemote` is set and {branch} has no upstream; letting Git set \
+                     it"
+                );
+                refspec = false;
+            }
+            (UpstreamAction::Keep, None) => {}
+            (UpstreamAction::Set | UpstreamAction::Retarget | UpstreamAction::Auto, _) => {
+                command.arg("--set-upstream");
+            }
+        }
+
+        if self.forcing() {
+            command.arg(self.force_flag(branch)?);
+            if self.force_if_includes()? {
+                command.arg("--force-if-includes");
+            }
+        }
+        if self.cli.quiet > 0 {
+            command.arg("--quiet");
+        }
+        if self.cli.dry_run {
+            command.arg("--dry-run");
+        }
+        if self.cli.no_verify {
+            command.arg("--no-verify");
+        }
+        if let Some(signed) = capabilities::signed(self, remote) {
+            command.arg(signed.flag());
+        }
+        if capabilities::atomic(self, remote) {
+            command.arg("--atomic");
+        }
+        for option in self.merge_request_options(branch, remote)? {
+            command.args(["--push-option", &option]);
+        }
+        command.arg(remote);
+        if refspec {
+            command.arg(branch);
+        }
+        for notes_ref in self.notes_refs() {
+            if self.git.rev_parse(&notes_ref)?.is_none() {
+                tracing::warn!("{notes_ref} doesn't exist; not pushing it");
+                continue;
+            }
+            // Notes histories diverge constantly, so `--force` forces them too.
+            let force = if self.forcing() { "+" } else { "" };
+            command.arg(format!("{force}{notes_ref}:{notes_ref}"));
+        }
+        command.args(&self.cli.git_push_args);
+
+        Ok(command)
+    }
+
+    /// Check if `remote` is on GitLab, from its host or `gitlab-hosts` in the configuration
+    /// file.
+    pub fn is_gitlab(&self, remote: &str) -> miette::Result<bool> {
+        Ok(self.remote_url(remote)?.is_some_and(|url| {
+            url.web(&self.file.host_templates).forge == Some(Forge::GitLab)
+                || self
+                    .file
+                    .gitlab_hosts
+                    .iter()
+                    .any(|host| host.eq_ignore_ascii_case(&url.host))
+        }))
+    }
+
+    /// Whether any of the `--mr` flags were given.
+    fn merge_request_requested(&self) -> bool {
+        self.cli.mr
+            || self.cli.mr_target.is_some()
+            || self.cli.mr_title.is_some()
+            || self.cli.mr_remove_source_branch
+    }
+
+    /// Get the push options the `--mr` flags ask for, like `merge_request.create`, if `remote`
+    /// is on GitLab.
+    fn merge_request_options(&self, branch: &str, remote: &str) -> miette::Result<Vec<String>> {
+        if !self.merge_request_requested() {
+            return Ok(Vec::new());
+        }
+        if !self.is_gitlab(remote)? {
+            tracing::warn!(
+                "{remote} isn't on GitLab, so not creating a merge request (add its host to \
+                 `gitlab-hosts` in the configuration file if it's a self-hosted GitLab)"
+            );
+            return Ok(Vec::new());
+        }
+
+        let mut options = vec!["merge_request.create".to_owned()];
+        if let Some(target) = &self.cli.mr_target {
+            options.push(format!("merge_request.target={target}"));
+        }
+        if let Some(title) = &self.cli.mr_title {
+            options.push(format!("merge_request.title={title}"));
+        }
+        if let Some(ticket) = Ticket::of(self, branch) {
+            // Push options can't contain newlines.
+            let description = ticket.reference.replace('\n', " ");
+            options.push(format!("merge_request.description={description}"));
+        }
+        if self.cli.mr_remove_source_branch {
+            options.push("merge_request.remove_source_branch".to_owned());
+        }
+        if self.cli.draft {
+            options.push("merge_request.draft".to_owned());
+        }
+        Ok(options)
+    }
+
+    /// Whether to force-push, with `--force`, `--force-unchecked`, `--force-if-includes`, or
+    /// `--expect`.
+    pub fn forcing(&self) -> bool {
+        self.cli.force
+            || self.cli.force_unchecked
+            || self.cli.force_if_includes
+            || self.cli.expect.is_some()
+    }
+
+    /// Whether to pass `--force-if-includes` alongside `--force-with-lease`, because
+    /// `--force-if-includes` was given or `push.useForceIfIncludes` is set.
+    fn force_if_includes(&self) -> miette::Result<bool> {
+        if self.cli.force_unchecked {
+            return Ok(false);
+        }
+        if self.cli.force_if_includes {
+            self.git_version
+                .require(GitVersion::FORCE_IF_INCLUDES, "--force-if-includes")
+                .map_err(exit_code::usage)?;
+            return Ok(true);
+        }
+        Ok(self.git_version >= GitVersion::FORCE_IF_INCLUDES
+            && self
+                .snapshot()?
+                .config_get_bool("push.useForceIfIncludes")
+                .unwrap_or(false))
+    }
+
+    /// With `--fetch-first` or `fetch-first = true`, before force-pushing `branch` to `remote`,
+    /// fetch it so that `--force-with-lease` checks against the remote branch as it is now, not
+    /// as it was when it was last fetched.
+    ///
+    /// Returns whether the remote branch had moved, after warning about the new commits.
+    fn fetch_first(&self, branch: &str, remote: &str) -> miette::Result<bool> {
+        if !self.forcing() || !(self.cli.fetch_first || self.file.fetch_first.unwrap_or(false)) {
+            return Ok(false);
+        }
+
+        let tracking = format!("refs/remotes/{remote}/{branch}");
+        let before = self.snapshot()?.rev(&tracking).map(ToOwned::to_owned);
+        let mut command = git::command();
+        command.args(["fetch", "--quiet", remote, branch]);
+        git::log_command(&command);
+        let output = command.output_checked_utf8();
+        self.refresh_snapshot();
+        if let Err(err) = output {
+            if !self.remote_has_branch(remote, branch) {
+                tracing::debug!("{remote}/{branch} doesn't exist yet, so there's nothing to lease");
+            } else {
+                tracing::warn!(
+                    "Failed to fetch {branch} from {remote}, so `--force-with-lease` may be \
+                     checking a stale remote-tracking ref: {err}"
+                );
+            }
+            return Ok(false);
+        }
+
+        let after = self.snapshot()?.rev(&tracking).map(ToOwned::to_owned);
+        let Some(after) = after.filter(|after| before.as_ref() != Some(after)) else {
+            return Ok(false);
+        };
+        let mut log = git::command();
+        log.args(["log", "--oneline", "--no-decorate", &after, "--not"]);
+        log.arg(before.unwrap_or_else(|| format!("refs/heads/{branch}")));
+        let commits = log.output_checked_utf8().into_diagnostic()?.stdout;
+        tracing::warn!(
+            "{remote}/{branch} has moved since it was last fetched; force-pushing would discard \
+             these new commits:\n{}",
+            commits.trim_end()
+        );
+        Ok(true)
+    }
+
+    /// Check if `remote` has `branch`, with `git ls-remote --exit-code`, which exits with code 2
+    /// when nothing matches. Returns `true` if that can't be told, like when the remote can't be
+    /// reached.
+    fn remote_has_branch(&self, remote: &str, branch: &str) -> bool {
+        let mut command = git::command();
+        command
+            .args([
+                "ls-remote",
+                "--exit-code",
+                remote,
+                &format!("refs/heads/{branch}"),
+            ])
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
+        git::log_command(&command);
+        command
+            .status()
+            .map_or(true, |status| status.code() != Some(2))
+    }
+
+    /// Before force-pushing `branch` to `remote` (see [`Config::forcing`]), ask the user to
+    /// confirm, unless `--yes` or `confirm-force = false` skip the prompt. If the remote branch
+    /// `moved` since it was last fetched, ask even with `confirm-force = false`.
+    ///
+    /// Protected branches need their name typed out. Without a terminal to ask on, refuses.
+    fn confirm_force(&self, branch: &str, remote: &str, moved: bool) -> miette::Result<()> {
+        if !self.forcing()
+            || self.cli.dry_run
+            || self.cli.yes
+            || !(moved || self.file.confirm_force.unwrap_or(true))
+        {
+            return Ok(());
+        }
+        if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+            let help = if moved {
+                "Pass `--yes` to overwrite the new commits"
+            } else {
+                "Pass `--yes`, or set `confirm-force = false` in the configuration file"
+            };
+            return Err(exit_code::usage(miette!(
+                help = help,
+                "Refusing to force-push {branch} to {remote} without confirmation, because \
+                 there's no terminal to ask on"
+            )));
+        }
+
+        let tracking = format!("refs/remotes/{remote}/{branch}");
+        if self.snapshot()?.rev(&tracking).is_some() {
+            let discarded = git::command()
+                .args(["rev-list", "--count", &tracking, "--not"])
+                .arg(format!("refs/heads/{branch}"))
+                .output_checked_utf8()
+                .into_diagnostic()?
+                .stdout;
+            match discarded.trim() {
+                "0" => {}
+                "1" => tracing::warn!("1 commit on {remote}/{branch} isn't on {branch}"),
+                count => tracing::warn!("{count} commits on {remote}/{branch} aren't on {branch}"),
+            }
+        }
+
+        let question = format!("Force-push {branch} to {remote}, overwriting remote history?");
+        let confirmed = match self.protected_pattern(branch) {
+            Some(pattern) => confirm_typed(
+                &format!("{branch} is protected (it matches `{pattern}`). {question}"),
+                branch,
+                "--yes",
+            )?,
+            None => confirm(&question, "--yes")?,
+        };
+        if confirmed {
+            Ok(())
+        } else {
+            Err(miette!("Not force-pushing {branch}"))
+        }
+    }
+
+    /// Get the flag to force-push `branch` with: `--force` for `--force-unchecked`,
+    /// `--force-with-lease=BRANCH:COMMIT` for `--expect`, or `--force-with-lease`. With Git
+    /// versions which don't support `--force-with-lease`, `--force` if the user agrees.
+    fn force_flag(&self, branch: &str) -> miette::Result<String> {
+        if self.cli.force_unchecked {
+            return Ok("--force".to_owned());
+        }
+        if let Some(expect) = &self.cli.expect {
+            self.git_version
+                .require(GitVersion::FORCE_WITH_LEASE, "--force-with-lease")?;
+            let commit = self
+                .git
+                .rev_parse(&format!("{expect}^{{commit}}"))?
+                .ok_or_else(|| {
+                    exit_code::usage(miette!(
+                        help = "`--expect` takes the commit you expect the remote branch to point \
+                            to, like `origin/main` or a commit hash",
+                        "`--expect {expect}` doesn't name a commit"
+                    ))
+                })?;
+            return Ok(format!("--force-with-lease={branch}:{commit}"));
+        }
+        if self.git_version >= GitVersion::FORCE_WITH_LEASE {
+            return Ok("--force-with-lease".to_owned());
+        }
+        let version = self.git_version;
+        tracing::warn!(
+            "Git {version} doesn't support `--force-with-lease`, so force-pushing could overwrite \
+             commits you haven't seen"
+        );
+        if self.cli.yes || confirm("Force-push with `--force` instead?", "--yes")? {
+            Ok("--force".to_owned())
+        } else {
+            Err(miette!(
+                help = format!(
+                    "Upgrade to Git {} or newer for `--force-with-lease`",
+                    GitVersion::FORCE_WITH_LEASE
+                ),
+                "Not force-pushing without `--force-with-lease`"
+            ))
+        }
+    }
+
+    /// Get the notes refs to push alongside the branch, from `--notes` or `push-notes`.
+    pub fn notes_refs(&self) -> Vec<String> {
+        if self.cli.notes.is_empty() {
+            if self.file.push_notes.unwrap_or(false) {
+                vec![DEFAULT_NOTES_REF.to_owned()]
+            } else {
+                Vec::new()
+            }
+        } else {
+            self.cli
+                .notes
+                .iter()
+                .map(|notes_ref| {
+                    if notes_ref.starts_with("refs/") {
+                        notes_ref.clone()
+                    } else {
+                        format!("refs/notes/{notes_ref}")
+                    }
+                })
+                .collect()
+        }
+    }
+
+    /// Can we rely on `push.autoSetupRemote` to push `branch` and set its upstream?
+    ///
+    /// Git only does this for a default push (no refspec) of the current branch, when
+    /// `push.default` pushes the current branch to a branch of the same name.
+    fn can_auto_setup_remote(&self, branch: &str) -> miette::Result<bool> {
+        if !self.cli.git_push_args.is_empty()
+            || !self.notes_refs().is_empty()
+            || self.git.current_branch()? != branch
+        {
+            return Ok(false);
+        }
+        let snapshot = self.snapshot()?;
+        let push_default = snapshot.config_get("push.default");
+        Ok(matches!(
+            push_default,
+            None | Some("simple") | Some("current")
+        ))
+    }
+
+    /// Log what `git push --porcelain` did, linking `REMOTE/BRANCH` to the branch's web page if
+    /// [`Config::hyperlinks`] is enabled.
+    ///
+    /// If Git didn't report any refs and the push `succeeded`, just log that it was pushed.
+    fn show_updates(
+        &self,
+        branch: &str,
+        remote: &str,
+        updates: &[RefUpdate],
+        succeeded: bool,
+    ) -> miette::Result<()> {
+        let pushed = format!("{remote}/{branch}");
+        let link = self
+            .hyperlinks()
+            .then(|| {
+                pull_request::branch_url(self, remote, branch)
+                    .ok()
+                    .flatten()
+            })
+            .flatten()
+            .map(|url| color::hyperlink(&pushed, &url));
+        let new = self
+            .git
+            .rev_parse(&format!("refs/heads/{branch}"))?
+            .map(|commit| commit.chars().take(7).collect::<String>());
+
+        if updates.is_empty() && succeeded {
+            tracing::info!("Pushed {}", link.as_deref().unwrap_or(&pushed));
+        }
+        for update in updates {
+            let mut description = update.describe(remote, new.as_deref());
+            if let Some(link) = &link {
+                description = description.replacen(&pushed, link, 1);
+            }
+            tracing::info!("{description}");
+        }
+        Ok(())
+    }
+
+    /// After pushing, print a URL to open a pull request, copy it, and open a page in the
+    /// browser, as requested. Failures are only logged, because the push itself succeeded.
+    fn show_urls(&self, branch: &str, remote: &str, stderr: &str) {
+        let open = self.open_target();
+        let hyperlinks = self.hyperlinks();
+
+        // GitLab prints the merge request's URL if `--mr` created one (or there already was
+        // one).
+        let existing = pull_request::merge_request_url(stderr);
+        let label = if existing.is_some() {
+            "View the merge request"
+        } else {
+            "Open a pull request"
+        };
+        let copy_url = self.copy_url();
+        let mut compare = existing;
+        if compare.is_none() && (!self.cli.no_url || copy_url || open == Some(OpenTarget::Compare))
+        {
+            match pull_request::url(self, remote, branch, stderr) {
+                Ok(url) => compare = url,
+                Err(err) => tracing::debug!("Failed to find a pull request URL: {err}"),
+            }
+        }
+        if let (false, Some(url)) = (self.cli.no_url, &compare) {
+            let styled = url
+                .if_supports_color(Stream::Stderr, |text| {
+                    Style::new().bold().cyan().style(text)
+                })
+                .to_string();
+            if hyperlinks {
+                tracing::info!("{label}: {}", color::hyperlink(styled, url));
+            } else {
+                tracing::info!("{label}: {styled}");
+            }
+        }
+
+        if copy_url {
+            let url = match compare.clone() {
+                Some(url) => Ok(Some(url)),
+                None => pull_request::branch_url(self, remote, branch),
+            };
+            match url {
+                Ok(Some(url)) => match clipboard::copy(&url) {
+                    // Print it too, in case the terminal ignored OSC 52.
+                    Ok(()) => tracing::info!("Copied {url} to the clipboard"),
+                    Err(err) => tracing::warn!("Failed to copy {url} to the clipboard: {err}"),
+                },
+                Ok(None) => tracing::warn!("Couldn't find a web page for {remote} to copy"),
+                Err(err) => tracing::warn!("Couldn't find a web page for {remote} to copy: {err}"),
+            }
+        }
+
+        let Some(target) = open else {
+            return;
+        };
+        let url = match target {
+            OpenTarget::Compare => Ok(compare),
+            OpenTarget::Branch => pull_request::branch_url(self, remote, branch),
+            OpenTarget::Repo => pull_request::repo_url(self, remote),
+        };
+        match url {
+            Ok(Some(url)) => {
+                if let Err(err) = browser::open(&url) {
+                    tracing::warn!("Failed to open {url} in a browser: {err}");
+                }
+            }
+            Ok(None) => tracing::warn!("Couldn't find a web page for {remote} to open"),
+            Err(err) => tracing::warn!("Couldn't find a web page for {remote} to open: {err}"),
+        }
+    }
+
+    /// Whether to link to web pages with OSC 8 escape sequences, which only makes sense for
+    /// colored output to a terminal.
+    pub fn hyperlinks(&self) -> bool {
+        !self.cli.no_hyperlinks && self.cli.color.stderr_enabled()
+    }
+
+    /// Get how long to let `git push` run before killing it, from `--timeout` or `timeout`.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.cli
+            .timeout
+            .or_else(|| self.file.timeout.map(Duration::from_secs))
+    }
+
+    /// Get how long `--watch-ci` follows CI, from `--watch-ci-timeout` or `watch-ci-timeout`.
+    pub fn watch_ci_timeout(&self) -> Duration {
+        self.cli
+            .watch_ci_timeout
+            .unwrap_or_else(|| Duration::from_secs(self.file.watch_ci_timeout.unwrap_or(30 * 60)))
+    }
+
+    /// Start appending logs to `log-file` from the configuration file, if `--log-file` didn't
+    /// already open one. See [`Cli::install_tracing`].
+    pub fn open_log_file(&self, log_file: &LogFile) {
+        if let Some(path) = self.log_file() {
+            log_file.open(path);
+        }
+    }
+
+    /// Get the paths of the configuration files, like [`ConfigFile::paths`].
+    pub fn config_paths(&self) -> miette::Result<Vec<PathBuf>> {
+        ConfigFile::paths(self.cli.config.as_deref(), self.dirs.as_ref())
+    }
+
+    /// Get the file to append debug logs to, from `--log-file` or `log-file`.
+    pub fn log_file(&self) -> Option<&Path> {
+        self.cli
+            .log_file
+            .as_ref()
+            .or(self.file.log_file.as_ref())
+            .map(PathBuf::as_path)
+    }
+
+    /// Whether to copy the pull request URL after pushing, from `--copy-url` or `copy-url`.
+    pub fn copy_url(&self) -> bool {
+        self.cli.copy_url || self.file.copy_url.unwrap_or(false)
+    }
+
+    /// Get the page to open after pushing, from `--open` or `open` in the configuration file.
+    pub fn open_target(&self) -> Option<OpenTarget> {
+        self.cli.open.or_else(|| {
+            self.file
+                .open
+                .unwrap_or(false)
+                .then_some(OpenTarget::Compare)
+        })
+    }
+
+    /// Try to push to the given remote.
+    ///
+    /// Failing to push isn't an error; check [`Attempt::succeeded`].
+    pub fn try_push(&self, branch: &str, remote: &Remote) -> miette::Result<Attempt> {
+        let url = remote.resolved_push_url.as_ref();
+        let remote = remote.name.as_str();
+        self.check_default_branch(branch, remote)?;
+
+        let start = Instant::now();
+        // Time spent waiting for `--confirm` isn't counted.
+        let prompting = Cell::new(Duration::ZERO);
+        let attempt = |result, exit_code, failure, message| Attempt {
+            remote: remote.to_owned(),
+            result,
+            duration_ms: start.elapsed().saturating_sub(prompting.get()).as_millis() as u64,
+            exit_code,
+            failure,
+            message,
+            output: String::new(),
+            ref_updates: Vec::new(),
+            retry: 0,
+            help: None,
+        };
+
+        if self.cli.sync {
+            let synced = sync::sync(self, branch, remote)?;
+            // Syncing fetches, and may rebase or merge the branch.
+            self.refresh_snapshot();
+            if !synced {
+                return Ok(attempt(
+                    AttemptResult::Failed,
+                    None,
+                    None,
+                    Some(format!("failed to sync {branch}")),
+                ));
+            }
+        }
+
+        if self.is_up_to_date(branch, remote)? {
+            tracing::info!("Already up to date with {remote}/{branch}");
+            return Ok(attempt(AttemptResult::UpToDate, None, None, None));
+        }
+
+        if let Some(mode) = self.cli.summary {
+            let prompt_start = Instant::now();
+            if !summary::summarize(self, branch, remote, mode)? {
+                return Err(miette!("Not pushing {branch}"));
+            }
+            if self.cli.confirm {
+                prompting.set(prompt_start.elapsed());
+            }
+        }
+
+        wip::check(self, branch, remote)?;
+        secrets::check(self, branch, remote)?;
+        large_files::check(self, branch, remote)?;
+        let moved = self.fetch_first(branch, remote)?;
+        let prompt_start = Instant::now();
+        self.confirm_force(branch, remote, moved)?;
+        prompting.set(prompting.get() + prompt_start.elapsed());
+
+        let mut pending = journal::PendingPush::new(branch, remote, self.forcing())?;
+        let command = self.push_command(branch, remote)?;
+        git::log_command(&command);
+
+        let PushResult {
+            exit_code,
+            timed_out,
+            interrupted,
+            stdout,
+            stderr,
+        } = self.git.push(&PushRequest {
+            remote: remote.to_owned(),
+            branch: branch.to_owned(),
+            command,
+            timeout: self.timeout(),
+        })?;
+        let success = exit_code == Some(0);
+        // Pushing updates the remote-tracking refs, and maybe the upstream.
+        self.refresh_snapshot();
+
+        if interrupted {
+            tracing::warn!("Interrupted, not trying further remotes");
+            return Err(Interrupted.into());
+        }
+
+        let updates = parse_porcelain(&stdout);
+        let branch_ref = format!("refs/heads/{branch}");
+        let branch_update = updates.iter().find(|update| update.to == branch_ref);
+        self.show_updates(branch, remote, &updates, success)?;
+
+        let result = if success && self.cli.dry_run {
+            tracing::info!("Dry run: {remote} would accept {branch}");
+            attempt(AttemptResult::Pushed, exit_code, None, None)
+        } else if success
+            && branch_update.is_some_and(|update| update.flag == RefUpdateFlag::UpToDate)
+        {
+            attempt(AttemptResult::UpToDate, exit_code, None, None)
+        } else if success {
+            match branch_update {
+                Some(update) if update.flag == RefUpdateFlag::New => pending.set_old(None),
+                Some(update) => {
+                    if let Some((old, _)) = update.range() {
+                        pending.set_old(self.git.rev_parse(old)?);
+                    }
+                }
+                None => {}
+            }
+            if let Err(err) = pending.record(self) {
+                tracing::warn!("Failed to record push for `git upstream undo`: {err}");
+            }
+            self.show_urls(branch, remote, &stderr);
+            attempt(AttemptResult::Pushed, exit_code, None, None)
+        } else {
+            // With `--porcelain`, Git prints why a ref was rejected on stdout, like
+            // `(fetch first)`, so classify that before the rest of stderr.
+            let rejection = branch_update
+                .filter(|update| update.flag == RefUpdateFlag::Rejected)
+                .map(|update| update.describe(remote, None));
+            let mut failure = match rejection.as_deref().map(FailureKind::classify) {
+                Some(FailureKind::Other) | None => FailureKind::classify(&stderr),
+                Some(failure) => failure,
+            };
+            if timed_out {
+                failure = FailureKind::Timeout;
+            }
+            // Servers don't always say that they're missing history, so check for ourselves.
+            if failure == FailureKind::Other && rejection.is_some() && self.git.is_shallow()? {
+                failure = FailureKind::Shallow;
+            }
+            // A failing `pre-push` hook prints whatever it likes, so guess from there being a
+            // hook and Git not reporting any refs.
+            if failure == FailureKind::Other
+                && updates.is_empty()
+                && self.git.git_path("hooks/pre-push")?.is_file()
+            {
+                failure = FailureKind::Hook;
+            }
+            tracing::debug!(%remote, %failure, "Failed to push to {remote}: {failure}");
+            let message = match rejection {
+                // The failure kind and duration say it all.
+                _ if timed_out => None,
+                Some(rejection) => Some(rejection),
+                None => failure::summarize(&stderr),
+            };
+            let help = if failure == FailureKind::PushOptions && self.merge_request_requested() {
+                Some(format!(
+                    "The `--mr` flags need GitLab, which creates merge requests from push \
+                     options, but {remote} doesn't support them"
+                ))
+            } else {
+                capabilities::help(self, failure, remote)
+                    .or_else(|| url.and_then(|url| failure::ssh_help(failure, url, &stderr)))
+            };
+            Attempt {
+                help,
+                ..attempt(AttemptResult::Failed, exit_code, Some(failure), message)
+            }
+        };
+        Ok(Attempt {
+            output: format!("{stdout}{stderr}"),
+            ref_updates: updates,
+            ..result
+        })
+    }
+
+    /// After a push failed because the repository is a shallow clone, fetch the rest of the
+    /// history with `git fetch --unshallow` if `--unshallow` was given or the user agrees.
+    ///
+    /// Returns whether the history was fetched, so the push is worth trying again.
+    fn unshallow(&self) -> miette::Result<bool> {
+        if !self.cli.unshallow
+            && (!std::io::stdin().is_terminal()
+                || !confirm(
+                    "This repository is a shallow clone. Fetch the rest of its history with \
+                     `git fetch --unshallow` and push again?",
+                    "--unshallow",
+                )?)
+        {
+            return Ok(false);
+        }
+
+        let mut command = git::command();
+        command.args(["fetch", "--unshallow"]);
+        // Keep stdout for `git upstream`'s result.
+        command.stdout(std::io::stderr());
+        git::log_command(&command);
+        command.status_checked().into_diagnostic()?;
+        self.refresh_snapshot();
+        Ok(true)
+    }
+
+    /// Decide which remotes to try pushing `branch` to, in order.
+    pub fn plan(&self, branch: &str) -> miette::Result<PushPlan> {
+        Ok(PushPlan {
+            branch: branch.to_owned(),
+            candidates: preflight::reorder(self, self.ranked_candidates()?),
+        })
+    }
+
+    /// Push `branch` to the first remote that accepts it, calling `on_attempt` after each
+    /// attempt.
+    pub fn push_report(
+        &self,
+        branch: &str,
+        on_attempt: impl FnMut(&Attempt) -> miette::Result<()>,
+    ) -> miette::Result<PushReport> {
+        let plan = self.plan(branch)?;
+        self.push_plan(&plan, on_attempt)
+    }
+
+    /// Push to the remotes in `plan` until one accepts the branch, calling `on_attempt` after
+    /// each attempt.
+    pub fn push_plan(
+        &self,
+        plan: &PushPlan,
+        mut on_attempt: impl FnMut(&Attempt) -> miette::Result<()>,
+    ) -> miette::Result<PushReport> {
+        let start = Instant::now();
+        let metadata_ms = self.snapshot()?.duration.as_millis() as u64;
+        let branch = plan.branch.as_str();
+        let candidates = &plan.candidates;
+        if self.cli.dry_run {
+            tracing::info!("Dry run: checking which remote would accept {branch}");
+        }
+        let mut attempts = Vec::new();
+        let mut outcome = Outcome {
+            success: false,
+            up_to_date: false,
+            remote: None,
+            remote_ref: None,
+        };
+
+        for (i, candidate) in candidates.iter().enumerate() {
+            // So structured logs say which remote each event is about.
+            let _span = tracing::info_span!("push", remote = %candidate.remote.name).entered();
+            if candidates.len() > 1 {
+                tracing::info!(
+                    "{}",
+                    format!(
+                        "Pushing to {} (attempt {} of {})…",
+                        candidate.remote,
+                        i + 1,
+                        candidates.len()
+                    )
+                    .if_supports_color(Stream::Stderr, |text| text.bold())
+                );
+            }
+            let mut retry = 0;
+            let mut unshallowed = false;
+            let attempt = loop {
+                let mut attempt = self.try_push(branch, &candidate.remote)?;
+                attempt.retry = retry;
+                on_attempt(&attempt)?;
+                if attempt.failure == Some(FailureKind::Shallow)
+                    && !unshallowed
+                    && self.unshallow()?
+                {
+                    unshallowed = true;
+                    tracing::info!("Pushing {branch} to {} again", candidate.remote);
+                    attempts.push(attempt);
+                    continue;
+                }
+                if !attempt.failure.is_some_and(FailureKind::is_transient)
+                    || retry >= self.cli.retry
+                {
+                    break attempt;
+                }
+                let delay = 2u32
+                    .checked_pow(retry)
+                    .and_then(|factor| self.cli.retry_delay.checked_mul(factor))
+                    .unwrap_or(MAX_RETRY_DELAY)
+                    .min(MAX_RETRY_DELAY);
+                retry += 1;
+                tracing::info!(
+                    "Retrying {} in {} (retry {retry} of {})",
+                    candidate.remote,
+                    report::format_duration(delay.as_millis() as u64),
+                    self.cli.retry
+                );
+                attempts.push(attempt);
+                std::thread::sleep(delay);
+            };
+            let succeeded = attempt.succeeded();
+            let up_to_date = attempt.result == AttemptResult::UpToDate;
+            let failure = attempt.failure;
+            attempts.push(attempt);
+            if succeeded {
+                outcome = Outcome {
+                    success: true,
+                    up_to_date,
+                    remote: Some(candidate.remote.name.clone()),
+                    remote_ref: Some(format!("refs/heads/{branch}")),
+                };
+                break;
+            }
+            if self.cli.fail_fast {
+                break;
+            }
+            if let Some(failure) = failure.filter(|&failure| match candidates.get(i + 1) {
+                Some(next) => capabilities::fails_the_same(
+                    self,
+                    failure,
+                    &candidate.remote.name,
+                    &next.remote.name,
+                ),
+                None => !failure.falls_through(),
+            }) {
+                if i + 1 < candidates.len() {
+                    tracing::info!(
+                        "{}: {failure}; not trying other remotes, because they'd fail the same way",
+                        candidate.remote,
+                    );
+                }
+                break;
+            }
+            if let Some(next) = candidates.get(i + 1) {
+                tracing::info!(
+                    "{} rejected the push, trying {} next",
+                    candidate.remote,
+                    next.remote
+                );
+            }
+        }
+
+        let report = PushReport {
+            branch: branch.to_owned(),
+            dry_run: self.cli.dry_run,
+            candidates: candidates.clone(),
+            attempts,
+            outcome,
+            metadata_ms,
+            duration_ms: start.elapsed().as_millis() as u64,
+        };
+        report.log_timings(self.cli.timings);
+        Ok(report)
+    }
+
+    /// Print the `git push` commands [`Config::try_push`] would run, for `--show-command`.
+    pub fn show_commands(&self, branch: &str) -> miette::Result<()> {
+        let remotes = self.candidate_remotes()?;
+        if remotes.is_empty() {
+            return Err(miette!("No remotes to push {branch} to"));
+        }
+        let count = if self.cli.all { remotes.len() } else { 1 };
+        for remote in &remotes[..count] {
+            println!("{}", shell::join(&self.push_command(branch, remote)?));
+        }
+        Ok(())
+    }
+
+    /// Push `branch` to the first remote that accepts it, returning that remote.
+    pub fn push(&self, branch: &str) -> miette::Result<String> {
+        let outcome = self.push_outcome(branch)?;
+        Ok(outcome.remote.expect("successful pushes have a remote"))
+    }
+
+    /// Like [`Config::push`], but also get whether anything was pushed.
+    pub fn push_outcome(&self, branch: &str) -> miette::Result<Outcome> {
+        let report = self.push_report(branch, |_| Ok(()))?;
+        if report.outcome.success {
+            Ok(report.outcome)
+        } else {
+            Err(PushFailed::new(self, &report).into())
+        }
+    }
+}
+
+/// Push the branch in `plan` to the first of its remotes that accepts it.
+///
+/// Failing to push to every remote isn't an error here; check [`Outcome::success`] in the
+/// report. Nothing is printed, except what `git push` itself writes to stderr and any logs.
+pub fn push_upstream(config: &Config, plan: &PushPlan) -> miette::Result<PushReport> {
+    config.push_plan(plan, |_| Ok(()))
+}
+
+/// The longest to wait between retries, however many there are.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10 * 60);
+
+/// How long to wait for a remote to say what its default branch is, without `--timeout`.
+const DEFAULT_BRANCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The notes ref `git notes` uses by default.
+const DEFAULT_NOTES_REF: &str = "refs/notes/commits";
+
+impl Cli {
+    /// Make colored output and error messages follow `--color`.
+    pub fn install_colors(&self) -> miette::Result<()> {
+        self.color.install()
+    }
+
+    /// Set up logging to stderr (and `--log-file`), from `--log`, `--log-format`, and friends.
+    ///
+    /// This happens before the configuration file is loaded, so that loading it is logged too;
+    /// once it's loaded, [`Config::open_log_file`] opens its `log-file`. Colors are set up
+    /// separately, with [`Cli::install_colors`].
+    pub fn install_tracing(&self) -> miette::Result<LogFile> {
+        let log_file = install_tracing(
+            self.log_filter(),
+            self.log_format,
+            self.color.stderr_enabled() && self.log_format != LogFormat::Json,
+        )?;
+        if let Some(path) = &self.log_file {
+            log_file.open(path);
+        }
+        Ok(log_file)
+    }
+
+    /// Run the subcommands which don't need the configuration file or a repository's
+    /// configuration, like `completions`, returning whether this was one of them.
+    pub fn run_standalone(&self) -> bool {
+        match &self.command {
+            Some(CliCommand::Completions(args)) => completions::completions(args),
+            Some(CliCommand::Complete(args)) => completions::complete(args),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Get the log filter directives.
+    ///
+    /// `--quiet` wins, then `--log` (or `$GIT_UPSTREAM_LOG`), then `--verbose`.
+    pub fn log_filter(&self) -> &str {
+        if self.quiet > 0 {
+            return "warn";
+        }
+        if let Some(log) = &self.log {
+            return log;
+        }
+        match self.verbose {
+            0 => "info",
+            1 => "debug",
+            _ => "trace",
+        }
+    }
+}
+
+/// An error for when there's no remote named `remote`, suggesting the closest of `remotes`.
+fn unknown_remote(remote: &str, remotes: &[Remote]) -> miette::Report {
+    let closest = remotes
+        .iter()
+        .map(|other| (strsim::damerau_levenshtein(remote, &other.name), other))
+        .filter(|(distance, _)| *distance <= (remote.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, other)| other);
+    let list = remotes
+        .iter()
+        .map(|other| other.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let help = match closest {
+        Some(closest) => format!("Did you mean `{closest}`? The remotes are: {list}"),
+        None => format!("The remotes are: {list}"),
+    };
+    exit_code::usage(miette!(help = help, "There's no remote named {remote}"))
+}
+
+/// Whether to pass `--set-upstream` when pushing a branch to a remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpstreamAction {
+    /// The branch has no upstream, or already tracks the remote; set it.
+    Set,
+    /// The branch has no upstream, and `push.autoSetupRemote` is set; let Git set it.
+    Auto,
+    /// The branch already tracks the remote, and `push.autoSetupRemote` is set; there's nothing
+    /// to change.
+    Tracking,
+    /// The branch tracks a different remote; leave its upstream alone.
+    Keep,
+    /// The branch tracks a different remote, but `--retarget-upstream` was given; change it.
+    Retarget,
+}
+
+impl UpstreamAction {
+    /// Decide what to do with the upstream of a branch currently tracking `existing`, when
+    /// pushing it to `remote`.
+    pub fn new(existing: Option<&str>, remote: &str, retarget: bool, auto_setup: bool) -> Self {
+        match existing {
+            Some(existing) if existing != remote => {
+                if retarget {
+                    Self::Retarget
+                } else {
+                    Self::Keep
+                }
+            }
+            Some(_) if auto_setup => Self::Tracking,
+            None if auto_setup => Self::Auto,
+            _ => Self::Set,
+        }
+    }
+}
+
+/// Configuration file format. See `config::TEMPLATE` for documentation.
+///
+/// TODO: Add `fail-fast`/`on-failure` behavior.
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ConfigFile {
+    /// Remotes to attempt to push to, in order.
+    #[serde(default)]
+    remotes: Vec<String>,
+
+    /// Glob patterns for branches which must not be deleted from remotes, and which need their
+    /// name typed out to force-push. Defaults to `main` and `master`.
+    #[serde(default)]
+    protected_branches: Option<Vec<String>>,
+
+    /// How `--sync` integrates remote changes: `rebase` (the default) or `ff-only`.
+    #[serde(default)]
+    sync_strategy: Option<SyncStrategy>,
+
+    /// Allow pushing a remote's default branch.
+    #[serde(default)]
+    allow_default_branch: Option<bool>,
+
+    /// Ask before force-pushing with `--force` or `--force-unchecked`. Defaults to true.
+    #[serde(default)]
+    confirm_force: Option<bool>,
+
+    /// Fetch the branch before force-pushing, as if `--fetch-first` was given.
+    #[serde(default)]
+    fetch_first: Option<bool>,
+
+    /// What to do about uncommitted changes before pushing. Defaults to `warn`.
+    #[serde(default)]
+    warn_dirty: Option<WarnDirty>,
+
+    /// Count untracked files as uncommitted changes for `warn-dirty`.
+    #[serde(default)]
+    warn_dirty_untracked: Option<bool>,
+
+    /// Refuse to push `fixup!`, `squash!`, `amend!`, and work-in-progress commits. Defaults to
+    /// true.
+    #[serde(default)]
+    check_wip: Option<bool>,
+
+    /// Regexes for the subjects of work-in-progress commits. Defaults to `(?i)^wip\b`.
+    #[serde(default)]
+    wip_patterns: Option<Vec<WipPattern>>,
+
+    /// Refuse to push commits which add lines which look like secrets, as if `--scan-secrets`
+    /// was given.
+    #[serde(default)]
+    scan_secrets: Option<bool>,
+
+    /// Regexes for more kinds of secrets, by name, for `scan-secrets`.
+    #[serde(default)]
+    secret_patterns: BTreeMap<String, SecretPattern>,
+
+    /// Glob patterns for files `scan-secrets` skips, like test fixtures.
+    #[serde(default)]
+    secret_allow_paths: Vec<String>,
+
+    /// Glob patterns for branches which are never pushed, like scratch branches.
+    #[serde(default)]
+    never_push: Vec<String>,
+
+    /// Sign pushes, as if `--signed` was given.
+    #[serde(default)]
+    signed: Option<Signed>,
+
+    /// Push atomically, as if `--atomic` was given.
+    #[serde(default)]
+    atomic: Option<bool>,
+
+    /// Overrides for `signed` and `atomic`, by remote name.
+    #[serde(default)]
+    remote_settings: RemoteSettingsMap,
+
+    /// Check the outgoing commits for large files. Defaults to true.
+    #[serde(default)]
+    check_large_files: Option<bool>,
+
+    /// Warn about files larger than this. Defaults to 10 MiB.
+    #[serde(default)]
+    warn_blob_size: Option<ByteSize>,
+
+    /// Refuse to push files larger than this, unless `--allow-large-files` is given. Defaults
+    /// to 100 MiB.
+    #[serde(default)]
+    max_blob_size: Option<ByteSize>,
+
+    /// Push `refs/notes/commits` alongside the branch, as if `--notes` was given.
+    #[serde(default)]
+    push_notes: Option<bool>,
+
+    /// Open the page to create a pull request after pushing, as if `--open` was given.
+    #[serde(default)]
+    open: Option<bool>,
+
+    /// Copy the pull request URL to the clipboard after pushing, as if `--copy-url` was given.
+    #[serde(default)]
+    copy_url: Option<bool>,
+
+    /// Append debug logs to this file, as if `--log-file` was given.
+    #[serde(default)]
+    log_file: Option<PathBuf>,
+
+    /// When to send a desktop notification after pushing, as if `--notify` was given.
+    #[serde(default)]
+    notify: Option<NotifyWhen>,
+
+    /// How many seconds a push must take before `notify` sends a notification. Defaults to 10.
+    #[serde(default)]
+    notify_after: Option<u64>,
+
+    /// How many seconds to let `git push` run before killing it, as if `--timeout` was given.
+    #[serde(default)]
+    timeout: Option<u64>,
+
+    /// How many seconds `--watch-ci` follows CI for, as if `--watch-ci-timeout` was given.
+    #[serde(default)]
+    watch_ci_timeout: Option<u64>,
+
+    /// The tool `--pr` creates pull requests with, instead of guessing from the remote's host.
+    #[serde(default)]
+    pr_tool: Option<PrTool>,
+
+    /// Hosts running GitLab, in addition to `gitlab.com` and hosts named `gitlab.*`.
+    #[serde(default)]
+    gitlab_hosts: Vec<String>,
+
+    /// How to build web URLs for self-hosted forges, by host.
+    #[serde(default)]
+    host_templates: HostTemplates,
+
+    /// A regex for ticket IDs in branch names, to reference in pull requests.
+    #[serde(default)]
+    ticket_pattern: Option<TicketPattern>,
+
+    /// The text to reference a ticket with, where `{ticket}` is the ticket ID.
+    #[serde(default)]
+    ticket_template: Option<String>,
+}
+
+impl ConfigFile {
+    /// Get the paths of the configuration files, lowest precedence first: the global
+    /// configuration file (or `config`, from `--config`), and then the repository's
+    /// `git-upstream.toml`.
+    ///
+    /// Without `dirs`, there's no global configuration file.
+    pub fn paths(config: Option<&Path>, dirs: Option<&UserDirs>) -> miette::Result<Vec<PathBuf>> {
+        let mut paths: Vec<_> = match config {
+            Some(config) => vec![config.to_owned()],
+            None => dirs
+                .map(|dirs| dirs.config_file("config.toml"))
+                .into_iter()
+                .collect(),
+        };
+        if let Some(git_dir) = git::common_dir()? {
+            paths.push(git_dir.join("git-upstream.toml"));
+        }
+        Ok(paths)
+    }
+
+    /// Load a configuration file, or the default configuration if it doesn't exist.
+    pub fn load(path: &Path) -> miette::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        tracing::debug!("Reading configuration file {}", path.display());
+        let contents = fs::read_to_string(path)
+            .into_diagnostic()
+            .wrap_err("Failed to read configuration file")?;
+        Self::parse(path, contents).map_err(exit_code::usage)
+    }
+
+    /// Parse a configuration file, pointing at the problem in `contents` if it's invalid.
+    pub fn parse(path: &Path, contents: String) -> miette::Result<Self> {
+        toml::from_str(&contents).map_err(|err| {
+            let diagnostic =
+                MietteDiagnostic::new(format!("Failed to deserialize configuration file {path:?}"));
+            let diagnostic = match err.span() {
+                Some(span) => diagnostic.with_label(LabeledSpan::at(span, err.message())),
+                None => diagnostic.with_help(err.message()),
+            };
+            miette::Report::new(diagnostic)
+                .with_source_code(NamedSource::new(path.display().to_string(), contents))
+        })
+    }
+
+    /// Merge another configuration file into this one. Values set in `overrides` take
+    /// precedence.
+    pub fn merge(self, overrides: Self) -> Self {
+        Self {
+            remotes: if overrides.remotes.is_empty() {
+                self.remotes
+            } else {
+                overrides.remotes
+            },
+            protected_branches: overrides.protected_branches.or(self.protected_branches),
+            sync_strategy: overrides.sync_strategy.or(self.sync_strategy),
+            allow_default_branch: overrides.allow_default_branch.or(self.allow_default_branch),
+            confirm_force: overrides.confirm_force.or(self.confirm_force),
+            fetch_first: overrides.fetch_first.or(self.fetch_first),
+            warn_dirty: overrides.warn_dirty.or(self.warn_dirty),
+            warn_dirty_untracked: overrides.warn_dirty_untracked.or(self.warn_dirty_untracked),
+            check_wip: overrides.check_wip.or(self.check_wip),
+            wip_patterns: overrides.wip_patterns.or(self.wip_patterns),
+            scan_secrets: overrides.scan_secrets.or(self.scan_secrets),
+            secret_patterns: {
+                let mut patterns = self.secret_patterns;
+                patterns.extend(overrides.secret_patterns);
+                patterns
+            },
+            check_large_files: overrides.check_large_files.or(self.check_large_files),
+            warn_blob_size: overrides.warn_blob_size.or(self.warn_blob_size),
+            max_blob_size: overrides.max_blob_size.or(self.max_blob_size),
+            secret_allow_paths: if overrides.secret_allow_paths.is_empty() {
+                self.secret_allow_paths
+            } else {
+                overrides.secret_allow_paths
+            },
+            signed: overrides.signed.or(self.signed),
+            atomic: overrides.atomic.or(self.atomic),
+            remote_settings: {
+                let mut settings = self.remote_settings;
+                settings.extend(overrides.remote_settings);
+                settings
+            },
+            never_push: if overrides.never_push.is_empty() {
+                self.never_push
+            } else {
+                overrides.never_push
+            },
+            push_notes: overrides.push_notes.or(self.push_notes),
+            open: overrides.open.or(self.open),
+            copy_url: overrides.copy_url.or(self.copy_url),
+            log_file: overrides.log_file.or(self.log_file),
+            notify: overrides.notify.or(self.notify),
+            notify_after: overrides.notify_after.or(self.notify_after),
+            timeout: overrides.timeout.or(self.timeout),
+            watch_ci_timeout: overrides.watch_ci_timeout.or(self.watch_ci_timeout),
+            pr_tool: overrides.pr_tool.or(self.pr_tool),
+            gitlab_hosts: if overrides.gitlab_hosts.is_empty() {
+                self.gitlab_hosts
+            } else {
+                overrides.gitlab_hosts
+            },
+            host_templates: self.host_templates.merge(overrides.host_templates),
+            ticket_pattern: overrides.ticket_pattern.or(self.ticket_pattern),
+            ticket_template: overrides.ticket_template.or(self.ticket_template),
+        }
+    }
+}
+
+/// A shortcut for `git push --set-upstream REMOTE BRANCH`.
+///
+/// After a successful push, `REMOTE/BRANCH` is printed on stdout; everything else is printed on
+/// stderr.
+#[derive(Debug, Clone, Parser)]
+#[command(version, author, about)]
+#[command(max_term_width = 100, disable_help_subcommand = true)]
+#[command(after_long_help = exit_code::HELP)]
+#[command(group(
+    clap::ArgGroup::new("review")
+        .args(["pr", "mr", "mr_target", "mr_title", "mr_remove_source_branch"])
+        .multiple(true)
+))]
+pub struct Cli {
+    /// Log filter directives, of the form `target[span{field=value}]=level`, where all components
+    /// except the level are optional. Defaults to `info`.
+    ///
+    /// Overrides `--verbose`, and is ignored with `--quiet`.
+    #[arg(long, env = "GIT_UPSTREAM_LOG", global = true)]
+    log: Option<String>,
+
+    /// How to format logs. With `json`, the log file is written as JSON too.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value = "pretty",
+        env = "GIT_UPSTREAM_LOG_FORMAT",
+        global = true
+    )]
+    log_format: LogFormat,
+
+    /// Also append debug logs, with timestamps, to this file.
+    #[arg(long, value_name = "PATH", global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Run as if `git upstream` was started in this directory, like `git -C`.
+    #[arg(short = 'C', long, value_name = "PATH", global = true)]
+    directory: Option<PathBuf>,
+
+    /// Read this configuration file instead of `~/.config/git-upstream/config.toml` (or
+    /// `%APPDATA%\git-upstream\config.toml` on Windows). The repository's `git-upstream.toml`
+    /// is still read.
+    #[arg(long, value_name = "PATH", env = "GIT_UPSTREAM_CONFIG", global = true)]
+    config: Option<PathBuf>,
+
+    /// Log more: `-v` for debug logs, `-vv` for trace logs.
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Only print warnings and errors, and pass `--quiet` to `git push`.
+    ///
+    /// Give twice to not print `REMOTE/BRANCH` on stdout after a successful push either.
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    quiet: u8,
+
+    /// When to color output. `NO_COLOR` and `CLICOLOR_FORCE` are respected with `auto`.
+    #[arg(long, value_name = "WHEN", default_value = "auto", global = true)]
+    color: ColorChoice,
+
+    /// By default, if pushing to a remote fails (e.g. because you don't have permissions),
+    /// `git-upstream` will try the next remote until one works.
+    ///
+    /// With this option, a single failure to push will abort the run.
+    #[arg(long, global = true)]
+    fail_fast: bool,
+
+    /// The branch to push. Defaults to the current branch.
+    #[arg(long)]
+    branch: Option<String>,
+
+    /// The remote to push to first. Defaults to `origin` if it exists and no `remotes` are
+    /// set in the configuration file.
+    ///
+    /// This can also be given as `REMOTE/BRANCH` to set the branch to push, as long as `REMOTE`
+    /// is the name of an existing remote.
+    #[arg(env = "GIT_UPSTREAM_REMOTE")]
+    remote: Option<String>,
+
+    /// Fail if a remote listed in the configuration file doesn't exist, instead of skipping it.
+    ///
+    /// Remotes given on the command line must always exist.
+    #[arg(long)]
+    strict_remotes: bool,
+
+    /// Push even if the branch already tracks the remote and matches its remote-tracking ref.
+    ///
+    /// By default, `git-upstream` skips the push in that case, but the remote-tracking ref may be
+    /// stale.
+    #[arg(long, visible_alias = "force-push-anyway")]
+    no_skip_up_to_date: bool,
+
+    /// Before pushing, fetch the branch from the remote and rebase onto it (or fast-forward,
+    /// depending on `sync-strategy` in the configuration file).
+    ///
+    /// If the remote branch doesn't exist yet, there's nothing to sync.
+    #[arg(long)]
+    sync: bool,
+
+    /// Change the branch's upstream even if it already tracks a different remote.
+    ///
+    /// By default, when falling back to another remote, the existing upstream is kept, so that
+    /// `git pull` keeps working.
+    #[arg(long)]
+    retarget_upstream: bool,
+
+    /// Force-push with `--force-with-lease`, after asking for confirmation (see `--yes`).
+    ///
+    /// With `--rename`, also delete the old remote branch even if it has commits which aren't
+    /// contained in the new branch.
+    #[arg(short, long)]
+    force: bool,
+
+    /// Force-push with plain `--force`, overwriting the remote branch even if it has commits
+    /// you haven't fetched.
+    #[arg(long, conflicts_with = "force")]
+    force_unchecked: bool,
+
+    /// Force-push with `--force-with-lease --force-if-includes`, which also refuses if the
+    /// remote branch has commits you've fetched but haven't integrated. Implies `--force`.
+    ///
+    /// Used by default if `push.useForceIfIncludes` is set.
+    #[arg(long, conflicts_with = "force_unchecked")]
+    force_if_includes: bool,
+
+    /// Before force-pushing, fetch the branch from the remote, so the lease checks against
+    /// what's there now. If someone else pushed, lists their commits and asks before
+    /// overwriting them.
+    #[arg(long)]
+    fetch_first: bool,
+
+    /// Force-push only if the remote branch points to COMMIT, with
+    /// `--force-with-lease=BRANCH:COMMIT`. Implies `--force`.
+    ///
+    /// Plain `--force-with-lease` only protects commits you've already fetched; this protects
+    /// everything but the commit you name.
+    #[arg(
+        long,
+        value_name = "COMMIT",
+        conflicts_with_all = ["force_unchecked", "stdin", "stack"],
+    )]
+    expect: Option<String>,
+
+    /// Also push a notes ref (`refs/notes/commits` by default). May be given multiple times.
+    ///
+    /// Notes refs which don't exist are skipped with a warning. With `--force`, notes refs are
+    /// force-pushed too.
+    #[arg(
+        long,
+        value_name = "REF",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_NOTES_REF,
+        require_equals = true,
+    )]
+    notes: Vec<String>,
+
+    /// Migrate the upstream of a branch renamed from `OLD`.
+    ///
+    /// Pushes the branch, deletes `OLD` from the remote it was pushed to, and removes any
+    /// leftover `branch.OLD.*` configuration.
+    #[arg(long, value_name = "OLD", conflicts_with = "delete")]
+    rename: Option<String>,
+
+    /// Delete the branch from its upstream remote and unset its upstream.
+    ///
+    /// If the branch has no upstream, it's deleted from the first remote that `git-upstream`
+    /// would push to. Protected branches are never deleted.
+    #[arg(long)]
+    delete: bool,
+
+    /// Allow pushing the remote's default branch (the branch `REMOTE/HEAD` points to).
+    ///
+    /// By default, `git-upstream` refuses, because that's usually a mistake in pull
+    /// request-based workflows.
+    #[arg(long)]
+    allow_default_branch: bool,
+
+    /// Push even while a rebase, merge, cherry-pick, or revert is in progress.
+    ///
+    /// By default, `git-upstream` refuses, because the branch may only be partly rewritten.
+    #[arg(long)]
+    allow_in_progress: bool,
+
+    /// Push even with uncommitted changes, when `warn-dirty = "block"` in the configuration
+    /// file.
+    #[arg(long)]
+    allow_dirty: bool,
+
+    /// Push even if the branch has `fixup!`, `squash!`, `amend!`, or work-in-progress commits
+    /// which aren't on the remote yet.
+    #[arg(long)]
+    allow_wip: bool,
+
+    /// Before pushing, scan the commits for lines which look like secrets, like AWS access keys,
+    /// private keys, and GitHub tokens, and refuse to push them.
+    #[arg(long)]
+    scan_secrets: bool,
+
+    /// With `--scan-secrets`, push even if there are lines which look like secrets.
+    #[arg(long)]
+    allow_secrets: bool,
+
+    /// Push even if the commits add files larger than `max-blob-size` (100 MiB by default).
+    #[arg(long)]
+    allow_large_files: bool,
+
+    /// Push even if the branch matches a `never-push` pattern.
+    #[arg(long)]
+    allow_never_push: bool,
+
+    /// Read branch names to push from stdin, one per line.
+    ///
+    /// Each branch is pushed and checked like a single branch would be. Every branch is
+    /// attempted, and the run fails if any of them couldn't be pushed.
+    #[arg(long, conflicts_with_all = ["branch", "rename", "delete", "sync"])]
+    stdin: bool,
+
+    /// Push every local branch between the remote's default branch and `HEAD`, oldest first.
+    ///
+    /// This is useful for stacked branches, where each branch is based on the previous one.
+    #[arg(long, conflicts_with_all = ["branch", "rename", "delete", "sync", "stdin"])]
+    stack: bool,
+
+    /// Print a JSON description of the push attempts and their result on stdout.
+    ///
+    /// `--json=stream` prints one JSON event per line as each remote is tried instead. Everything
+    /// after the push, like `--pr`, `--watch-ci`, and `--exit-code-up-to-date`, still applies.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        default_missing_value = "document",
+        require_equals = true,
+        conflicts_with_all = ["rename", "delete", "stdin", "stack"],
+    )]
+    json: Option<JsonFormat>,
+
+    /// Don't print a URL to open a pull request after pushing.
+    #[arg(long)]
+    no_url: bool,
+
+    /// Don't link to the pushed branch's web page with terminal hyperlinks.
+    #[arg(long, global = true)]
+    no_hyperlinks: bool,
+
+    /// Open a web page for the branch after pushing: the page to open a pull request (the
+    /// default), the branch's files, or the repository.
+    #[arg(
+        long,
+        value_name = "PAGE",
+        num_args = 0..=1,
+        default_missing_value = "compare",
+        require_equals = true
+    )]
+    open: Option<OpenTarget>,
+
+    /// Copy the URL to open a pull request (or the branch's web page) to the clipboard after
+    /// pushing. Over SSH, the terminal is asked to copy it instead.
+    #[arg(long)]
+    copy_url: bool,
+
+    /// Send a desktop notification when a push which took more than 10 seconds finishes:
+    /// `always` (the default), or only `on-failure`.
+    #[arg(
+        long,
+        value_name = "WHEN",
+        num_args = 0..=1,
+        default_missing_value = "always",
+        require_equals = true
+    )]
+    notify: Option<NotifyWhen>,
+
+    /// Before pushing, list the commits the remote doesn't have yet and a diffstat.
+    ///
+    /// With `--summary=fetch`, fetch the branch first if there's no remote-tracking ref for it,
+    /// so the list is accurate.
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "local",
+        require_equals = true
+    )]
+    summary: Option<SummaryMode>,
+
+    /// With `--summary`, ask before pushing.
+    #[arg(long, requires = "summary")]
+    confirm: bool,
+
+    /// Retry pushing to a remote up to this many times after network errors, before trying the
+    /// next remote.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    retry: u32,
+
+    /// Don't check for SSH keys and credentials before pushing. Normally, remotes we don't seem
+    /// to have credentials for are tried last.
+    #[arg(long)]
+    no_auth_check: bool,
+
+    /// Kill `git push` if it runs for longer than this, and try the next remote. A number
+    /// without a unit is in seconds.
+    #[arg(long, value_name = "DURATION", value_parser = timeout::parse_duration)]
+    timeout: Option<Duration>,
+
+    /// How long to wait before the first retry. The delay doubles after each retry, up to 10
+    /// minutes.
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "1s",
+        value_parser = timeout::parse_duration
+    )]
+    retry_delay: Duration,
+
+    /// After pushing, create a pull request for the branch with `gh pr create`, or a merge
+    /// request with `glab mr create` if the remote is on GitLab.
+    ///
+    /// If there's already a pull request for the branch, its URL is printed instead. Without
+    /// `--pr-title` or `--pr-body`, the title and body are filled in from the commits.
+    #[arg(long)]
+    pr: bool,
+
+    /// The pull request's title, for `--pr`.
+    #[arg(long, value_name = "TITLE", requires = "pr")]
+    pr_title: Option<String>,
+
+    /// The pull request's description, for `--pr`.
+    #[arg(long, value_name = "BODY", requires = "pr")]
+    pr_body: Option<String>,
+
+    /// Finish creating the pull request in a web browser, for `--pr`.
+    #[arg(long, requires = "pr")]
+    pr_web: bool,
+
+    /// Create a GitLab merge request for the branch as part of the push, with the
+    /// `merge_request.create` push option.
+    ///
+    /// This only applies to remotes on GitLab; self-hosted instances can be listed in
+    /// `gitlab-hosts` in the configuration file.
+    #[arg(long)]
+    mr: bool,
+
+    /// The branch the merge request should merge into, instead of the default branch. Implies
+    /// `--mr`.
+    #[arg(long, value_name = "BRANCH")]
+    mr_target: Option<String>,
+
+    /// The merge request's title, instead of the last commit's subject. Implies `--mr`.
+    #[arg(long, value_name = "TITLE")]
+    mr_title: Option<String>,
+
+    /// Delete the branch when the merge request is merged. Implies `--mr`.
+    #[arg(long)]
+    mr_remove_source_branch: bool,
+
+    /// Open the pull request or merge request as a draft, with `--pr` or `--mr`.
+    #[arg(long, requires = "review")]
+    draft: bool,
+
+    /// Follow CI for the pushed commit until it finishes, with `gh` or `glab`: log each status
+    /// change (`stream`, the default), only the result (`summary`), or exit with code 7 if CI
+    /// fails (`gate`).
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "stream",
+        require_equals = true
+    )]
+    watch_ci: Option<WatchCi>,
+
+    /// Stop following CI after this long, with `--watch-ci`. A number without a unit is in
+    /// seconds. Defaults to 30 minutes.
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = timeout::parse_duration,
+        requires = "watch_ci"
+    )]
+    watch_ci_timeout: Option<Duration>,
+
+    /// Push with Git even in a Jujutsu repository, where `git upstream` otherwise pushes the
+    /// bookmark on `@` or `@-` with `jj git push`.
+    #[arg(long, global = true)]
+    use_git: bool,
+
+    /// Don't run the `pre-push` hook, like `git push --no-verify`.
+    #[arg(long)]
+    no_verify: bool,
+
+    /// Sign the push with GPG, like `git push --signed`: `true` (the default), `false`, or
+    /// `if-asked` to only sign if the remote supports it.
+    #[arg(
+        long,
+        value_name = "WHEN",
+        num_args = 0..=1,
+        default_missing_value = "true",
+        require_equals = true
+    )]
+    signed: Option<Signed>,
+
+    /// Update the branch and any notes refs all at once or not at all, like `git push --atomic`.
+    #[arg(long)]
+    atomic: bool,
+
+    /// Fork the repository on GitHub with `gh repo fork`, add the fork as the `fork` remote,
+    /// and push to it. If the `fork` remote already exists, push to it.
+    #[arg(long, conflicts_with_all = ["remote", "stdin", "stack"])]
+    fork: bool,
+
+    /// If the push fails because the repository is a shallow clone, fetch the rest of its
+    /// history with `git fetch --unshallow` and push again, without asking first.
+    #[arg(long)]
+    unshallow: bool,
+
+    /// Exit with code 6 if the branch was already up to date, so nothing was pushed.
+    #[arg(long)]
+    exit_code_up_to_date: bool,
+
+    /// Report how long each attempt took, and the total time. These are always logged at the
+    /// debug level.
+    #[arg(long)]
+    timings: bool,
+
+    /// Choose a remote and check the push with `git push --dry-run`, without changing any refs.
+    ///
+    /// Remotes which reject the push are skipped as usual, so this shows which remote a real run
+    /// would push to. Nothing is recorded for `git upstream undo`, and `--open` is ignored.
+    #[arg(short = 'n', long, conflicts_with_all = ["rename", "delete", "sync"])]
+    dry_run: bool,
+
+    /// Print the `git push` command for the first remote, quoted for the shell, instead of
+    /// running it.
+    #[arg(
+        long,
+        visible_alias = "print",
+        conflicts_with_all = ["rename", "delete", "stdin", "stack", "json", "sync"]
+    )]
+    show_command: bool,
+
+    /// With `--show-command`, print a command for each remote, in the order they'd be tried.
+    #[arg(long, requires = "show_command")]
+    all: bool,
+
+    /// Don't ask for confirmation.
+    #[arg(short, long, global = true)]
+    yes: bool,
+
+    /// Extra arguments to pass to `git push`.
+    ///
+    /// Refspecs and options which change what's pushed, like `--delete` and `--all`, are
+    /// rejected.
+    #[arg(last = true)]
+    git_push_args: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+/// Subcommands, for things other than pushing a branch.
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum CliCommand {
+    /// Show each local branch's upstream and whether it's ahead, behind, or gone.
+    Status(StatusArgs),
+    /// Remove a branch's upstream, like `git branch --unset-upstream`.
+    Unset(UnsetArgs),
+    /// Delete local branches whose upstream is gone, like after a pull request is merged.
+    Prune(PruneArgs),
+    /// Fetch the current branch from the first remote that has it, and integrate it.
+    ///
+    /// Remotes are tried in the same order as when pushing, starting with the branch's upstream.
+    Pull(PullArgs),
+    /// Fetch from the first remote that works, trying remotes in the same order as when pushing.
+    Fetch(FetchArgs),
+    /// List remotes and the order they're tried in.
+    Remotes(RemotesArgs),
+    /// Revert the last push `git-upstream` made in this repository.
+    ///
+    /// If the push created the remote branch, it's deleted; otherwise, it's reset to the commit
+    /// it pointed to before. The branch's previous upstream is restored.
+    Undo(UndoArgs),
+    /// List recent pushes made by `git-upstream` in this repository, newest first.
+    Log(LogArgs),
+    /// Set the upstream of branches which already exist on a remote, without pushing.
+    Adopt(AdoptArgs),
+    /// Rename a remote, like `git remote rename`, and update branches which refer to it.
+    RenameRemote(RenameRemoteArgs),
+    /// Move the current branch's upstream to another remote, pushing it there if needed.
+    Switch(SwitchArgs),
+    /// Manage configuration files.
+    Config(ConfigArgs),
+    /// Check the environment and report anything that might make pushing fail.
+    ///
+    /// Exits with 0 if every check passed, 8 if any check warned, and 9 if any check failed.
+    Doctor(DoctorArgs),
+    /// Print a shell completion script.
+    ///
+    /// For example, add `source <(git-upstream completions bash)` to your `~/.bashrc`.
+    Completions(CompletionsArgs),
+    #[command(name = "__complete", hide = true)]
+    Complete(CompleteArgs),
+}
+
+/// Run `git upstream` with the options in `config`, like the binary does after setting up
+/// logging.
+///
+/// Unlike [`push_upstream`], this prints results, like `REMOTE/BRANCH` after a successful push.
+pub fn run(mut config: Config) -> miette::Result<Exit> {
+    if let Some(command) = &config.cli.command {
+        if !matches!(
+            command,
+            CliCommand::Config(_)
+                | CliCommand::Doctor(_)
+                | CliCommand::Completions(_)
+                | CliCommand::Complete(_)
+        ) {
+            git::require_repository()?;
+        }
+        match command {
+            CliCommand::Status(args) => status::status(&config, args),
+            CliCommand::Unset(args) => unset::unset(args),
+            CliCommand::Prune(args) => prune::prune(&config, args),
+            CliCommand::Pull(args) => pull::pull(&config, args),
+            CliCommand::Fetch(args) => fetch::fetch(&config, args),
+            CliCommand::Remotes(args) => remotes::remotes(&config, args),
+            CliCommand::Undo(args) => undo::undo(&config, args),
+            CliCommand::Log(args) => log::log(&config, args),
+            CliCommand::Adopt(args) => adopt::adopt(&config, args),
+            CliCommand::RenameRemote(args) => rename_remote::rename_remote(&config, args),
+            CliCommand::Switch(args) => switch::switch(&config, args),
+            CliCommand::Config(args) => config::config(&config, args),
+            CliCommand::Doctor(args) => {
+                return Ok(match doctor::doctor(&config, args)? {
+                    CheckStatus::Pass => Exit::Success,
+                    CheckStatus::Warn => Exit::DoctorWarnings,
+                    CheckStatus::Fail => Exit::DoctorFailures,
+                });
+            }
+            CliCommand::Completions(_) | CliCommand::Complete(_) => {
+                config.cli.run_standalone();
+                Ok(())
+            }
+        }?;
+        return Ok(Exit::Success);
+    }
+
+    git::require_work_tree()?;
+    config.split_remote_branch()?;
+    push_args::check(&config.cli.git_push_args).map_err(exit_code::usage)?;
+    if !config.cli.delete && !config.cli.show_command {
+        in_progress::check(&config)?;
+        dirty::check(&config)?;
+    }
+
+    if config.cli.fork {
+        fork::create(&config)?;
+        config.refresh_snapshot();
+        config.cli.remote = Some(fork::FORK_REMOTE.to_owned());
+    }
+
+    if config.cli.stdin {
+        return batch::push_stdin(&config).map(|()| Exit::Success);
+    }
+
+    if config.cli.stack {
+        return stack::push_stack(&config).map(|()| Exit::Success);
+    }
+
+    let branch = config.branch()?;
+    tracing::debug!("Pushing {branch}");
+
+    if let Some(old) = &config.cli.rename {
+        return rename::rename(&config, &branch, old).map(|()| Exit::Success);
+    }
+
+    if config.cli.delete {
+        return delete::delete(&config, &branch).map(|()| Exit::Success);
+    }
+
+    if config.cli.show_command {
+        return config.show_commands(&branch).map(|()| Exit::Success);
+    }
+
+    if jj::active(&config)? {
+        return jj::push(&config, &branch).map(|()| Exit::Success);
+    }
+
+    let start = Instant::now();
+    let result = match config.cli.json {
+        Some(format) => report::push_json(&config, &branch, format),
+        None => config.push_outcome(&branch),
+    };
+    notify::finished(&config, &branch, &result, start.elapsed());
+    let outcome = result?;
+    // Logs and `git push`'s output go to stderr, so this is the only thing on stdout, unless
+    // it's JSON.
+    if let (Some(remote), None, true) = (&outcome.remote, config.cli.json, config.cli.quiet < 2) {
+        println!("{remote}/{branch}");
+    }
+
+    if let (true, false, Some(remote)) = (config.cli.pr, config.cli.dry_run, &outcome.remote) {
+        // The push succeeded, so failing to create the pull request doesn't fail the run.
+        if let Err(err) = pr::create(&config, &branch, remote) {
+            eprintln!("{err:?}");
+        }
+    }
+
+    if let (Some(mode), false, Some(remote)) =
+        (config.cli.watch_ci, config.cli.dry_run, &outcome.remote)
+    {
+        // CI only fails the run with `--watch-ci=gate`.
+        if let Err(err) = ci::watch(&config, mode, &branch, remote) {
+            if err.downcast_ref::<CiFailed>().is_some() {
+                return Err(err);
+            }
+            tracing::warn!("Failed to watch CI: {err}");
+        }
+    }
+
+    if outcome.up_to_date && config.cli.exit_code_up_to_date {
+        Ok(Exit::UpToDate)
+    } else {
+        Ok(Exit::Success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A configuration from `args` and the configuration file `file`, without running Git.
+    pub(crate) fn config_from(args: &[&str], file: &str) -> Config {
+        Config {
+            dirs: None,
+            file: toml::from_str(file).unwrap(),
+            cli: Cli::parse_from(std::iter::once("git-upstream").chain(args.iter().copied())),
+            git_version: GitVersion::new(2, 45, 0),
+            git: Arc::new(FakeGit::new("main")),
+            snapshot: Mutex::default(),
+            remote_heads: RemoteHeads::default(),
+        }
+    }
+
+    #[test]
+    fn never_push_pattern() {
+        let config = config_from(&[], "never-push = ['main', 'release/*', 'wip/*']\n");
+        assert_eq!(config.never_push_pattern("main"), Some("main"));
+        assert_eq!(config.never_push_pattern("release/2.0"), Some("release/*"));
+        assert_eq!(config.never_push_pattern("wip/me/idea"), Some("wip/*"));
+        assert_eq!(config.never_push_pattern("maintenance"), None);
+        assert_eq!(config.never_push_pattern("feature/release/2.0"), None);
+
+        let config = config_from(&["--allow-never-push"], "never-push = ['*']\n");
+        assert_eq!(config.never_push_pattern("main"), None);
+
+        let config = config_from(&[], "");
+        assert_eq!(config.never_push_pattern("main"), None);
+    }
+
+    #[test]
+    fn upstream_action() {
+        use UpstreamAction::*;
+
+        // The existing upstream, the remote pushed to, `--retarget-upstream`,
+        // `push.autoSetupRemote`, and what to do.
+        let cases = [
+            (None, "origin", false, false, Set),
+            (None, "origin", true, false, Set),
+            (None, "origin", false, true, Auto),
+            (None, "origin", true, true, Auto),
+            (Some("origin"), "origin", false, false, Set),
+            (Some("origin"), "origin", true, false, Set),
+            (Some("origin"), "origin", false, true, Tracking),
+            (Some("origin"), "origin", true, true, Tracking),
+            (Some("origin"), "fork", false, false, Keep),
+            (Some("origin"), "fork", false, true, Keep),
+            (Some("origin"), "fork", true, false, Retarget),
+            (Some("origin"), "fork", true, true, Retarget),
+        ];
+        for (existing, remote, retarget, auto_setup, expected) in cases {
+            assert_eq!(
+                UpstreamAction::new(existing, remote, retarget, auto_setup),
+                expected,
+                "tracking {existing:?}, pushing to {remote}, retarget: {retarget}, \
+                 auto-setup: {auto_setup}"
+            );
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! `git upstream log`, for listing the pushes `git-upstream` has made.
+
+use std::time::SystemTime;
+
+use miette::IntoDiagnostic;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stdout;
+use owo_colors::Style;
+
+use crate::journal;
+use crate::journal::Journal;
+use crate::json;
+use crate::Config;
+
+/// List recent pushes made by `git-upstream` in this repository, newest first.
+#[derive(Debug, Clone, clap::Args)]
+pub struct LogArgs {
+    /// List pushes in every repository.
+    #[arg(long)]
+    all_repos: bool,
+
+    /// Print the pushes as JSON instead of a list.
+    #[arg(long)]
+    json: bool,
+
+    /// List at most this many pushes.
+    #[arg(long, short = 'n', value_name = "N")]
+    limit: Option<usize>,
+}
+
+/// Run `git upstream log`.
+pub fn log(config: &Config, args: &LogArgs) -> miette::Result<()> {
+    let journal = Journal::load(config)?;
+    let repo = if args.all_repos {
+        None
+    } else {
+        Some(journal::repo()?)
+    };
+
+    let records = journal
+        .pushes
+        .iter()
+        .rev()
+        .filter(|record| repo.as_ref().is_none_or(|repo| record.repo == *repo))
+        .take(args.limit.unwrap_or(usize::MAX))
+        .collect::<Vec<_>>();
+
+    if args.json {
+        println!("{}", json::to_string_pretty(&records).into_diagnostic()?);
+        return Ok(());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    for record in records {
+        let action = match &record.old {
+            Some(old) => format!("updated {}..{}", short(old), short(&record.new)),
+            None => format!("created at {}", short(&record.new)),
+        };
+        let force = if record.force { " (forced)" } else { "" };
+        let repo = if args.all_repos {
+            format!(" in {}", record.repo.display())
+        } else {
+            String::new()
+        };
+        println!(
+            "{} {} to {}{repo}: {action}{force}",
+            ago(now.saturating_sub(record.time))
+                .if_supports_color(Stdout, |text| Style::new().dimmed().style(text)),
+            record
+                .branch
+                .if_supports_color(Stdout, |text| Style::new().bold().style(text)),
+            record.remote,
+        );
+    }
+
+    Ok(())
+}
+
+/// Abbreviate a commit hash.
+fn short(hash: &str) -> &str {
+    hash.get(..8).unwrap_or(hash)
+}
+
+/// Describe a duration in seconds, like `5 minutes ago`.
+fn ago(seconds: u64) -> String {
+    let (count, unit) = match seconds {
+        0..60 => return "just now".to_owned(),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural} ago")
+}
@@ -0,0 +1,93 @@
+//! `--notify`, for desktop notifications when a slow push finishes.
+
+use std::process::Command;
+use std::process::Stdio;
+use std::time::Duration;
+
+use command_error::CommandExt;
+use miette::IntoDiagnostic;
+use serde::Deserialize;
+
+use crate::report::Outcome;
+use crate::Config;
+
+/// How long a run must take before it's worth a notification, by default.
+const DEFAULT_THRESHOLD: Duration = Duration::from_secs(10);
+
+/// When to send a desktop notification after pushing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifyWhen {
+    Always,
+    OnFailure,
+    Never,
+}
+
+/// Send a desktop notification about a finished push, if it took long enough and `--notify` or
+/// `notify` asks for one.
+///
+/// `result` is the outcome of the push, or the error. Failing to send the
+/// notification is only logged, because there may be no notification daemon.
+pub fn finished(
+    config: &Config,
+    branch: &str,
+    result: &miette::Result<Outcome>,
+    elapsed: Duration,
+) {
+    let when = config
+        .cli
+        .notify
+        .or(config.file.notify)
+        .unwrap_or(NotifyWhen::Never);
+    let threshold = config
+        .file
+        .notify_after
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_THRESHOLD);
+
+    let send = match when {
+        NotifyWhen::Always => true,
+        NotifyWhen::OnFailure => result.is_err(),
+        NotifyWhen::Never => false,
+    };
+    if !send || elapsed < threshold {
+        return;
+    }
+
+    let message = match result {
+        Ok(Outcome {
+            remote: Some(remote),
+            ..
+        }) => format!("Pushed {branch} to {remote}"),
+        Ok(_) => format!("Pushed {branch}"),
+        Err(err) => err.to_string(),
+    };
+    if let Err(err) = send_notification("git-upstream", &message) {
+        tracing::debug!("Failed to send a desktop notification: {err}");
+    }
+}
+
+/// Send a desktop notification with `osascript` on macOS or `notify-send` elsewhere.
+fn send_notification(title: &str, message: &str) -> miette::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {} with title {}",
+            quote(message),
+            quote(title)
+        ));
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name", title, title, message]);
+        command
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status_checked()
+        .into_diagnostic()?;
+    Ok(())
+}
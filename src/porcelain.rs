@@ -0,0 +1,248 @@
+//! Parsing `git push --porcelain` output.
+
+use serde::Serialize;
+
+/// How a ref was updated by `git push`, from the flag character of a `--porcelain` line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RefUpdateFlag {
+    /// ` `: a successfully pushed fast-forward.
+    FastForward,
+    /// `+`: a successful forced update.
+    Forced,
+    /// `-`: a successfully deleted ref.
+    Deleted,
+    /// `*`: a successfully pushed new ref.
+    New,
+    /// `!`: a ref that was rejected or failed to push.
+    Rejected,
+    /// `=`: a ref that was up to date and did not need pushing.
+    UpToDate,
+}
+
+impl RefUpdateFlag {
+    fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            ' ' => Self::FastForward,
+            '+' => Self::Forced,
+            '-' => Self::Deleted,
+            '*' => Self::New,
+            '!' => Self::Rejected,
+            '=' => Self::UpToDate,
+            _ => return None,
+        })
+    }
+
+    /// Did the ref push successfully (or not need pushing)?
+    pub fn succeeded(self) -> bool {
+        self != Self::Rejected
+    }
+}
+
+/// A single ref update reported by `git push --porcelain`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RefUpdate {
+    /// How the ref was updated.
+    pub flag: RefUpdateFlag,
+    /// The local ref, like `refs/heads/main`. Empty for deletions.
+    pub from: String,
+    /// The remote ref, like `refs/heads/main`.
+    pub to: String,
+    /// Git's summary of the update, like `[new branch]` or `[rejected] (non-fast-forward)`.
+    pub summary: String,
+}
+
+impl RefUpdate {
+    /// Get the abbreviated old and new commits of an update, from summaries like `a1b2..c3d4` or
+    /// `a1b2...c3d4 (forced update)`.
+    pub fn range(&self) -> Option<(&str, &str)> {
+        let range = self.summary.split_whitespace().next()?;
+        range
+            .split_once("...")
+            .or_else(|| range.split_once(".."))
+            .filter(|(old, new)| !old.is_empty() && !new.is_empty())
+    }
+
+    /// Get why the ref was rejected, from summaries like `[rejected] (non-fast-forward)`.
+    pub fn reason(&self) -> Option<&str> {
+        let (_, reason) = self.summary.split_once('(')?;
+        reason.strip_suffix(')')
+    }
+
+    /// Describe the update in one line, like `created fork/foo at 1a2b3c4` or `updated
+    /// origin/bar 9d8c7b..1a2b3c (forced)`.
+    ///
+    /// `new` is the pushed commit, since Git doesn't print it for new refs.
+    pub fn describe(&self, remote: &str, new: Option<&str>) -> String {
+        let name = self.to.strip_prefix("refs/heads/").unwrap_or(&self.to);
+        let target = format!("{remote}/{name}");
+        match self.flag {
+            RefUpdateFlag::New => match new {
+                Some(new) => format!("created {target} at {new}"),
+                None => format!("created {target}"),
+            },
+            RefUpdateFlag::FastForward | RefUpdateFlag::Forced => {
+                let forced = if self.flag == RefUpdateFlag::Forced {
+                    " (forced)"
+                } else {
+                    ""
+                };
+                match self.range() {
+                    Some((old, new)) => format!("updated {target} {old}..{new}{forced}"),
+                    None => format!("updated {target}{forced}"),
+                }
+            }
+            RefUpdateFlag::Deleted => format!("deleted {target}"),
+            RefUpdateFlag::UpToDate => {
+                format!("{target} is already up to date, so nothing was pushed")
+            }
+            RefUpdateFlag::Rejected => match self.reason() {
+                Some(reason) => format!("rejected {target} ({reason})"),
+                None => format!("rejected {target}"),
+            },
+        }
+    }
+}
+
+/// Parse the ref update lines from `git push --porcelain` output.
+///
+/// Lines look like `<flag> \t <from>:<to> \t <summary>`; other lines (`To <url>`, `Done`) are
+/// ignored.
+pub fn parse_porcelain(output: &str) -> Vec<RefUpdate> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut chars = line.chars();
+            let flag = RefUpdateFlag::from_char(chars.next()?)?;
+            let (refs, summary) = chars.as_str().strip_prefix('\t')?.split_once('\t')?;
+            let (from, to) = refs.split_once(':')?;
+            Some(RefUpdate {
+                flag,
+                from: from.to_owned(),
+                to: to.to_owned(),
+                summary: summary.to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the ref updates from `git push --porcelain` output, as tuples.
+    fn parse(output: &str) -> Vec<(RefUpdateFlag, String, String, String)> {
+        parse_porcelain(output)
+            .into_iter()
+            .map(|update| (update.flag, update.from, update.to, update.summary))
+            .collect()
+    }
+
+    fn update(
+        flag: RefUpdateFlag,
+        from: &str,
+        to: &str,
+        summary: &str,
+    ) -> (RefUpdateFlag, String, String, String) {
+        (flag, from.to_owned(), to.to_owned(), summary.to_owned())
+    }
+
+    #[test]
+    fn new_and_up_to_date() {
+        let output = "To /tmp/origin.git\n\
+                      *\trefs/heads/feature:refs/heads/feature\t[new branch]\n\
+                      =\trefs/heads/main:refs/heads/main\t[up to date]\n\
+                      Done\n";
+        assert_eq!(
+            parse(output),
+            [
+                update(
+                    RefUpdateFlag::New,
+                    "refs/heads/feature",
+                    "refs/heads/feature",
+                    "[new branch]"
+                ),
+                update(
+                    RefUpdateFlag::UpToDate,
+                    "refs/heads/main",
+                    "refs/heads/main",
+                    "[up to date]"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn fast_forward_and_forced() {
+        let updates = parse_porcelain(
+            "To /tmp/origin.git\n \trefs/heads/a:refs/heads/a\t8dc145a..e2573ac\n\
+             +\trefs/heads/b:refs/heads/b\te2573ac...31951c1 (forced update)\nDone\n",
+        );
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].flag, RefUpdateFlag::FastForward);
+        assert_eq!(updates[0].range(), Some(("8dc145a", "e2573ac")));
+        assert_eq!(
+            updates[0].describe("origin", None),
+            "updated origin/a 8dc145a..e2573ac"
+        );
+        assert_eq!(updates[1].flag, RefUpdateFlag::Forced);
+        assert_eq!(updates[1].range(), Some(("e2573ac", "31951c1")));
+        assert_eq!(
+            updates[1].describe("origin", None),
+            "updated origin/b e2573ac..31951c1 (forced)"
+        );
+    }
+
+    #[test]
+    fn rejected() {
+        let updates = parse_porcelain(
+            "To /tmp/origin.git\n\
+             !\trefs/heads/feature:refs/heads/feature\t[rejected] (non-fast-forward)\n\
+             !\t:refs/heads/main\t[remote rejected] (deletion of the current branch prohibited)\n\
+             Done\n",
+        );
+        assert_eq!(updates.len(), 2);
+        assert!(updates.iter().all(|update| !update.flag.succeeded()));
+        assert_eq!(updates[0].reason(), Some("non-fast-forward"));
+        assert_eq!(
+            updates[0].describe("fork", None),
+            "rejected fork/feature (non-fast-forward)"
+        );
+        assert_eq!(updates[1].from, "");
+        assert_eq!(
+            updates[1].reason(),
+            Some("deletion of the current branch prohibited")
+        );
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        assert_eq!(parse(""), []);
+        assert_eq!(parse("To /tmp/origin.git\nDone\n"), []);
+        assert_eq!(
+            parse("remote: error: hook declined\n?\trefs/heads/a:refs/heads/a\tx\n"),
+            []
+        );
+        // Without a trailing `Done`, like when the connection drops.
+        assert_eq!(
+            parse("*\trefs/heads/a:refs/heads/a\t[new branch]"),
+            [update(
+                RefUpdateFlag::New,
+                "refs/heads/a",
+                "refs/heads/a",
+                "[new branch]"
+            )]
+        );
+    }
+
+    #[test]
+    fn describes_new_refs() {
+        let update = &parse_porcelain("*\trefs/heads/a:refs/heads/a\t[new branch]\n")[0];
+        assert_eq!(update.range(), None);
+        assert_eq!(
+            update.describe("origin", Some("1a2b3c4")),
+            "created origin/a at 1a2b3c4"
+        );
+        assert_eq!(update.describe("origin", None), "created origin/a");
+    }
+}
@@ -0,0 +1,191 @@
+//! Creating a pull request after pushing, for `--pr`, with `gh` or `glab`.
+
+use std::process::Command;
+use std::process::Stdio;
+
+use command_error::CommandExt;
+use command_error::OutputContext;
+use miette::miette;
+use miette::IntoDiagnostic;
+use serde::Deserialize;
+use utf8_command::Utf8Output;
+
+use crate::git;
+use crate::remote_url::Forge;
+use crate::ticket::Ticket;
+use crate::Config;
+
+/// The command-line tool `--pr` creates pull requests with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PrTool {
+    /// The GitHub CLI, `gh pr create`.
+    Gh,
+    /// The GitLab CLI, `glab mr create`.
+    Glab,
+}
+
+impl PrTool {
+    /// The names of the tools in configuration files.
+    pub const NAMES: &[&str] = &["gh", "glab"];
+
+    /// The tool's executable.
+    pub fn program(self) -> &'static str {
+        match self {
+            Self::Gh => "gh",
+            Self::Glab => "glab",
+        }
+    }
+
+    /// Where to get the tool from.
+    pub fn install_help(self) -> &'static str {
+        match self {
+            Self::Gh => "Install it from https://cli.github.com/",
+            Self::Glab => "Install it from https://gitlab.com/gitlab-org/cli",
+        }
+    }
+
+    /// Check if the tool is installed, by running `TOOL --version`.
+    pub fn installed(self) -> bool {
+        Command::new(self.program())
+            .arg("--version")
+            .output_checked()
+            .is_ok()
+    }
+}
+
+/// Get the tool for talking to `remote`'s forge: `pr-tool` from the configuration file, or
+/// `gh` for GitHub and `glab` for GitLab.
+///
+/// Returns `None` for other hosts.
+pub fn tool(config: &Config, remote: &str) -> miette::Result<Option<PrTool>> {
+    if let Some(tool) = config.file.pr_tool {
+        return Ok(Some(tool));
+    }
+    let forge = config
+        .remote_url(remote)?
+        .and_then(|url| url.web(&config.file.host_templates).forge);
+    Ok(if forge == Some(Forge::GitHub) {
+        Some(PrTool::Gh)
+    } else if config.is_gitlab(remote)? {
+        Some(PrTool::Glab)
+    } else {
+        None
+    })
+}
+
+/// Create a pull request for `branch`, which was just pushed to `remote`. If there's already a
+/// pull request for the branch, log its URL instead.
+pub fn create(config: &Config, branch: &str, remote: &str) -> miette::Result<()> {
+    let Some(tool) = tool(config, remote)? else {
+        return Err(miette!(
+            help = "Set `pr-tool` in the configuration file to `gh` or `glab` if the remote is on \
+                    GitHub or GitLab",
+            "Don't know how to create a pull request on {remote}"
+        ));
+    };
+    let program = tool.program();
+
+    if !tool.installed() {
+        return Err(miette!(
+            help = tool.install_help(),
+            "`--pr` needs `{program}`, which isn't installed"
+        ));
+    }
+
+    // The owner (or the whole path, for GitLab) of the repository the branch was pushed to, in
+    // case it's a fork.
+    let head_repo = config.remote_url(remote)?.map(|url| url.path());
+
+    if let Some(url) = existing(tool, branch)? {
+        tracing::info!("There's already a pull request for {branch}: {url}");
+        return Ok(());
+    }
+
+    let cli = &config.cli;
+    let body = match Ticket::of(config, branch) {
+        Some(ticket) => Some(ticket.add_to(cli.pr_body.as_deref())),
+        None => cli.pr_body.clone(),
+    };
+    let mut command = git::repo_command(program);
+    match tool {
+        PrTool::Gh => {
+            command.args(["pr", "create", "--head"]);
+            match head_repo.as_deref().and_then(|path| path.split('/').next()) {
+                Some(owner) => command.arg(format!("{owner}:{branch}")),
+                None => command.arg(branch),
+            };
+            if let Some(title) = &cli.pr_title {
+                command.args(["--title", title]);
+            }
+            if let Some(body) = &body {
+                command.args(["--body", body]);
+            }
+        }
+        PrTool::Glab => {
+            command.args(["mr", "create", "--source-branch", branch, "--yes"]);
+            if let Some(head_repo) = &head_repo {
+                command.args(["--head", head_repo]);
+            }
+            if let Some(title) = &cli.pr_title {
+                command.args(["--title", title]);
+            }
+            if let Some(body) = &body {
+                command.args(["--description", body]);
+            }
+        }
+    }
+    if cli.draft {
+        command.arg("--draft");
+    }
+    if cli.pr_web {
+        command.arg("--web");
+    } else if cli.pr_title.is_none() && cli.pr_body.is_none() {
+        // Otherwise, the tool asks for the title and body itself. The ticket reference, if
+        // there is one, replaces the body from the commits.
+        command.arg("--fill");
+    }
+
+    // Keep stdout for `git upstream`'s result. It's still a terminal, so prompts work.
+    command.stdout(std::io::stderr());
+    git::log_command(&command);
+    command.status_checked().map_err(|err| {
+        miette!(
+            help = format!(
+                "If `{program}` isn't signed in, sign in with `{program} auth login` and run \
+                 `git upstream --pr` again"
+            ),
+            "Pushed {branch}, but failed to create a pull request: {err}"
+        )
+    })?;
+    Ok(())
+}
+
+/// Get the URL of an open pull request for `branch`, if there is one.
+fn existing(tool: PrTool, branch: &str) -> miette::Result<Option<String>> {
+    let mut command = git::repo_command(tool.program());
+    match tool {
+        PrTool::Gh => command
+            .args(["pr", "view", branch, "--json", "url,state", "--jq"])
+            .arg(r#"select(.state == "OPEN") | .url"#),
+        // This only lists open merge requests.
+        PrTool::Glab => command.args(["mr", "list", "--source-branch", branch, "--output", "json"]),
+    };
+    command
+        .stdin(Stdio::null())
+        .output_checked_as(|context: OutputContext<Utf8Output>| {
+            // Failing just means there isn't one, or we can't tell.
+            if !context.status().success() {
+                return Ok::<_, command_error::Error>(None);
+            }
+            let stdout = &context.output().stdout;
+            Ok(match tool {
+                PrTool::Gh => Some(stdout.trim()).filter(|url| !url.is_empty()),
+                PrTool::Glab => stdout.split_once(r#""web_url""#).and_then(|(_, rest)| {
+                    rest.split('"').find(|part| part.starts_with("https://"))
+                }),
+            }
+            .map(ToOwned::to_owned))
+        })
+        .into_diagnostic()
+}
@@ -0,0 +1,189 @@
+//! Checking for credentials before pushing, so remotes which are sure to reject us are tried
+//! last.
+
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::git;
+use crate::pr::PrTool;
+use crate::remote_url::Forge;
+use crate::remote_url::RemoteUrl;
+use crate::remotes::Remote;
+use crate::remotes::RemoteSource;
+use crate::report::Candidate;
+use crate::timeout;
+use crate::Config;
+
+/// How long each check may take. If a check doesn't finish in time, the remote passes.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Move candidates which don't seem to have credentials to the end, keeping the order
+/// otherwise. They aren't skipped, because the checks are only heuristics.
+///
+/// Does nothing with `--no-auth-check`, or unless there are other remotes to try first.
+pub fn reorder(config: &Config, candidates: Vec<Candidate>) -> Vec<Candidate> {
+    if config.cli.no_auth_check || candidates.len() < 2 {
+        return candidates;
+    }
+
+    let problems: Vec<Option<String>> = std::thread::scope(|scope| {
+        let checks: Vec<_> = candidates
+            .iter()
+            .map(|candidate| {
+                scope.spawn(|| {
+                    // The user asked for this remote, so try it first regardless.
+                    if candidate.source == RemoteSource::CommandLine {
+                        None
+                    } else {
+                        check(config, &candidate.remote)
+                    }
+                })
+            })
+            .collect();
+        checks
+            .into_iter()
+            .map(|check| check.join().unwrap_or(None))
+            .collect()
+    });
+
+    let mut ready = Vec::new();
+    let mut demoted = Vec::new();
+    for (candidate, problem) in candidates.into_iter().zip(problems) {
+        match problem {
+            Some(problem) => {
+                tracing::info!(
+                    "Trying {} last, because {problem}; pass `--no-auth-check` to skip this check",
+                    candidate.remote
+                );
+                demoted.push(candidate);
+            }
+            None => ready.push(candidate),
+        }
+    }
+    ready.extend(demoted);
+    ready
+}
+
+/// Check if we seem to have credentials for `remote`, returning the problem if we don't.
+fn check(config: &Config, remote: &Remote) -> Option<String> {
+    let url = remote.resolved_push_url.as_ref()?;
+    match url.scheme.as_deref() {
+        None | Some("ssh" | "git+ssh" | "ssh+git") => check_ssh(config, url),
+        Some("https" | "http") => check_https(config, &remote.name, url),
+        _ => None,
+    }
+}
+
+/// Check that `ssh-agent` has a key, or that an identity file exists for the host.
+fn check_ssh(config: &Config, url: &RemoteUrl) -> Option<String> {
+    // A custom SSH command might authenticate some other way.
+    if std::env::var_os("GIT_SSH_COMMAND").is_some()
+        || std::env::var_os("GIT_SSH").is_some()
+        || config
+            .snapshot()
+            .is_ok_and(|snapshot| snapshot.config_get("core.sshCommand").is_some())
+    {
+        return None;
+    }
+
+    if succeeds(Command::new("ssh-add").arg("-l"))? {
+        return None;
+    }
+
+    // `ssh -G` prints the configuration for the host, including the default identity files.
+    let mut command = Command::new("ssh");
+    command.arg("-G");
+    if let Some(port) = url.port {
+        command.args(["-p", &port.to_string()]);
+    }
+    if let Some(user) = &url.user {
+        command.args(["-l", user]);
+    }
+    command.arg(&url.host);
+    let output = timeout::output_with_timeout(&mut command, CHECK_TIMEOUT)
+        .ok()??
+        .stdout;
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let has_identity = String::from_utf8_lossy(&output).lines().any(|line| {
+        line.strip_prefix("identityfile ").is_some_and(|path| {
+            let path = match (path.strip_prefix("~/"), &home) {
+                (Some(path), Some(home)) => home.join(path),
+                _ => PathBuf::from(path),
+            };
+            path.exists()
+        })
+    });
+
+    (!has_identity).then(|| {
+        format!(
+            "`ssh-add -l` lists no keys and there's no SSH identity file for {}",
+            url.host
+        )
+    })
+}
+
+/// Check that a credential helper is configured, or that `gh` or `glab` is signed in to the
+/// host.
+fn check_https(config: &Config, remote: &str, url: &RemoteUrl) -> Option<String> {
+    // Credentials (or a token) in the URL.
+    if url.user.is_some() {
+        return None;
+    }
+    let web_url = url.web(&config.file.host_templates);
+    let helper = git::command()
+        .args(["config", "--get-urlmatch", "credential.helper"])
+        .arg(&web_url.repo)
+        .output()
+        .ok()?;
+    if !String::from_utf8_lossy(&helper.stdout).trim().is_empty() {
+        return None;
+    }
+
+    let tool = match web_url.forge {
+        Some(Forge::GitHub) => Some(PrTool::Gh),
+        Some(Forge::GitLab) => Some(PrTool::Glab),
+        _ if config.is_gitlab(remote).unwrap_or(false) => Some(PrTool::Glab),
+        _ => None,
+    };
+    let signed_in = tool.and_then(|tool| {
+        succeeds(
+            Command::new(tool.program())
+                .args(["auth", "status", "--hostname"])
+                .arg(&url.host),
+        )
+    });
+    match (tool, signed_in) {
+        (_, Some(true)) => None,
+        (Some(tool), Some(false)) => Some(format!(
+            "no credential helper is configured and `{} auth status` says you're not signed in \
+             to {}",
+            tool.program(),
+            url.host
+        )),
+        // Git can ask for a username and password.
+        _ if std::io::stdin().is_terminal() => None,
+        _ => Some(format!(
+            "no credential helper is configured for {}, and Git can't ask for a password \
+             without a terminal",
+            url.host
+        )),
+    }
+}
+
+/// Run `command` with [`CHECK_TIMEOUT`], returning whether it succeeded, or `None` if it couldn't
+/// be run or timed out.
+fn succeeds(command: &mut Command) -> Option<bool> {
+    match timeout::output_with_timeout(command, CHECK_TIMEOUT) {
+        Ok(Some(output)) => Some(output.status.success()),
+        Ok(None) => {
+            tracing::debug!("{command:?} timed out");
+            None
+        }
+        Err(err) => {
+            tracing::debug!("Failed to run {command:?}: {err}");
+            None
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Interactive confirmation prompts.
+
+use std::io::BufRead;
+use std::io::IsTerminal;
+use std::io::Write;
+
+use miette::miette;
+use miette::IntoDiagnostic;
+
+/// Ask the user a yes/no question on stderr, defaulting to "no".
+///
+/// Fails if stdin isn't a terminal, so that we never hang or guess in scripts. `skip_flag` names
+/// the flag that skips the prompt, for the error message.
+pub fn confirm(question: &str, skip_flag: &str) -> miette::Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Err(miette!(
+            "Refusing to continue without confirmation because stdin isn't a terminal; pass \
+             `{skip_flag}` to skip the prompt"
+        ));
+    }
+
+    let mut stderr = std::io::stderr();
+    write!(stderr, "{question} [y/N] ").into_diagnostic()?;
+    stderr.flush().into_diagnostic()?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .into_diagnostic()?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes" | "YES"))
+}
+
+/// Ask the user to confirm by typing `expected`, for actions where `y` is too easy to type by
+/// reflex.
+///
+/// Like [`confirm`], fails if stdin isn't a terminal.
+pub fn confirm_typed(question: &str, expected: &str, skip_flag: &str) -> miette::Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Err(miette!(
+            "Refusing to continue without confirmation because stdin isn't a terminal; pass \
+             `{skip_flag}` to skip the prompt"
+        ));
+    }
+
+    let mut stderr = std::io::stderr();
+    write!(stderr, "{question} Type `{expected}` to continue: ").into_diagnostic()?;
+    stderr.flush().into_diagnostic()?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .into_diagnostic()?;
+
+    Ok(answer.trim() == expected)
+}
+
+/// Ask the user to pick one of `choices` on stderr, defaulting to the first.
+///
+/// Like [`confirm`], fails if stdin isn't a terminal.
+pub fn choose(question: &str, choices: &[String]) -> miette::Result<usize> {
+    if !std::io::stdin().is_terminal() {
+        return Err(miette!(
+            "Can't ask {question:?} because stdin isn't a terminal"
+        ));
+    }
+
+    let mut stderr = std::io::stderr();
+    writeln!(stderr, "{question}").into_diagnostic()?;
+    for (i, choice) in choices.iter().enumerate() {
+        writeln!(stderr, "  {}. {choice}", i + 1).into_diagnostic()?;
+    }
+
+    loop {
+        write!(stderr, "[1-{}, default 1] ", choices.len()).into_diagnostic()?;
+        stderr.flush().into_diagnostic()?;
+
+        let mut answer = String::new();
+        std::io::stdin()
+            .lock()
+            .read_line(&mut answer)
+            .into_diagnostic()?;
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(0);
+        }
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=choices.len()).contains(&n) => return Ok(n - 1),
+            _ => writeln!(stderr, "Please enter a number from 1 to {}", choices.len())
+                .into_diagnostic()?,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! `git upstream prune`, for deleting local branches whose upstream has been deleted.
+
+use command_error::CommandExt;
+use miette::IntoDiagnostic;
+
+use crate::exit_code::NoRemotes;
+use crate::git;
+use crate::status::BranchStatus;
+use crate::Config;
+
+/// Delete local branches whose upstream is gone, like after a pull request is merged.
+#[derive(Debug, Clone, clap::Args)]
+pub struct PruneArgs {
+    /// Delete branches even if they aren't fully merged into `HEAD`.
+    ///
+    /// Branches from squash-merged pull requests are usually not fully merged.
+    #[arg(short, long)]
+    force: bool,
+
+    /// Print the branches that would be deleted without deleting them.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Run `git upstream prune`.
+pub fn prune(config: &Config, args: &PruneArgs) -> miette::Result<()> {
+    let remote = config
+        .candidate_remotes()?
+        .into_iter()
+        .next()
+        .ok_or(NoRemotes)?;
+
+    let mut command = git::command();
+    command.args(["fetch", "--prune", &remote]);
+    git::log_command(&command);
+    command.status_checked().into_diagnostic()?;
+
+    let gone = BranchStatus::list()?
+        .into_iter()
+        .filter(|status| status.gone)
+        .collect::<Vec<_>>();
+    if gone.is_empty() {
+        tracing::info!("No branches have an upstream which is gone");
+        return Ok(());
+    }
+
+    for status in gone {
+        let branch = &status.branch;
+        let upstream = status.upstream.as_deref().unwrap_or_default();
+
+        if status.current {
+            tracing::info!("Not deleting {branch} because it's checked out");
+            continue;
+        }
+        if let Some(pattern) = config.protected_pattern(branch) {
+            tracing::info!(
+                "Not deleting {branch} because it matches the protected branch pattern \
+                 `{pattern}`"
+            );
+            continue;
+        }
+        if !args.force && !git::is_ancestor(&format!("refs/heads/{branch}"), "HEAD")? {
+            tracing::info!(
+                "Not deleting {branch} because it isn't fully merged; use `--force` to delete it \
+                 anyway"
+            );
+            continue;
+        }
+
+        if args.dry_run {
+            tracing::info!("Would delete {branch} (was tracking {upstream})");
+            continue;
+        }
+
+        git::command()
+            .args(["branch", if args.force { "-D" } else { "-d" }, branch])
+            .output_checked_utf8()
+            .into_diagnostic()?;
+        tracing::info!("Deleted {branch} (was tracking {upstream})");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,137 @@
+//! `git upstream pull`, for fetching and integrating a branch from the best remote.
+
+use command_error::CommandExt;
+use miette::miette;
+use miette::IntoDiagnostic;
+
+use crate::git;
+use crate::git::Upstream;
+use crate::sync::remote_branch_exists;
+use crate::Config;
+
+/// Fetch the current branch from the first remote that has it, and integrate it.
+#[derive(Debug, Clone, clap::Args)]
+pub struct PullArgs {
+    /// The remote to try first.
+    remote: Option<String>,
+
+    /// Rebase onto the remote branch instead of fast-forwarding.
+    #[arg(long, conflicts_with = "merge")]
+    rebase: bool,
+
+    /// Merge the remote branch instead of fast-forwarding.
+    #[arg(long)]
+    merge: bool,
+}
+
+/// Run `git upstream pull`.
+pub fn pull(config: &Config, args: &PullArgs) -> miette::Result<()> {
+    let branch = config.git().current_branch()?;
+    if branch == "HEAD" {
+        return Err(miette!("Can't pull without a branch checked out"));
+    }
+    let upstream = Upstream::of(&branch)?;
+
+    let candidates = candidates(config, args, upstream.as_ref())?;
+    for remote in &candidates {
+        if try_pull(config, args, &branch, remote)? {
+            if upstream.is_none() {
+                git::command()
+                    .args([
+                        "branch",
+                        &format!("--set-upstream-to={remote}/{branch}"),
+                        &branch,
+                    ])
+                    .output_checked_utf8()
+                    .into_diagnostic()?;
+                tracing::info!("{branch} now tracks {remote}/{branch}");
+            }
+            return Ok(());
+        }
+    }
+
+    Err(miette!(
+        help = "Push it with `git upstream`",
+        "None of the remotes have a branch named {branch}; tried {}",
+        candidates.join(", ")
+    ))
+}
+
+/// The remotes to try pulling from, in order: the remote given on the command line, the remote
+/// of the existing upstream, and then the usual [`Config::candidate_remotes`].
+fn candidates(
+    config: &Config,
+    args: &PullArgs,
+    upstream: Option<&Upstream>,
+) -> miette::Result<Vec<String>> {
+    let mut ret = Vec::new();
+    let first = args
+        .remote
+        .iter()
+        .chain(upstream.map(|upstream| &upstream.remote))
+        .cloned();
+    for remote in first.chain(config.candidate_remotes()?) {
+        if !ret.contains(&remote) {
+            ret.push(remote);
+        }
+    }
+    Ok(ret)
+}
+
+/// Fetch `branch` from `remote` and integrate it.
+///
+/// Returns `false` if `remote` doesn't have the branch or couldn't be fetched from. Returns an
+/// error if integrating the remote branch fails, because trying another remote after that would
+/// be a mistake.
+fn try_pull(config: &Config, args: &PullArgs, branch: &str, remote: &str) -> miette::Result<bool> {
+    match remote_branch_exists(remote, branch) {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::debug!("{remote} doesn't have a branch named {branch}");
+            return Ok(false);
+        }
+        Err(err) => {
+            if config.cli.fail_fast {
+                return Err(err);
+            }
+            tracing::debug!("Failed to list branches on {remote}: {err}");
+            return Ok(false);
+        }
+    }
+
+    let mut command = git::command();
+    command.args(["fetch", remote, branch]);
+    git::log_command(&command);
+    if let Err(err) = command.status_checked() {
+        if config.cli.fail_fast {
+            return Err(err).into_diagnostic();
+        }
+        tracing::debug!("Failed to fetch from {remote}: {err}");
+        return Ok(false);
+    }
+
+    let tracking = format!("{remote}/{branch}");
+    let mut command = git::command();
+    if args.rebase {
+        command.args(["rebase", &tracking]);
+    } else if args.merge {
+        command.args(["merge", &tracking]);
+    } else {
+        command.args(["merge", "--ff-only", &tracking]);
+    }
+    git::log_command(&command);
+
+    if command.status_checked().is_err() {
+        let help = if args.rebase || args.merge {
+            "Resolve the conflicts and continue, or abort to give up"
+        } else {
+            "Use `--rebase` or `--merge` to integrate diverged branches"
+        };
+        return Err(miette!(
+            help = help,
+            "Failed to integrate {tracking} into {branch}"
+        ));
+    }
+
+    Ok(true)
+}
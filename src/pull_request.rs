@@ -0,0 +1,92 @@
+//! Finding the URL to open a pull request for a pushed branch.
+
+use crate::Config;
+
+/// Which page `--open` opens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OpenTarget {
+    /// The page to open a pull request for the branch.
+    Compare,
+    /// The branch's files.
+    Branch,
+    /// The repository's main page.
+    Repo,
+}
+
+/// Find a URL to open a pull request for `branch`, which was just pushed to `remote`.
+///
+/// This uses the URL the server printed in `stderr` if there is one, and otherwise builds one
+/// from the remote's URL.
+pub fn url(
+    config: &Config,
+    remote: &str,
+    branch: &str,
+    stderr: &str,
+) -> miette::Result<Option<String>> {
+    if let Some(url) = url_from_stderr(stderr) {
+        return Ok(Some(url));
+    }
+
+    let Some(url) = config.remote_url(remote)? else {
+        return Ok(None);
+    };
+    url.web(&config.file.host_templates).compare(branch, || {
+        Ok(config
+            .default_branch(remote)?
+            .map(|default| default.name)
+            .unwrap_or_else(|| "main".to_owned()))
+    })
+}
+
+/// Get the URL of `branch`'s files on `remote`.
+pub fn branch_url(config: &Config, remote: &str, branch: &str) -> miette::Result<Option<String>> {
+    Ok(config
+        .remote_url(remote)?
+        .and_then(|url| url.web(&config.file.host_templates).branch(branch)))
+}
+
+/// Get the URL of `remote`'s main page.
+///
+/// Unlike the other URLs, this works for unknown hosts, on the assumption that they serve the
+/// repository at the same path.
+pub fn repo_url(config: &Config, remote: &str) -> miette::Result<Option<String>> {
+    Ok(config.remote_url(remote)?.map(|url| {
+        let web = url.web(&config.file.host_templates);
+        if web.uncertain() {
+            tracing::debug!("Guessing {remote}'s web page is {}", web.repo);
+        }
+        web.repo
+    }))
+}
+
+/// Find the URL of an existing merge request in the `remote:` lines `git push` prints, like
+/// GitLab's `View merge request for branch: ...`.
+pub fn merge_request_url(stderr: &str) -> Option<String> {
+    stderr
+        .lines()
+        .filter_map(|line| line.strip_prefix("remote:"))
+        .flat_map(str::split_whitespace)
+        .find(|word| {
+            word.starts_with("https://")
+                && word
+                    .rsplit_once("/merge_requests/")
+                    .is_some_and(|(_, id)| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .map(ToOwned::to_owned)
+}
+
+/// Find a pull request URL in the `remote:` lines `git push` prints, like GitHub's
+/// `Create a pull request for 'branch' on GitHub by visiting: ...`.
+fn url_from_stderr(stderr: &str) -> Option<String> {
+    stderr
+        .lines()
+        .filter_map(|line| line.strip_prefix("remote:"))
+        .flat_map(str::split_whitespace)
+        .find(|word| {
+            word.starts_with("https://")
+                && (word.contains("/pull/new/")
+                    || word.contains("/merge_requests/new")
+                    || word.contains("/compare/"))
+        })
+        .map(ToOwned::to_owned)
+}
@@ -0,0 +1,112 @@
+//! Checking the extra arguments given to pass to `git push`.
+
+use miette::miette;
+
+/// Long options which take a value in the next argument, unless it's given with `=`.
+const LONG_WITH_VALUE: &[&str] = &["--push-option", "--receive-pack", "--exec", "--repo"];
+
+/// Options which change what's pushed, so that the branch and `--set-upstream` which
+/// `git-upstream` adds don't make sense alongside them.
+const CONFLICTING: &[&str] = &["--delete", "--mirror", "--all", "--branches"];
+
+/// Check that the extra arguments for `git push` don't conflict with the ones `git-upstream`
+/// generates.
+///
+/// Refspecs and `--delete`-like options are rejected, because `git-upstream` always pushes
+/// exactly one branch.
+pub fn check(args: &[String]) -> miette::Result<()> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            if let Some(refspec) = args.next() {
+                return Err(refspec_error(refspec));
+            }
+            break;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let name = long.split_once('=').map_or(long, |(name, _)| name);
+            let name = format!("--{name}");
+            if CONFLICTING.contains(&name.as_str()) {
+                return Err(miette!(
+                    help = format!(
+                        "Use `git push {name}` directly; `git-upstream` pushes a single branch"
+                    ),
+                    "Can't pass `{name}` to `git push`"
+                ));
+            }
+            if LONG_WITH_VALUE.contains(&name.as_str()) && !long.contains('=') {
+                args.next();
+            }
+        } else if let Some(short) = arg.strip_prefix('-').filter(|short| !short.is_empty()) {
+            // Short options can be bundled, like `-fo value`; `-o` takes the rest of the
+            // argument, or the next one.
+            for (i, flag) in short.char_indices() {
+                match flag {
+                    'd' => {
+                        return Err(miette!(
+                            help = "Use `git push --delete` directly, or `git upstream --delete` \
+                                    to delete the current branch's upstream",
+                            "Can't pass `-d` to `git push`"
+                        ));
+                    }
+                    'o' => {
+                        if i + 1 == short.len() {
+                            args.next();
+                        }
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        } else {
+            return Err(refspec_error(arg));
+        }
+    }
+    Ok(())
+}
+
+fn refspec_error(refspec: &str) -> miette::Report {
+    miette!(
+        help = "Give the remote with `git upstream REMOTE` and the branch with `--branch`",
+        "Can't pass `{refspec}` to `git push`, because `git-upstream` already passes a remote and \
+         a branch"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_args() {
+        let cases: &[(&[&str], bool)] = &[
+            (&[], true),
+            (&["--no-verify", "--force-if-includes"], true),
+            (&["--push-option", "ci.skip"], true),
+            (&["--push-option=ci.skip"], true),
+            (&["-o", "ci.skip"], true),
+            (&["-fo", "ci.skip"], true),
+            (&["-oci.skip"], true),
+            // These take the next argument as a value, so it isn't a refspec.
+            (&["--push-option", "main"], true),
+            (&["-o", "main"], true),
+            (&["--receive-pack", "git-receive-pack"], true),
+            (&["main"], false),
+            (&["HEAD:refs/heads/main"], false),
+            (&["--", "main"], false),
+            (&["--"], true),
+            (&["--delete"], false),
+            (&["--mirror"], false),
+            (&["--all"], false),
+            (&["--branches"], false),
+            (&["-d"], false),
+            (&["-fd"], false),
+            (&["--force-with-lease=main:abc"], true),
+        ];
+        for (args, ok) in cases {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            assert_eq!(check(&args).is_ok(), *ok, "{args:?}");
+        }
+    }
+}
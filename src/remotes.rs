@@ -0,0 +1,322 @@
+//! `git upstream remotes`, for showing which remotes are tried and in what order.
+
+use miette::IntoDiagnostic;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stdout;
+use owo_colors::Style;
+use serde::Serialize;
+use std::fmt::Display;
+
+use crate::json;
+use crate::remote_url::RemoteUrl;
+use crate::Config;
+
+/// List remotes and the order they're tried in.
+#[derive(Debug, Clone, clap::Args)]
+pub struct RemotesArgs {
+    /// Print the remotes as JSON instead of a list.
+    #[arg(long)]
+    json: bool,
+}
+
+/// Where a remote's ranking came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RemoteSource {
+    /// The `REMOTE` argument.
+    CommandLine,
+    /// The `remotes` list in the configuration file.
+    ConfigFile,
+    /// `origin`, used when the configuration file doesn't list any remotes.
+    Default,
+    /// A remote which isn't listed anywhere, tried after the others.
+    Fallback,
+}
+
+impl Display for RemoteSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CommandLine => write!(f, "from the command line"),
+            Self::ConfigFile => write!(f, "from the configuration file"),
+            Self::Default => write!(f, "default"),
+            Self::Fallback => write!(f, "fallback"),
+        }
+    }
+}
+
+/// A remote, from `git remote -v`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    pub name: String,
+    /// The URL `git fetch` uses, after `url.<base>.insteadOf` rewriting.
+    pub fetch_url: Option<String>,
+    /// The URLs `git push` pushes to, from `remote.<name>.pushurl` or the fetch URL, after
+    /// `url.<base>.pushInsteadOf` rewriting. `git push` pushes to all of them.
+    pub push_urls: Vec<String>,
+    /// The first push URL, parsed, or `None` if it doesn't have a host, like a local path.
+    pub resolved_push_url: Option<RemoteUrl>,
+}
+
+impl Remote {
+    /// The first push URL, like `git remote get-url --push`.
+    pub fn push_url(&self) -> Option<&str> {
+        self.push_urls.first().map(String::as_str)
+    }
+
+    /// Is the push URL a placeholder like `no_push`, so that pushing will always fail?
+    pub fn push_disabled(&self) -> bool {
+        self.push_url().is_some_and(is_disabled_url)
+    }
+}
+
+impl Display for Remote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Parse `git remote -v`, which prints lines like `origin\tURL (fetch)` and `origin\tURL (push)`,
+/// with a `(push)` line for each push URL. The remotes are kept in the order they're listed in.
+pub fn parse_remotes(output: &str) -> Vec<Remote> {
+    let mut remotes = Vec::<Remote>::new();
+    for line in output.lines() {
+        let Some((name, rest)) = line.split_once('\t') else {
+            continue;
+        };
+        let index = match remotes.iter().position(|remote| remote.name == name) {
+            Some(index) => index,
+            None => {
+                remotes.push(Remote {
+                    name: name.to_owned(),
+                    fetch_url: None,
+                    push_urls: Vec::new(),
+                    resolved_push_url: None,
+                });
+                remotes.len() - 1
+            }
+        };
+        let remote = &mut remotes[index];
+        if let Some(url) = rest.strip_suffix(" (fetch)") {
+            remote.fetch_url = Some(url.to_owned());
+        } else if let Some(url) = rest.strip_suffix(" (push)") {
+            remote.push_urls.push(url.to_owned());
+        }
+    }
+    for remote in &mut remotes {
+        remote.resolved_push_url = remote.push_url().and_then(RemoteUrl::parse);
+    }
+    remotes
+}
+
+/// A remote and how `git-upstream` ranks it.
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteInfo {
+    pub name: String,
+    /// The position the remote is tried in, starting at 1, or `None` if it doesn't exist.
+    pub rank: Option<usize>,
+    pub source: RemoteSource,
+    pub fetch_url: Option<String>,
+    pub push_url: Option<String>,
+    /// Is the push URL a placeholder like `no_push`, so that pushing will always fail?
+    pub push_disabled: bool,
+}
+
+impl RemoteInfo {
+    /// List every remote in the order they're tried in, followed by preferred remotes which
+    /// don't exist.
+    pub fn list(config: &Config) -> miette::Result<Vec<Self>> {
+        let mut remotes = config.list_remotes()?;
+        let mut ranked = Vec::new();
+        let mut missing = Vec::new();
+
+        for (remote, source) in config.remote_preference_sources() {
+            if let Some(index) = remotes.iter().position(|other| other.name == remote) {
+                ranked.push((remotes.remove(index), source));
+            } else if !ranked.iter().any(|(other, _)| other.name == remote)
+                && !missing.iter().any(|(name, _)| *name == remote)
+            {
+                missing.push((remote, source));
+            }
+        }
+        // This matches the order in `Config::candidate_remotes`.
+        ranked.extend(
+            remotes
+                .into_iter()
+                .map(|remote| (remote, RemoteSource::Fallback)),
+        );
+
+        let mut ret = Vec::new();
+        for (i, (remote, source)) in ranked.into_iter().enumerate() {
+            ret.push(Self {
+                rank: Some(i + 1),
+                source,
+                push_url: remote.push_url().map(ToOwned::to_owned),
+                push_disabled: remote.push_disabled(),
+                name: remote.name,
+                fetch_url: remote.fetch_url,
+            });
+        }
+        ret.extend(missing.into_iter().map(|(name, source)| Self {
+            name,
+            rank: None,
+            source,
+            fetch_url: None,
+            push_url: None,
+            push_disabled: false,
+        }));
+
+        Ok(ret)
+    }
+}
+
+/// Run `git upstream remotes`.
+pub fn remotes(config: &Config, args: &RemotesArgs) -> miette::Result<()> {
+    let remotes = RemoteInfo::list(config)?;
+
+    if args.json {
+        println!("{}", json::to_string_pretty(&remotes).into_diagnostic()?);
+        return Ok(());
+    }
+
+    let mut printed_fallback_header = false;
+    let mut printed_missing_header = false;
+    for remote in &remotes {
+        if remote.source == RemoteSource::Fallback
+            && !std::mem::replace(&mut printed_fallback_header, true)
+        {
+            println!(
+                "{}",
+                "Not listed in the configuration file, tried in this order:"
+                    .if_supports_color(Stdout, |text| Style::new().dimmed().style(text))
+            );
+        }
+        if remote.rank.is_none() && !std::mem::replace(&mut printed_missing_header, true) {
+            println!(
+                "{}",
+                "Listed but not found:"
+                    .if_supports_color(Stdout, |text| Style::new().dimmed().style(text))
+            );
+        }
+
+        let rank = match remote.rank {
+            Some(rank) => format!("{rank}."),
+            None => "-".to_owned(),
+        };
+        println!(
+            "{rank} {} ({})",
+            remote
+                .name
+                .if_supports_color(Stdout, |text| Style::new().bold().style(text)),
+            remote.source,
+        );
+
+        if let Some(url) = &remote.fetch_url {
+            println!("     fetch: {url}");
+        }
+        if let Some(url) = &remote.push_url {
+            if remote.push_disabled {
+                println!(
+                    "     push:  {url} {}",
+                    "(disabled push URL)"
+                        .if_supports_color(Stdout, |text| Style::new().yellow().style(text))
+                );
+            } else if remote.fetch_url.as_ref() != Some(url) {
+                println!("     push:  {url}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A common way to disable pushing to a remote is to set its push URL to a placeholder like
+/// `no_push` or `DISABLE`, which isn't a path or URL.
+fn is_disabled_url(url: &str) -> bool {
+    !url.contains(['/', ':', '\\'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_remotes_several_push_urls() {
+        let remotes = parse_remotes(
+            "origin\tgit@github.com:me/repo.git (fetch)\n\
+             origin\tgit@github.com:me/repo.git (push)\n\
+             origin\tgit@gitlab.com:me/repo.git (push)\n",
+        );
+        assert_eq!(remotes.len(), 1);
+        let origin = &remotes[0];
+        assert_eq!(origin.name, "origin");
+        assert_eq!(
+            origin.fetch_url.as_deref(),
+            Some("git@github.com:me/repo.git")
+        );
+        assert_eq!(
+            origin.push_urls,
+            ["git@github.com:me/repo.git", "git@gitlab.com:me/repo.git"]
+        );
+        assert_eq!(origin.push_url(), Some("git@github.com:me/repo.git"));
+        assert_eq!(
+            origin
+                .resolved_push_url
+                .as_ref()
+                .map(|url| url.host.as_str()),
+            Some("github.com")
+        );
+    }
+
+    #[test]
+    fn parse_remotes_windows_path_with_spaces() {
+        let remotes = parse_remotes(
+            "origin\tC:\\Users\\Jo Smith\\my repo.git (fetch)\n\
+             origin\tC:\\Users\\Jo Smith\\my repo.git (push)\n",
+        );
+        assert_eq!(remotes.len(), 1);
+        assert_eq!(
+            remotes[0].fetch_url.as_deref(),
+            Some("C:\\Users\\Jo Smith\\my repo.git")
+        );
+        assert_eq!(remotes[0].push_urls, ["C:\\Users\\Jo Smith\\my repo.git"]);
+        // A local path, not `host:path`.
+        assert_eq!(remotes[0].resolved_push_url, None);
+    }
+
+    #[test]
+    fn parse_remotes_rewritten_urls() {
+        // With `url.git@github.com:.pushInsteadOf=https://github.com/`, `git remote -v` shows
+        // the rewritten push URL, which may differ from the fetch URL.
+        let remotes = parse_remotes(
+            "upstream\thttps://github.com/owner/repo (fetch)\n\
+             upstream\tgit@github.com:owner/repo (push)\n",
+        );
+        let upstream = &remotes[0];
+        assert_eq!(
+            upstream.fetch_url.as_deref(),
+            Some("https://github.com/owner/repo")
+        );
+        assert_eq!(upstream.push_url(), Some("git@github.com:owner/repo"));
+        let url = upstream.resolved_push_url.as_ref().unwrap();
+        assert_eq!(url.scheme, None);
+        assert_eq!(url.user.as_deref(), Some("git"));
+        assert_eq!(url.host, "github.com");
+        assert_eq!(url.segments, ["owner", "repo"]);
+    }
+
+    #[test]
+    fn parse_remotes_keeps_order() {
+        let remotes = parse_remotes(
+            "fork\thttps://example.com/fork.git (fetch)\n\
+             fork\thttps://example.com/fork.git (push)\n\
+             origin\thttps://example.com/origin.git (fetch)\n\
+             origin\tno_push (push)\n\
+             upstream\thttps://example.com/upstream.git (fetch)\n\
+             upstream\thttps://example.com/upstream.git (push)\n",
+        );
+        let names: Vec<_> = remotes.iter().map(|remote| remote.name.as_str()).collect();
+        assert_eq!(names, ["fork", "origin", "upstream"]);
+        assert!(remotes[1].push_disabled());
+        assert!(!remotes[0].push_disabled());
+    }
+}
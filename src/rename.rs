@@ -0,0 +1,100 @@
+//! `--rename` mode, for migrating the upstream of a renamed branch.
+
+use command_error::CommandExt;
+use miette::miette;
+use miette::IntoDiagnostic;
+
+use crate::git;
+use crate::Config;
+
+/// Push `branch`, delete `old` from the remote that hosted it, and clean up leftover
+/// `branch.OLD.*` configuration.
+pub fn rename(config: &Config, branch: &str, old: &str) -> miette::Result<()> {
+    if branch == old {
+        return Err(miette!(
+            "Can't rename {old} to itself; check out the renamed branch or pass `--branch`"
+        ));
+    }
+
+    let stale_config = git::config_get(&format!("branch.{old}.remote"))?.is_some()
+        || git::config_get(&format!("branch.{old}.merge"))?.is_some();
+
+    // Look this up before pushing, because pushing with `--set-upstream` overwrites the
+    // configuration we read it from.
+    let old_remote = previous_remote(config, branch, old)?;
+
+    config.push(branch)?;
+
+    match old_remote {
+        Some(old_remote) => delete_old(config, branch, old, &old_remote)?,
+        None => {
+            tracing::warn!("Couldn't find a remote hosting {old}; not deleting it");
+        }
+    }
+
+    if stale_config {
+        tracing::debug!("Removing leftover `branch.{old}` configuration");
+        git::command()
+            .args(["config", "--remove-section", &format!("branch.{old}")])
+            .status_checked()
+            .into_diagnostic()?;
+    }
+
+    Ok(())
+}
+
+/// Find the remote `old` was pushed to before the rename.
+fn previous_remote(config: &Config, branch: &str, old: &str) -> miette::Result<Option<String>> {
+    if let Some(remote) = git::config_get(&format!("branch.{old}.remote"))? {
+        return Ok(Some(remote));
+    }
+
+    // `git branch -m` moves `branch.OLD.*` to `branch.NEW.*`, so the new branch may still be
+    // tracking the old remote branch.
+    let merge = git::config_get(&format!("branch.{branch}.merge"))?;
+    if merge.as_deref() == Some(&format!("refs/heads/{old}")) {
+        if let Some(remote) = git::config_get(&format!("branch.{branch}.remote"))? {
+            return Ok(Some(remote));
+        }
+    }
+
+    for remote in config.candidate_remotes()? {
+        if git::rev_parse(&format!("refs/remotes/{remote}/{old}"))?.is_some() {
+            return Ok(Some(remote));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Delete `old` from `remote`, unless it has commits which `branch` doesn't.
+fn delete_old(config: &Config, branch: &str, old: &str, remote: &str) -> miette::Result<()> {
+    if !config.cli.force {
+        match git::rev_parse(&format!("refs/remotes/{remote}/{old}"))? {
+            None => {
+                tracing::warn!(
+                    "Can't tell if {remote}/{old} is merged into {branch} because its \
+                     remote-tracking ref is missing; not deleting it (use `--force` to delete it \
+                     anyway)"
+                );
+                return Ok(());
+            }
+            Some(old_tip) => {
+                if !git::is_ancestor(&old_tip, &format!("refs/heads/{branch}"))? {
+                    tracing::warn!(
+                        "{remote}/{old} has commits which aren't in {branch}; not deleting it \
+                         (use `--force` to delete it anyway)"
+                    );
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    let mut command = git::command();
+    command.args(["push", remote, "--delete", old]);
+    git::log_command(&command);
+    command.status_checked().into_diagnostic()?;
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+//! `git upstream rename-remote`, for renaming a remote and everything that refers to it.
+
+use std::collections::BTreeSet;
+
+use command_error::CommandExt;
+use command_error::OutputContext;
+use miette::miette;
+use miette::IntoDiagnostic;
+use utf8_command::Utf8Output;
+
+use crate::git;
+use crate::journal;
+use crate::journal::Journal;
+use crate::Config;
+use crate::ConfigFile;
+
+/// Rename a remote, like `git remote rename`, and update branches which refer to it.
+#[derive(Debug, Clone, clap::Args)]
+pub struct RenameRemoteArgs {
+    /// The remote to rename.
+    old: String,
+
+    /// The new name for the remote.
+    new: String,
+
+    /// Print what would be changed without changing anything.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Run `git upstream rename-remote`.
+pub fn rename_remote(config: &Config, args: &RenameRemoteArgs) -> miette::Result<()> {
+    let RenameRemoteArgs { old, new, dry_run } = args;
+
+    config.require_remote(old)?;
+    if config.has_remote(new)? {
+        return Err(miette!(
+            "Refusing to rename {old} to {new} because a remote named {new} already exists"
+        ));
+    }
+
+    let keys = keys_referring_to(old)?;
+    let branches = keys
+        .iter()
+        .filter_map(|key| key.strip_prefix("branch."))
+        .filter_map(|key| key.rsplit_once('.'))
+        .map(|(branch, _)| branch)
+        .collect::<BTreeSet<_>>()
+        .len();
+
+    let mut command = git::command();
+    command.args(["remote", "rename", old, new]);
+    if *dry_run {
+        tracing::info!("Would run: git remote rename {old} {new}");
+        for key in &keys {
+            tracing::info!("Would set {key} to {new}");
+        }
+    } else {
+        git::log_command(&command);
+        command.status_checked().into_diagnostic()?;
+
+        // `git remote rename` updates `branch.<name>.remote`, but not `pushRemote` or
+        // `remote.pushDefault`.
+        for key in keys_referring_to(old)? {
+            git::command()
+                .args(["config", &key, new])
+                .output_checked_utf8()
+                .into_diagnostic()?;
+        }
+        let noun = if branches == 1 { "branch" } else { "branches" };
+        tracing::info!("Renamed {old} to {new} and updated {branches} {noun} which used it");
+
+        update_journal(config, old, new)?;
+    }
+
+    for path in config.config_paths()? {
+        if ConfigFile::load(&path)?.remotes.contains(old) {
+            tracing::warn!(
+                "{} lists {old} in `remotes`; change it to {new}",
+                path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Find the Git configuration keys which refer to `remote`, like `branch.<name>.remote`,
+/// `branch.<name>.pushRemote`, and `remote.pushDefault`.
+fn keys_referring_to(remote: &str) -> miette::Result<Vec<String>> {
+    let output = git::command()
+        .args([
+            "config",
+            "--get-regexp",
+            r"^(branch\..*\.(remote|pushremote)|remote\.pushdefault)$",
+        ])
+        .output_checked_as(|context: OutputContext<Utf8Output>| {
+            if context.status().success() {
+                Ok(context.output().stdout.clone())
+            } else if context.status().code() == Some(1) {
+                // Nothing matched.
+                Ok(String::new())
+            } else {
+                Err(context.error())
+            }
+        })
+        .into_diagnostic()?;
+
+    Ok(output
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .filter(|(_, value)| *value == remote)
+        .map(|(key, _)| key.to_owned())
+        .collect())
+}
+
+/// Update the push journal, so that `git upstream undo` pushes to the renamed remote.
+fn update_journal(config: &Config, old: &str, new: &str) -> miette::Result<()> {
+    let repo = journal::repo()?;
+    let mut journal = Journal::load(config)?;
+    let mut changed = false;
+    for record in journal
+        .pushes
+        .iter_mut()
+        .filter(|record| record.repo == repo)
+    {
+        if record.remote == old {
+            record.remote = new.to_owned();
+            changed = true;
+        }
+        if record.previous_remote.as_deref() == Some(old) {
+            record.previous_remote = Some(new.to_owned());
+            changed = true;
+        }
+    }
+    if changed {
+        journal.save(config)?;
+    }
+    Ok(())
+}
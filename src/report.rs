@@ -0,0 +1,420 @@
+//! Structured results of pushing a branch, for `--json`.
+
+use std::fmt::Display;
+
+use miette::Diagnostic;
+use miette::IntoDiagnostic;
+use serde::Serialize;
+
+use crate::exit_code::Exit;
+use crate::failure;
+use crate::failure::FailureKind;
+use crate::fork;
+use crate::json;
+use crate::porcelain::RefUpdate;
+use crate::remotes::Remote;
+use crate::remotes::RemoteSource;
+use crate::Config;
+
+/// How `--json` output is formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum JsonFormat {
+    /// A single JSON document, printed when the run finishes.
+    Document,
+    /// One JSON event per line, printed as the run progresses.
+    Stream,
+}
+
+/// A remote to try pushing to, and why it was ranked where it was.
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    /// Serialized as the remote's name.
+    #[serde(serialize_with = "serialize_name")]
+    pub remote: Remote,
+    pub source: RemoteSource,
+}
+
+/// The remotes to try pushing a branch to, from [`Config::plan`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PushPlan {
+    pub branch: String,
+    /// The remotes to try, in order.
+    pub candidates: Vec<Candidate>,
+}
+
+fn serialize_name<S: serde::Serializer>(remote: &Remote, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&remote.name)
+}
+
+/// What happened when pushing to a remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AttemptResult {
+    Pushed,
+    /// The branch already matched the remote, so nothing was pushed.
+    UpToDate,
+    Failed,
+}
+
+/// An attempt to push a branch to a remote.
+#[derive(Debug, Clone, Serialize)]
+pub struct Attempt {
+    pub remote: String,
+    pub result: AttemptResult,
+    /// How long the attempt took, in milliseconds.
+    pub duration_ms: u64,
+    /// The exit code of `git push`, if it was run.
+    pub exit_code: Option<i32>,
+    /// Why the attempt failed, if it did.
+    pub failure: Option<FailureKind>,
+    /// A one-line summary of the failure, from `git push`'s stderr.
+    pub message: Option<String>,
+    /// What `git push` printed, with progress updates collapsed.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub output: String,
+    /// The refs `git push --porcelain` reported updating.
+    pub ref_updates: Vec<RefUpdate>,
+    /// How many times this remote had already been retried, after network errors.
+    pub retry: u32,
+    /// How to fix the failure, if we know.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+}
+
+impl Attempt {
+    pub fn succeeded(&self) -> bool {
+        self.result != AttemptResult::Failed
+    }
+}
+
+impl Display for Attempt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.remote)?;
+        match (&self.message, self.failure, self.exit_code) {
+            (Some(message), _, _) => write!(f, "{message}")?,
+            (None, Some(failure), _) => write!(f, "{failure}")?,
+            (None, None, Some(code)) => write!(f, "`git push` exited with code {code}")?,
+            (None, None, None) => write!(f, "failed")?,
+        }
+        write!(f, " (after {})", format_duration(self.duration_ms))
+    }
+}
+
+impl std::error::Error for Attempt {}
+
+impl Diagnostic for Attempt {
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.help
+            .as_ref()
+            .map(|help| Box::new(help) as Box<dyn Display>)
+    }
+}
+
+/// The result of pushing a branch.
+#[derive(Debug, Clone, Serialize)]
+pub struct Outcome {
+    pub success: bool,
+    /// Whether the branch was already up to date, so nothing was pushed.
+    pub up_to_date: bool,
+    /// The remote the branch was pushed to.
+    pub remote: Option<String>,
+    /// The ref the branch was pushed to on the remote, like `refs/heads/main`.
+    pub remote_ref: Option<String>,
+}
+
+/// Everything that happened when pushing a branch.
+#[derive(Debug, Clone, Serialize)]
+pub struct PushReport {
+    pub branch: String,
+    /// Whether this was a `--dry-run`, so nothing was actually pushed.
+    pub dry_run: bool,
+    /// The remotes to try, in order.
+    pub candidates: Vec<Candidate>,
+    pub attempts: Vec<Attempt>,
+    pub outcome: Outcome,
+    /// How long reading the repository's remotes, refs, and configuration took, in
+    /// milliseconds.
+    pub metadata_ms: u64,
+    /// How long the whole run took, in milliseconds.
+    pub duration_ms: u64,
+}
+
+impl PushReport {
+    /// Log how long reading the repository and each attempt took, and the total, like
+    /// `origin: failed after 32.4s`. With `info`, these are logged at the info level instead of
+    /// the debug level.
+    pub fn log_timings(&self, info: bool) {
+        let lines = [format!("metadata: {}", format_duration(self.metadata_ms))]
+            .into_iter()
+            .chain(self.attempts.iter().map(|attempt| {
+                let duration = format_duration(attempt.duration_ms);
+                let remote = match attempt.retry {
+                    0 => attempt.remote.clone(),
+                    retry => format!("{} (retry {retry})", attempt.remote),
+                };
+                match attempt.result {
+                    AttemptResult::Pushed => format!("{remote}: ok in {duration}"),
+                    AttemptResult::UpToDate => format!("{remote}: up to date in {duration}"),
+                    AttemptResult::Failed => format!("{remote}: failed after {duration}"),
+                }
+            }))
+            .chain([format!("total: {}", format_duration(self.duration_ms))]);
+        for line in lines {
+            if info {
+                tracing::info!("{line}");
+            } else {
+                tracing::debug!("{line}");
+            }
+        }
+    }
+}
+
+/// Format a duration in milliseconds for people, like `850ms`, `32.4s`, or `2m05s`.
+pub fn format_duration(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{ms}ms")
+    } else if ms < 60_000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        let seconds = ms / 1000;
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    }
+}
+
+/// How many lines of a failed `pre-push` hook's output to show again in the error.
+const HOOK_OUTPUT_LINES: usize = 10;
+
+/// An error for when every attempt to push a branch failed, with each attempt as a related
+/// diagnostic.
+#[derive(Debug)]
+pub struct PushFailed {
+    message: String,
+    help: Option<String>,
+    attempts: Vec<Attempt>,
+}
+
+impl PushFailed {
+    pub fn new(config: &Config, report: &PushReport) -> Self {
+        let branch = &report.branch;
+        let fail_fast = config.cli.fail_fast;
+        // Don't hide the server's message; it's still in the related attempt.
+        let denied = report
+            .attempts
+            .iter()
+            .find(|attempt| attempt.failure == Some(FailureKind::PermissionDenied));
+        let mut help = denied
+            .and_then(|attempt| fork::permission_help(config, &attempt.remote, &attempt.output));
+        let message = match report.attempts.as_slice() {
+            [.., attempt] if attempt.failure == Some(FailureKind::IfIncludes) => {
+                let remote = &attempt.remote;
+                help = Some(format!(
+                    "Integrate them with `git pull --rebase {remote} {branch}` (or look at them \
+                     with `git log {branch}..{remote}/{branch}`), then force-push again"
+                ));
+                format!(
+                    "{remote}/{branch} has commits which were fetched but never integrated into \
+                     {branch}, so `--force-if-includes` blocked the push"
+                )
+            }
+            [.., attempt] if attempt.failure == Some(FailureKind::NonFastForward) => {
+                let remote = &attempt.remote;
+                if let Some(expect) = &config.cli.expect {
+                    help = Some(format!(
+                        "Someone else may have pushed to it; check with `git fetch {remote}` and \
+                         `git log {remote}/{branch}`"
+                    ));
+                    format!(
+                        "{remote}/{branch} doesn't point to `{expect}`, so {remote} rejected the \
+                         push"
+                    )
+                } else {
+                    help = Some(format!(
+                        "Integrate the remote changes with `git pull --rebase {remote} {branch}`, \
+                         or overwrite them with `git upstream --force {remote}` (which uses \
+                         `--force-with-lease`)"
+                    ));
+                    format!(
+                        "{remote}/{branch} has commits which {branch} doesn't, so {remote} \
+                         rejected the push"
+                    )
+                }
+            }
+            // Git reports refs after talking to the remote, so without any, our own `pre-push`
+            // hook failed rather than the remote's hooks.
+            [.., attempt]
+                if attempt.failure == Some(FailureKind::Hook) && attempt.ref_updates.is_empty() =>
+            {
+                let tail = failure::tail(&attempt.output, HOOK_OUTPUT_LINES);
+                help = Some(if tail.is_empty() {
+                    "Fix what the hook reported, or skip it with `git upstream --no-verify`"
+                        .to_owned()
+                } else {
+                    format!(
+                        "The hook printed:\n{}\nFix what it reported, or skip it with `git \
+                         upstream --no-verify`",
+                        tail.iter()
+                            .map(|line| format!("    {line}\n"))
+                            .collect::<String>()
+                    )
+                });
+                format!(
+                    "The `pre-push` hook failed, so {branch} wasn't pushed to {}",
+                    attempt.remote
+                )
+            }
+            [.., attempt] if attempt.failure == Some(FailureKind::Hook) => {
+                format!("{}'s hooks rejected {branch}", attempt.remote)
+            }
+            [.., attempt] if attempt.failure == Some(FailureKind::Gpg) => {
+                // Git only says signing failed; what went wrong is in the signing program's
+                // output.
+                let tail = failure::tail(&attempt.output, HOOK_OUTPUT_LINES);
+                help = Some(format!(
+                    "Git printed:\n{}\nCheck that `user.signingKey` and `gpg.program` are set up, \
+                     or push without `--signed`",
+                    tail.iter()
+                        .map(|line| format!("    {line}\n"))
+                        .collect::<String>()
+                ));
+                format!("Couldn't sign the push to {}", attempt.remote)
+            }
+            [.., attempt] if attempt.failure == Some(FailureKind::SignedUnsupported) => {
+                format!("{} doesn't support signed pushes", attempt.remote)
+            }
+            [.., attempt] if attempt.failure == Some(FailureKind::AtomicUnsupported) => {
+                format!("{} doesn't support atomic pushes", attempt.remote)
+            }
+            [.., attempt] if attempt.failure == Some(FailureKind::Shallow) => {
+                help = Some(
+                    "Fetch the rest of the history with `git fetch --unshallow`, or pass \
+                     `--unshallow` to fetch it and push again"
+                        .to_owned(),
+                );
+                format!(
+                    "This repository is a shallow clone, so {} can't accept {branch} without the \
+                     history it's missing",
+                    attempt.remote
+                )
+            }
+            [attempt] if attempt.failure == Some(FailureKind::PermissionDenied) => {
+                format!("You don't have permission to push to {}", attempt.remote)
+            }
+            [attempt] if fail_fast || attempt.failure.is_some_and(|kind| !kind.falls_through()) => {
+                format!("Failed to push {branch} to {}", attempt.remote)
+            }
+            [] => format!("Failed to upstream {branch} because there are no remotes to push to"),
+            _ => format!("Failed to upstream {branch} to any remote"),
+        };
+        Self {
+            message,
+            help,
+            attempts: report
+                .attempts
+                .iter()
+                .filter(|attempt| !attempt.succeeded())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Get the exit code for the failure. See [`Exit`].
+    pub fn exit(&self) -> Exit {
+        match self.attempts.as_slice() {
+            [] => Exit::NoRemotes,
+            [.., attempt]
+                if matches!(
+                    attempt.failure,
+                    Some(FailureKind::NonFastForward | FailureKind::IfIncludes)
+                ) =>
+            {
+                Exit::NonFastForward
+            }
+            attempts
+                if attempts.iter().all(|attempt| {
+                    matches!(
+                        attempt.failure,
+                        Some(
+                            FailureKind::Auth | FailureKind::PermissionDenied | FailureKind::SshKey
+                        )
+                    )
+                }) =>
+            {
+                Exit::Rejected
+            }
+            _ => Exit::Failure,
+        }
+    }
+}
+
+impl Display for PushFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for PushFailed {}
+
+impl Diagnostic for PushFailed {
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.help
+            .as_ref()
+            .map(|help| Box::new(help) as Box<dyn Display>)
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        Some(Box::new(
+            self.attempts
+                .iter()
+                .map(|attempt| attempt as &dyn Diagnostic),
+        ))
+    }
+}
+
+/// An event in `--json=stream` output.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+enum Event<'a> {
+    Start {
+        branch: &'a str,
+        candidates: &'a [Candidate],
+    },
+    Attempt(&'a Attempt),
+    Finish(&'a Outcome),
+}
+
+fn print_event(event: &Event<'_>) -> miette::Result<()> {
+    println!("{}", json::to_string(event).into_diagnostic()?);
+    Ok(())
+}
+
+/// Push `branch` like [`Config::push_outcome`], printing the results as JSON on stdout.
+pub fn push_json(config: &Config, branch: &str, format: JsonFormat) -> miette::Result<Outcome> {
+    let stream = format == JsonFormat::Stream;
+    let plan = config.plan(branch)?;
+
+    if stream {
+        print_event(&Event::Start {
+            branch,
+            candidates: &plan.candidates,
+        })?;
+    }
+
+    let report = config.push_plan(&plan, |attempt| {
+        if stream {
+            print_event(&Event::Attempt(attempt))?;
+        }
+        Ok(())
+    })?;
+
+    if stream {
+        print_event(&Event::Finish(&report.outcome))?;
+    } else {
+        println!("{}", json::to_string_pretty(&report).into_diagnostic()?);
+    }
+
+    if report.outcome.success {
+        Ok(report.outcome)
+    } else {
+        Err(PushFailed::new(config, &report).into())
+    }
+}
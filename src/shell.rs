@@ -0,0 +1,74 @@
+//! Quoting commands for POSIX shells, for `--show-command`.
+
+use std::borrow::Cow;
+use std::process::Command;
+
+/// Quote `arg` so that a POSIX shell reads it as a single word.
+///
+/// Arguments made of characters which are never special are left alone; anything else is wrapped
+/// in single quotes, which can't be escaped inside single quotes, so each `'` becomes `'\''`.
+pub fn quote(arg: &str) -> Cow<'_, str> {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_plain) {
+        Cow::Borrowed(arg)
+    } else {
+        Cow::Owned(format!("'{}'", arg.replace('\'', r"'\''")))
+    }
+}
+
+/// Format a command's program and arguments as a line which a POSIX shell runs as the same
+/// command.
+///
+/// Environment variables and the working directory aren't included.
+pub fn join(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| quote(&arg.to_string_lossy()).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARGS: &[&str] = &[
+        "push",
+        "--push-option=ci.skip",
+        "two words",
+        "it's",
+        "$HOME",
+        "`id`",
+        "",
+        "naïve-☃",
+        "a\nb",
+        "*",
+        "~user",
+    ];
+
+    #[test]
+    fn quotes() {
+        let mut command = Command::new("git");
+        command.args(ARGS);
+        assert_eq!(
+            join(&command),
+            "git push --push-option=ci.skip 'two words' 'it'\\''s' '$HOME' '`id`' '' \
+             'naïve-☃' 'a\nb' '*' '~user'"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shell_reads_the_same_args() {
+        let mut command = Command::new("printf");
+        command.arg("%s\\0").args(ARGS);
+        let output = Command::new("sh")
+            .args(["-c", &join(&command)])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let printed = String::from_utf8(output.stdout).unwrap();
+        let printed: Vec<&str> = printed.strip_suffix('\0').unwrap().split('\0').collect();
+        assert_eq!(printed, ARGS);
+    }
+}
@@ -0,0 +1,416 @@
+//! Reading the repository's remotes, refs, and configuration up front, so each check doesn't
+//! spawn its own `git`.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::Instant;
+
+use command_error::CommandExt;
+use miette::IntoDiagnostic;
+
+use crate::git;
+use crate::git::Upstream;
+use crate::remote_url::RemoteUrl;
+use crate::remotes::parse_remotes;
+use crate::remotes::Remote;
+
+/// The configuration sections the snapshot reads.
+const CONFIG_SECTIONS: &str = r"^(remote|branch|push|url|core)\.";
+
+/// The repository's remotes, refs, and configuration at one point in time, from three `git`
+/// invocations run in parallel.
+///
+/// Anything which changes the repository, like pushing, makes this stale; see
+/// [`crate::Config::refresh_snapshot`].
+#[derive(Debug, Clone, Default)]
+pub struct RepoSnapshot {
+    /// The remotes, in the order `git remote -v` lists them.
+    remotes: Vec<Remote>,
+    /// Local branches and remote-tracking refs, by full name.
+    refs: BTreeMap<String, Ref>,
+    /// Configuration entries, in the order Git reads them.
+    config: Vec<(String, String)>,
+    /// How long reading the snapshot took.
+    pub duration: Duration,
+    /// What remotes said their `HEAD` points to. See [`RepoSnapshot::remote_head`].
+    remote_heads: RemoteHeads,
+}
+
+/// The branches remotes' `HEAD`s point to, from `git ls-remote`, by remote name. `None` if the
+/// remote didn't say.
+///
+/// Pushing doesn't change them, so they're shared between snapshots; see
+/// [`RepoSnapshot::with_remote_heads`].
+pub type RemoteHeads = Arc<Mutex<BTreeMap<String, Option<String>>>>;
+
+/// A ref from `git for-each-ref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ref {
+    object: String,
+    /// What the ref points to, if it's a symbolic ref like `refs/remotes/origin/HEAD`.
+    symref: Option<String>,
+}
+
+impl RepoSnapshot {
+    /// Read a snapshot of the current repository.
+    pub fn read() -> miette::Result<Self> {
+        let start = Instant::now();
+        let run = |args: &[&str]| {
+            git::command()
+                .args(args)
+                .output_checked_utf8()
+                .into_diagnostic()
+                .map(|output| output.stdout)
+        };
+        let (remotes, refs, config) = std::thread::scope(|scope| {
+            let remotes = scope.spawn(|| run(&["remote", "-v"]));
+            let refs = scope.spawn(|| {
+                run(&[
+                    "for-each-ref",
+                    "--format=%(objectname) %(refname) %(symref)",
+                    "refs/heads",
+                    "refs/remotes",
+                ])
+            });
+            // This exits with code 1 if nothing matches.
+            let config = scope.spawn(|| {
+                git::command()
+                    .args(["config", "--null", "--get-regexp", CONFIG_SECTIONS])
+                    .output_checked_with_utf8(|output| {
+                        if output.status.success() || output.status.code() == Some(1) {
+                            Ok(())
+                        } else {
+                            Err(None::<String>)
+                        }
+                    })
+                    .into_diagnostic()
+                    .map(|output| output.stdout)
+            });
+            let join = |handle: std::thread::ScopedJoinHandle<'_, miette::Result<String>>| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(miette::miette!("Reading the repository panicked")))
+            };
+            (join(remotes), join(refs), join(config))
+        });
+
+        Ok(Self {
+            remotes: parse_remotes(&remotes?),
+            refs: parse_refs(&refs?),
+            config: parse_config(&config?),
+            duration: start.elapsed(),
+            remote_heads: RemoteHeads::default(),
+        })
+    }
+
+    /// Share `remote_heads` with other snapshots, so remotes are only asked once.
+    pub fn with_remote_heads(mut self, remote_heads: RemoteHeads) -> Self {
+        self.remote_heads = remote_heads;
+        self
+    }
+
+    /// Get the branch `remote`'s `HEAD` points to, from `ask` the first time and from the cache
+    /// after that.
+    pub fn remote_head(
+        &self,
+        remote: &str,
+        ask: impl FnOnce() -> Option<String>,
+    ) -> Option<String> {
+        let cached = self
+            .remote_heads
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(remote)
+            .cloned();
+        if let Some(head) = cached {
+            return head;
+        }
+        // Don't hold the lock while talking to the remote.
+        let head = ask();
+        self.remote_heads
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(remote.to_owned(), head.clone());
+        head
+    }
+
+    /// Add a remote at `url`, whose `refs/remotes/<name>/HEAD` points to `main`, for
+    /// [`crate::FakeGit`].
+    pub(crate) fn insert_remote(&mut self, name: &str, url: &str) {
+        self.remotes.push(Remote {
+            name: name.to_owned(),
+            fetch_url: Some(url.to_owned()),
+            push_urls: vec![url.to_owned()],
+            resolved_push_url: RemoteUrl::parse(url),
+        });
+        self.refs.insert(
+            format!("refs/remotes/{name}/HEAD"),
+            Ref {
+                object: String::new(),
+                symref: Some(format!("refs/remotes/{name}/main")),
+            },
+        );
+    }
+
+    /// The remotes, in the order Git lists them.
+    pub fn remotes(&self) -> &[Remote] {
+        &self.remotes
+    }
+
+    /// Get the remote named `name`.
+    pub fn remote(&self, name: &str) -> Option<&Remote> {
+        self.remotes.iter().find(|remote| remote.name == name)
+    }
+
+    /// Get the object a ref like `refs/heads/main` points to.
+    pub fn rev(&self, name: &str) -> Option<&str> {
+        self.refs.get(name).map(|entry| entry.object.as_str())
+    }
+
+    /// Get what a symbolic ref like `refs/remotes/origin/HEAD` points to.
+    pub fn symref(&self, name: &str) -> Option<&str> {
+        self.refs.get(name)?.symref.as_deref()
+    }
+
+    /// Get a configuration value like `git config --get`, which gives the last value for
+    /// multi-valued keys.
+    ///
+    /// Only the sections in [`CONFIG_SECTIONS`] are read.
+    pub fn config_get(&self, key: &str) -> Option<&str> {
+        self.config_get_all(key).last().copied()
+    }
+
+    /// Get a boolean configuration value like `git config --type=bool --get`.
+    ///
+    /// Returns `None` if the key isn't set, or if the value isn't a boolean Git understands.
+    pub fn config_get_bool(&self, key: &str) -> Option<bool> {
+        // A key without a value, like `[push] autoSetupRemote`, is true.
+        match self.config_get(key)?.to_ascii_lowercase().as_str() {
+            "" | "true" | "yes" | "on" | "1" => Some(true),
+            "false" | "no" | "off" | "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Get every value of a multi-valued configuration key, like `git config --get-all`.
+    pub fn config_get_all(&self, key: &str) -> Vec<&str> {
+        let key = normalize_key(key);
+        self.config
+            .iter()
+            .filter(|(name, _)| *name == key)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Get the upstream configured for `branch`, like [`Upstream::of`].
+    pub fn upstream(&self, branch: &str) -> Option<Upstream> {
+        let remote = self.config_get(&format!("branch.{branch}.remote"))?;
+        let merge = self.config_get(&format!("branch.{branch}.merge"))?;
+        Some(Upstream {
+            remote: remote.to_owned(),
+            branch: merge
+                .strip_prefix("refs/heads/")
+                .unwrap_or(merge)
+                .to_owned(),
+        })
+    }
+}
+
+/// Parse `git for-each-ref --format='%(objectname) %(refname) %(symref)'`.
+fn parse_refs(output: &str) -> BTreeMap<String, Ref> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ' ');
+            let object = parts.next()?;
+            let name = parts.next()?;
+            let symref = parts.next().filter(|symref| !symref.is_empty());
+            Some((
+                name.to_owned(),
+                Ref {
+                    object: object.to_owned(),
+                    symref: symref.map(ToOwned::to_owned),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Parse `git config --null --get-regexp`, which prints `KEY\nVALUE\0` for each entry, or just
+/// `KEY\0` for keys without a value.
+///
+/// Keys are printed with the section and variable names lowercased, like
+/// `branch.Feature.remote`.
+pub fn parse_config(output: &str) -> Vec<(String, String)> {
+    output
+        .split('\0')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('\n') {
+            Some((key, value)) => (key.to_owned(), value.to_owned()),
+            None => (entry.to_owned(), String::new()),
+        })
+        .collect()
+}
+
+/// Lowercase the section and variable names of `key`, but not the subsection, like Git does.
+fn normalize_key(key: &str) -> String {
+    match (key.split_once('.'), key.rsplit_once('.')) {
+        (Some((section, _)), Some((rest, variable))) if rest.len() > section.len() => format!(
+            "{}{}.{}",
+            section.to_ascii_lowercase(),
+            &rest[section.len()..],
+            variable.to_ascii_lowercase()
+        ),
+        _ => key.to_ascii_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn remote_head_is_cached_between_snapshots() {
+        let heads = RemoteHeads::default();
+        let asked = Cell::new(0);
+        let ask = || {
+            asked.set(asked.get() + 1);
+            Some("main".to_owned())
+        };
+
+        let first = RepoSnapshot::default().with_remote_heads(Arc::clone(&heads));
+        assert_eq!(first.remote_head("origin", ask), Some("main".to_owned()));
+        let second = RepoSnapshot::default().with_remote_heads(heads);
+        assert_eq!(second.remote_head("origin", ask), Some("main".to_owned()));
+        assert_eq!(asked.get(), 1);
+
+        // Remotes which don't say are remembered too.
+        assert_eq!(second.remote_head("fork", || None), None);
+        assert_eq!(second.remote_head("fork", ask), None);
+        assert_eq!(asked.get(), 1);
+    }
+
+    /// A ref's name, object, and symbolic ref target.
+    type RefRow = (&'static str, &'static str, Option<&'static str>);
+
+    #[test]
+    fn refs() {
+        let cases: &[(&str, &[RefRow])] = &[
+            ("", &[]),
+            (
+                "abc123 refs/heads/main \n",
+                &[("refs/heads/main", "abc123", None)],
+            ),
+            (
+                "abc123 refs/heads/Foo/Bar \n\
+                 def456 refs/remotes/origin/HEAD refs/remotes/origin/main\n\
+                 def456 refs/remotes/origin/main \n",
+                &[
+                    ("refs/heads/Foo/Bar", "abc123", None),
+                    (
+                        "refs/remotes/origin/HEAD",
+                        "def456",
+                        Some("refs/remotes/origin/main"),
+                    ),
+                    ("refs/remotes/origin/main", "def456", None),
+                ],
+            ),
+            // A line without a ref name is skipped.
+            ("abc123\n", &[]),
+        ];
+        for (output, expected) in cases {
+            let expected: BTreeMap<_, _> = expected
+                .iter()
+                .map(|(name, object, symref)| {
+                    (
+                        name.to_string(),
+                        Ref {
+                            object: object.to_string(),
+                            symref: symref.map(ToOwned::to_owned),
+                        },
+                    )
+                })
+                .collect();
+            assert_eq!(parse_refs(output), expected, "{output:?}");
+        }
+    }
+
+    #[test]
+    fn config() {
+        let cases: &[(&str, &[(&str, &str)])] = &[
+            ("", &[]),
+            ("push.autosetupremote\0", &[("push.autosetupremote", "")]),
+            (
+                "branch.Foo/Bar.remote\norigin\0branch.Foo/Bar.merge\nrefs/heads/Foo/Bar\0",
+                &[
+                    ("branch.Foo/Bar.remote", "origin"),
+                    ("branch.Foo/Bar.merge", "refs/heads/Foo/Bar"),
+                ],
+            ),
+            (
+                "remote.origin.pushurl\na\0remote.origin.pushurl\nb\0",
+                &[
+                    ("remote.origin.pushurl", "a"),
+                    ("remote.origin.pushurl", "b"),
+                ],
+            ),
+            // Only the first newline separates the key from the value.
+            (
+                "url.git@example.com:.insteadof\nhttps://x.test/a=b c\nd\0",
+                &[("url.git@example.com:.insteadof", "https://x.test/a=b c\nd")],
+            ),
+        ];
+        for (output, expected) in cases {
+            let expected: Vec<_> = expected
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            assert_eq!(parse_config(output), expected, "{output:?}");
+        }
+    }
+
+    #[test]
+    fn normalize_keys() {
+        let cases = [
+            ("push.autoSetupRemote", "push.autosetupremote"),
+            ("Branch.Foo/Bar.Remote", "branch.Foo/Bar.remote"),
+            (
+                "url.git@Example.com:Org/.insteadOf",
+                "url.git@Example.com:Org/.insteadof",
+            ),
+            ("core", "core"),
+            ("", ""),
+        ];
+        for (key, expected) in cases {
+            assert_eq!(normalize_key(key), expected, "{key:?}");
+        }
+    }
+
+    #[test]
+    fn config_lookups() {
+        let snapshot = RepoSnapshot {
+            config: parse_config(
+                "branch.Foo/Bar.remote\nfork\0branch.Foo/Bar.merge\nrefs/heads/Foo/Bar\0\
+                 remote.origin.pushurl\na\0remote.origin.pushurl\nb\0push.autosetupremote\0",
+            ),
+            ..RepoSnapshot::default()
+        };
+        assert_eq!(
+            snapshot.upstream("Foo/Bar"),
+            Some(Upstream {
+                remote: "fork".to_owned(),
+                branch: "Foo/Bar".to_owned(),
+            })
+        );
+        assert_eq!(snapshot.upstream("foo/bar"), None);
+        assert_eq!(snapshot.config_get("remote.origin.pushURL"), Some("b"));
+        assert_eq!(snapshot.config_get_all("remote.origin.pushurl"), ["a", "b"]);
+        assert_eq!(snapshot.config_get_bool("push.autoSetupRemote"), Some(true));
+        assert_eq!(snapshot.config_get("remote.fork.url"), None);
+    }
+}
@@ -0,0 +1,131 @@
+//! `--stack` mode, for pushing a chain of stacked branches.
+
+use std::collections::BTreeMap;
+
+use command_error::CommandExt;
+use miette::miette;
+use miette::IntoDiagnostic;
+
+use crate::exit_code::NoRemotes;
+use crate::git;
+use crate::Config;
+
+/// Push every local branch between the remote's default branch and `HEAD`, oldest first.
+pub fn push_stack(config: &Config) -> miette::Result<()> {
+    let candidates = config.candidate_remotes()?;
+    let remote = candidates.first().cloned().ok_or(NoRemotes)?;
+
+    // The stack may be pushed to a fork which doesn't have the default branch, so check the
+    // other remotes too.
+    let mut found = None;
+    for candidate in &candidates {
+        if let Some(default_branch) = config.default_branch(candidate)? {
+            found = Some((candidate.clone(), default_branch));
+            break;
+        }
+    }
+    let (base_remote, default_branch) = found.ok_or_else(|| {
+        miette!(
+            help = format!("Try `git remote set-head {remote} --auto`"),
+            "Couldn't detect the default branch of any remote, so the bottom of the stack is \
+             unknown"
+        )
+    })?;
+    let base = format!("refs/remotes/{base_remote}/{}", default_branch.name);
+    tracing::debug!(%base, "Finding stacked branches");
+
+    let stack: Vec<_> = stacked_branches(&base)?
+        .into_iter()
+        .filter(|branch| match config.never_push_pattern(branch) {
+            Some(pattern) => {
+                tracing::debug!(%branch, %pattern, "Skipping branch matching `never-push`");
+                false
+            }
+            None => true,
+        })
+        .collect();
+    if stack.is_empty() {
+        return Err(miette!("No branches found between {base} and HEAD"));
+    }
+
+    let target = config.remote(&remote)?;
+    let mut results = Vec::new();
+    for branch in &stack {
+        let up_to_date = config.is_up_to_date(branch, &remote)?;
+        let pushed = up_to_date || {
+            let attempt = config.try_push(branch, &target)?;
+            if !attempt.succeeded() && config.cli.fail_fast {
+                return Err(miette!("Failed to push {branch} to {remote}"));
+            }
+            attempt.succeeded()
+        };
+        results.push((branch, up_to_date, pushed));
+    }
+
+    tracing::info!(
+        "Stack on {base_remote}/{}, pushed to {remote}, oldest first:",
+        default_branch.name
+    );
+    let mut failures = 0;
+    for (i, (branch, up_to_date, pushed)) in results.into_iter().enumerate() {
+        let status = if up_to_date {
+            "already up to date"
+        } else if pushed {
+            "pushed"
+        } else {
+            failures += 1;
+            "failed"
+        };
+        tracing::info!("  {}. {branch} ({status})", i + 1);
+    }
+
+    if failures > 0 {
+        Err(miette!(
+            "Failed to upstream {failures} of {} stacked branches",
+            stack.len()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Find the local branches pointing at commits on the first-parent history between `base` and
+/// `HEAD`, oldest first.
+fn stacked_branches(base: &str) -> miette::Result<Vec<String>> {
+    let mut branches_at: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let refs = git::command()
+        .args([
+            "for-each-ref",
+            "--format=%(objectname) %(refname:short)",
+            "refs/heads",
+        ])
+        .output_checked_utf8()
+        .into_diagnostic()?
+        .stdout;
+    for line in refs.lines() {
+        if let Some((commit, branch)) = line.split_once(' ') {
+            branches_at
+                .entry(commit.to_owned())
+                .or_default()
+                .push(branch.to_owned());
+        }
+    }
+
+    let commits = git::command()
+        .args([
+            "log",
+            "--first-parent",
+            "--reverse",
+            "--format=%H",
+            &format!("{base}..HEAD"),
+        ])
+        .output_checked_utf8()
+        .into_diagnostic()?
+        .stdout;
+
+    Ok(commits
+        .lines()
+        .filter_map(|commit| branches_at.remove(commit.trim()))
+        .flatten()
+        .collect())
+}
@@ -0,0 +1,173 @@
+//! `git upstream status`, for an overview of local branches and their upstreams.
+
+use command_error::CommandExt;
+use miette::IntoDiagnostic;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stdout;
+use owo_colors::Style;
+use serde::Serialize;
+
+use crate::git;
+use crate::json;
+use crate::prompt::confirm;
+use crate::Config;
+
+/// Show each local branch's upstream and whether it's ahead, behind, or gone.
+#[derive(Debug, Clone, clap::Args)]
+pub struct StatusArgs {
+    /// Print the status as JSON instead of a table.
+    #[arg(long)]
+    json: bool,
+
+    /// Offer to upstream each branch without an upstream.
+    #[arg(long, conflicts_with = "json")]
+    fix: bool,
+}
+
+/// The status of a local branch.
+#[derive(Debug, Clone, Serialize)]
+pub struct BranchStatus {
+    /// The branch name.
+    pub branch: String,
+    /// Is this the current branch?
+    pub current: bool,
+    /// The upstream, like `origin/main`.
+    pub upstream: Option<String>,
+    /// Commits on the branch which aren't on the upstream.
+    pub ahead: u32,
+    /// Commits on the upstream which aren't on the branch.
+    pub behind: u32,
+    /// Has the upstream been deleted?
+    pub gone: bool,
+}
+
+impl BranchStatus {
+    /// List the status of every local branch with a single `git for-each-ref`.
+    pub fn list() -> miette::Result<Vec<Self>> {
+        let output = git::command()
+            .args([
+                "for-each-ref",
+                "--format=%(HEAD)%00%(refname:short)%00%(upstream:short)%00%(upstream:track)",
+                "refs/heads",
+            ])
+            .output_checked_utf8()
+            .into_diagnostic()?;
+
+        Ok(output.stdout.lines().filter_map(Self::parse).collect())
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\0');
+        let head = fields.next()?;
+        let branch = fields.next()?;
+        let upstream = fields.next()?;
+        let track = fields.next()?;
+
+        let mut status = Self {
+            branch: branch.to_owned(),
+            current: head == "*",
+            upstream: (!upstream.is_empty()).then(|| upstream.to_owned()),
+            ahead: 0,
+            behind: 0,
+            gone: false,
+        };
+
+        // Looks like `[ahead 1, behind 2]` or `[gone]`.
+        let track = track.trim_start_matches('[').trim_end_matches(']');
+        for part in track.split(", ") {
+            if part == "gone" {
+                status.gone = true;
+            } else if let Some(count) = part.strip_prefix("ahead ") {
+                status.ahead = count.parse().ok()?;
+            } else if let Some(count) = part.strip_prefix("behind ") {
+                status.behind = count.parse().ok()?;
+            }
+        }
+
+        Some(status)
+    }
+}
+
+/// Run `git upstream status`.
+pub fn status(config: &Config, args: &StatusArgs) -> miette::Result<()> {
+    let statuses = BranchStatus::list()?;
+
+    if args.json {
+        println!("{}", json::to_string_pretty(&statuses).into_diagnostic()?);
+        return Ok(());
+    }
+
+    print_table(&statuses);
+
+    if args.fix {
+        for status in statuses.iter().filter(|status| status.upstream.is_none()) {
+            let branch = &status.branch;
+            if config.cli.yes || confirm(&format!("Upstream {branch}?"), "--yes")? {
+                config.push(branch)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_table(statuses: &[BranchStatus]) {
+    let branch_width = statuses
+        .iter()
+        .map(|status| status.branch.chars().count())
+        .max()
+        .unwrap_or(0);
+    let upstream_width = statuses
+        .iter()
+        .map(|status| {
+            status
+                .upstream
+                .as_ref()
+                .map(|upstream| upstream.chars().count())
+                .unwrap_or(NO_UPSTREAM.len())
+        })
+        .max()
+        .unwrap_or(0);
+
+    for status in statuses {
+        let marker = if status.current { "*" } else { " " };
+        let branch = format!("{:branch_width$}", status.branch);
+        let (upstream, upstream_style) = match &status.upstream {
+            Some(upstream) => (upstream.as_str(), Style::new()),
+            None => (NO_UPSTREAM, Style::new().yellow().bold()),
+        };
+        let upstream = format!("{upstream:upstream_width$}");
+
+        let (track, track_style) = if status.gone {
+            ("gone".to_owned(), Style::new().red())
+        } else if status.upstream.is_none() {
+            (String::new(), Style::new())
+        } else {
+            match (status.ahead, status.behind) {
+                (0, 0) => ("up to date".to_owned(), Style::new().dimmed()),
+                (ahead, 0) => (format!("ahead {ahead}"), Style::new().green()),
+                (0, behind) => (format!("behind {behind}"), Style::new().red()),
+                (ahead, behind) => (
+                    format!("ahead {ahead}, behind {behind}"),
+                    Style::new().yellow(),
+                ),
+            }
+        };
+
+        let current_style = if status.current {
+            Style::new().green().bold()
+        } else {
+            Style::new()
+        };
+
+        println!(
+            "{} {}  {}  {}",
+            marker.if_supports_color(Stdout, |text| current_style.style(text)),
+            branch.if_supports_color(Stdout, |text| current_style.style(text)),
+            upstream.if_supports_color(Stdout, |text| upstream_style.style(text)),
+            track.if_supports_color(Stdout, |text| track_style.style(text)),
+        );
+    }
+}
+
+const NO_UPSTREAM: &str = "(no upstream)";
@@ -0,0 +1,130 @@
+//! `--summary`, for showing what's about to be pushed.
+
+use command_error::CommandExt;
+use miette::miette;
+use miette::IntoDiagnostic;
+
+use crate::color::ColorChoice;
+use crate::git;
+use crate::prompt::confirm;
+use crate::Config;
+
+/// The most commits to list when the remote doesn't have any of the branch yet.
+const MAX_COMMITS: usize = 20;
+
+/// How `--summary` finds what the remote already has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SummaryMode {
+    /// Use the remote-tracking refs as they are.
+    Local,
+    /// Fetch the branch first if there's no remote-tracking ref for it.
+    Fetch,
+}
+
+/// Print the commits which pushing `branch` to `remote` would publish, and a diffstat.
+///
+/// With `--confirm`, ask before pushing. Returns `false` if the user declined.
+pub fn summarize(
+    config: &Config,
+    branch: &str,
+    remote: &str,
+    mode: SummaryMode,
+) -> miette::Result<bool> {
+    let tracking = format!("refs/remotes/{remote}/{branch}");
+    if mode == SummaryMode::Fetch && git::rev_parse(&tracking)?.is_none() {
+        let mut command = git::command();
+        command
+            .args(["fetch", "--quiet", remote, branch])
+            .stdout(std::io::stderr());
+        git::log_command(&command);
+        if command.status_checked().is_err() {
+            tracing::debug!("{remote} doesn't have {branch} yet");
+        }
+    }
+
+    // If the remote doesn't have the branch, show the commits it doesn't have at all.
+    let range = match git::rev_parse(&tracking)? {
+        Some(_) => vec![format!("{tracking}..{branch}")],
+        None => vec![
+            branch.to_owned(),
+            "--not".to_owned(),
+            format!("--remotes={remote}"),
+        ],
+    };
+
+    let count: usize = git::command()
+        .arg("rev-list")
+        .arg("--count")
+        .args(&range)
+        .output_checked_utf8()
+        .into_diagnostic()?
+        .stdout
+        .trim()
+        .parse()
+        .into_diagnostic()?;
+
+    let commits = if count == 1 { "commit" } else { "commits" };
+    tracing::info!("Pushing {count} {commits} to {remote}:");
+    if count > MAX_COMMITS {
+        tracing::info!("Showing the newest {MAX_COMMITS}");
+    }
+
+    if count > 0 {
+        let color = match config.cli.color {
+            ColorChoice::Auto => "--color=auto",
+            ColorChoice::Always => "--color=always",
+            ColorChoice::Never => "--color=never",
+        };
+        // Keep stdout for `git upstream`'s result.
+        git::command()
+            .args(["log", "--oneline", "--no-decorate", color])
+            .arg(format!("--max-count={MAX_COMMITS}"))
+            .args(&range)
+            .stdout(std::io::stderr())
+            .status_checked()
+            .into_diagnostic()?;
+        git::command()
+            .args(["diff", "--stat", color])
+            .arg(diff_base(&range)?)
+            .arg(branch)
+            .stdout(std::io::stderr())
+            .status_checked()
+            .into_diagnostic()?;
+    }
+
+    if !config.cli.confirm || config.cli.yes {
+        return Ok(true);
+    }
+    confirm(
+        &format!("Push these {count} {commits} to {remote}?"),
+        "--yes",
+    )
+}
+
+/// Get the commit to diff the branch against for a diffstat of the commits in `range`.
+fn diff_base(range: &[String]) -> miette::Result<String> {
+    if let [range] = range {
+        if let Some((base, _)) = range.split_once("..") {
+            return Ok(base.to_owned());
+        }
+    }
+    // The parent of the oldest commit the remote doesn't have, or the empty tree if the remote
+    // has none of the branch's history.
+    let oldest = git::command()
+        .args(["rev-list", "--reverse"])
+        .args(range)
+        .output_checked_utf8()
+        .into_diagnostic()?
+        .stdout
+        .lines()
+        .next()
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| miette!("There are no commits to push"))?;
+    match git::rev_parse(&format!("{oldest}^"))? {
+        Some(parent) => Ok(parent),
+        None => Ok(EMPTY_TREE.to_owned()),
+    }
+}
+
+/// The object name of the empty tree, for a diffstat of a root commit.
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
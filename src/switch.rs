@@ -0,0 +1,93 @@
+//! `git upstream switch`, for moving a branch's upstream to another remote.
+
+use command_error::CommandExt;
+use miette::IntoDiagnostic;
+
+use crate::git;
+use crate::git::Upstream;
+use crate::prompt::confirm;
+use crate::sync::remote_branch_exists;
+use crate::Config;
+
+/// Move the current branch's upstream to another remote, pushing it there if needed.
+#[derive(Debug, Clone, clap::Args)]
+pub struct SwitchArgs {
+    /// The remote to track.
+    remote: String,
+
+    /// Delete the branch from the previous upstream's remote afterwards.
+    #[arg(long)]
+    delete_old: bool,
+}
+
+/// Run `git upstream switch`.
+pub fn switch(config: &Config, args: &SwitchArgs) -> miette::Result<()> {
+    let remote = &args.remote;
+    config.require_remote(remote)?;
+
+    let branch = config.git().current_branch()?;
+    let previous = Upstream::of(&branch)?;
+    let target = Upstream {
+        remote: remote.clone(),
+        branch: branch.clone(),
+    };
+
+    if previous
+        .as_ref()
+        .is_some_and(|previous| previous.remote == *remote)
+    {
+        tracing::info!("{branch} already tracks {target}");
+        return Ok(());
+    }
+
+    let tracking = format!("refs/remotes/{target}");
+    let exists = git::rev_parse(&tracking)?.is_some() || remote_branch_exists(remote, &branch)?;
+    if exists {
+        if git::rev_parse(&tracking)?.is_none() {
+            // `--set-upstream-to` needs the remote-tracking ref.
+            let mut command = git::command();
+            command.args(["fetch", remote, &branch]);
+            git::log_command(&command);
+            command.status_checked().into_diagnostic()?;
+        }
+        git::command()
+            .args(["branch", &format!("--set-upstream-to={target}"), &branch])
+            .output_checked_utf8()
+            .into_diagnostic()?;
+    } else {
+        let mut command = git::command();
+        command.args(["push", "--set-upstream", remote, &branch]);
+        git::log_command(&command);
+        command.status_checked().into_diagnostic()?;
+    }
+
+    match &previous {
+        Some(previous) => {
+            tracing::info!("Changed the upstream of {branch} from {previous} to {target}")
+        }
+        None => tracing::info!("Set the upstream of {branch} to {target}"),
+    }
+
+    if args.delete_old {
+        match previous {
+            Some(previous) => delete_old(config, &previous)?,
+            None => tracing::info!("{branch} had no upstream; not deleting anything"),
+        }
+    }
+
+    Ok(())
+}
+
+fn delete_old(config: &Config, previous: &Upstream) -> miette::Result<()> {
+    if !config.cli.yes && !confirm(&format!("Delete {previous} from the remote?"), "--yes")? {
+        tracing::info!("Not deleting {previous}");
+        return Ok(());
+    }
+
+    let mut command = git::command();
+    command.args(["push", &previous.remote, "--delete", &previous.branch]);
+    git::log_command(&command);
+    command.status_checked().into_diagnostic()?;
+    tracing::info!("Deleted {previous}");
+    Ok(())
+}
@@ -0,0 +1,113 @@
+//! `--sync` mode, for integrating remote changes before pushing.
+
+use std::process::Output;
+
+use command_error::CommandExt;
+use command_error::OutputContext;
+use miette::miette;
+use miette::IntoDiagnostic;
+use serde::Deserialize;
+
+use crate::git;
+use crate::Config;
+
+/// How `--sync` integrates the remote branch into the local branch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyncStrategy {
+    /// `git rebase REMOTE/BRANCH`.
+    #[default]
+    Rebase,
+    /// `git merge --ff-only REMOTE/BRANCH`.
+    FfOnly,
+}
+
+impl SyncStrategy {
+    /// The names of the strategies in configuration files.
+    pub const NAMES: &[&str] = &["rebase", "ff-only"];
+}
+
+/// Fetch `branch` from `remote` and integrate it into the local branch.
+///
+/// Returns `false` if the remote couldn't be fetched from. Returns an error if integrating the
+/// remote branch fails, because pushing after that would be a mistake.
+pub fn sync(config: &Config, branch: &str, remote: &str) -> miette::Result<bool> {
+    if config.git().current_branch()? != branch {
+        return Err(miette!(
+            "`--sync` requires {branch} to be checked out, so that it can be rebased or merged"
+        ));
+    }
+
+    match remote_branch_exists(remote, branch) {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::debug!(%remote, %branch, "Remote branch doesn't exist; nothing to sync");
+            return Ok(true);
+        }
+        Err(err) => {
+            if config.cli.fail_fast {
+                return Err(err);
+            }
+            tracing::debug!(%remote, "Failed to list Git remote branches");
+            return Ok(false);
+        }
+    }
+
+    let mut command = git::command();
+    command
+        .args(["fetch", remote, branch])
+        .stdout(std::io::stderr());
+    git::log_command(&command);
+    if let Err(err) = command.status_checked() {
+        if config.cli.fail_fast {
+            return Err(err).into_diagnostic();
+        }
+        tracing::debug!(%remote, "Failed to fetch from Git remote");
+        return Ok(false);
+    }
+
+    let tracking = format!("{remote}/{branch}");
+    let mut command = git::command();
+    match config.sync_strategy() {
+        SyncStrategy::Rebase => command.args(["rebase", &tracking]),
+        SyncStrategy::FfOnly => command.args(["merge", "--ff-only", &tracking]),
+    };
+    // Keep stdout for `git upstream`'s result.
+    command.stdout(std::io::stderr());
+    git::log_command(&command);
+
+    if command.status_checked().is_err() {
+        if git::git_path("rebase-merge")?.exists() || git::git_path("rebase-apply")?.exists() {
+            return Err(miette!(
+                help = "Resolve the conflicts and run `git rebase --continue` (or `git rebase \
+                        --abort` to give up), then run `git upstream` again",
+                "Rebasing {branch} onto {tracking} stopped because of conflicts; not pushing"
+            ));
+        }
+        return Err(miette!(
+            "Failed to integrate {tracking} into {branch}; not pushing"
+        ));
+    }
+
+    Ok(true)
+}
+
+/// Check if `remote` has a branch named `branch` with `git ls-remote`.
+pub fn remote_branch_exists(remote: &str, branch: &str) -> miette::Result<bool> {
+    git::command()
+        .args([
+            "ls-remote",
+            "--exit-code",
+            "--heads",
+            remote,
+            &format!("refs/heads/{branch}"),
+        ])
+        .output_checked_as(
+            |context: OutputContext<Output>| match context.status().code() {
+                Some(0) => Ok(true),
+                Some(2) => Ok(false),
+                _ => Err(context.error()),
+            },
+        )
+        .into_diagnostic()
+}
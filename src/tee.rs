@@ -0,0 +1,231 @@
+//! Running commands while both showing and capturing their output.
+
+use std::collections::VecDeque;
+use std::io::IsTerminal;
+use std::io::Read;
+use std::io::Write;
+use std::process::Child;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Stdio;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::git;
+use crate::interrupt;
+
+/// How much of each stream is kept. Output past this is still shown, but only the end of it is
+/// captured.
+const MAX_CAPTURED: usize = 64 * 1024;
+
+/// The result of [`run`].
+#[derive(Debug)]
+pub struct Captured {
+    pub status: ExitStatus,
+    /// Whether the command was killed because it ran for too long.
+    pub timed_out: bool,
+    /// Whether the user pressed Ctrl-C while the command ran.
+    pub interrupted: bool,
+    pub stdout: String,
+    /// Stderr, with progress updates collapsed to their final state.
+    pub stderr: String,
+}
+
+/// Run `command`, copying its stderr (and, with `forward_stdout`, its stdout) to our stderr as
+/// they're written and also capturing (the end of) them.
+///
+/// Our stdout is reserved for results, like the remote a branch was pushed to.
+///
+/// If our stderr is a terminal, `--progress` is passed so that Git still shows progress.
+///
+/// If the command runs for longer than `timeout`, it's killed, along with anything it started
+/// (like `ssh`), and [`Captured::timed_out`] is set.
+///
+/// Ctrl-C doesn't kill us while the command runs; it's reported in [`Captured::interrupted`]
+/// instead.
+pub fn run(
+    command: &Command,
+    forward_stdout: bool,
+    timeout: Option<Duration>,
+) -> std::io::Result<Captured> {
+    let mut command = if std::io::stderr().is_terminal() {
+        git::with_progress(command)
+    } else {
+        let mut ret = git::without_args(command);
+        ret.args(command.get_args());
+        ret
+    };
+    if timeout.is_some() {
+        // The process group is killed on timeout. It isn't the terminal's foreground process
+        // group, so it can't prompt for credentials either.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    }
+    if timeout.is_some() || !std::io::stdin().is_terminal() {
+        command.env("GIT_TERMINAL_PROMPT", "0");
+    }
+    let catch = interrupt::Catch::new();
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let (status, timed_out, stdout, stderr) = std::thread::scope(|scope| {
+        let stdout = scope.spawn(move || {
+            if forward_stdout {
+                copy(stdout, std::io::stderr())
+            } else {
+                copy(stdout, std::io::sink())
+            }
+        });
+        let stderr = scope.spawn(move || copy(stderr, std::io::stderr()));
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut timed_out = false;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                timed_out = true;
+                kill(&mut child)?;
+                break child.wait()?;
+            }
+            // With a timeout, the command is in its own process group, so it didn't get the
+            // signal from the terminal.
+            if timeout.is_some() && interrupt::interrupted() {
+                kill(&mut child)?;
+                break child.wait()?;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        };
+
+        std::io::Result::Ok((
+            status,
+            timed_out,
+            stdout.join().expect("copying stdout panicked"),
+            stderr.join().expect("copying stderr panicked"),
+        ))
+    })?;
+    drop(catch);
+
+    #[cfg(unix)]
+    let killed_by_sigint =
+        std::os::unix::process::ExitStatusExt::signal(&status) == Some(libc::SIGINT);
+    #[cfg(not(unix))]
+    let killed_by_sigint = false;
+
+    Ok(Captured {
+        status,
+        timed_out,
+        interrupted: interrupt::interrupted() || killed_by_sigint,
+        stdout: stdout?,
+        stderr: collapse_progress(&stderr?),
+    })
+}
+
+/// Kill `child` and its process group, so that processes it started don't keep its output open.
+fn kill(child: &mut Child) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        // The child leads its own process group, so the group has the child's ID.
+        let group = libc::pid_t::try_from(child.id()).expect("process IDs fit in `pid_t`");
+        // SAFETY: `killpg` doesn't touch memory.
+        if unsafe { libc::killpg(group, libc::SIGKILL) } != 0 {
+            let err = std::io::Error::last_os_error();
+            // `ESRCH` means the whole group already exited.
+            if err.raw_os_error() != Some(libc::ESRCH) {
+                tracing::debug!("Failed to kill process group {group}: {err}");
+            }
+        }
+    }
+    match child.kill() {
+        // The process group was already killed.
+        Err(err) if err.kind() == std::io::ErrorKind::InvalidInput => Ok(()),
+        result => result,
+    }
+}
+
+/// Copy `from` to `to` until it's closed, returning the end of what was copied.
+fn copy(mut from: impl Read, mut to: impl Write) -> std::io::Result<String> {
+    let mut captured = VecDeque::new();
+    let mut buffer = [0; 4096];
+    loop {
+        let n = match from.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        to.write_all(&buffer[..n])?;
+        to.flush()?;
+        captured.extend(&buffer[..n]);
+        if captured.len() > MAX_CAPTURED {
+            captured.drain(..captured.len() - MAX_CAPTURED);
+        }
+    }
+    Ok(String::from_utf8_lossy(captured.make_contiguous()).into_owned())
+}
+
+/// Git redraws progress lines like `Writing objects:  50% (1/2)` with carriage returns; keep only
+/// the last version of each line.
+fn collapse_progress(output: &str) -> String {
+    output
+        .split_inclusive('\n')
+        .map(|line| {
+            let (line, newline) = match line.strip_suffix('\n') {
+                Some(line) => (line, "\n"),
+                None => (line, ""),
+            };
+            let line = line.trim_end_matches('\r');
+            let last = line.rsplit('\r').next().unwrap_or(line);
+            format!("{last}{newline}")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_progress_keeps_the_environment() {
+        let mut command = Command::new("git");
+        command
+            .args(["push", "origin", "main"])
+            .current_dir("/tmp")
+            .env("GIT_SSH_COMMAND", "ssh -i key")
+            .env_remove("GIT_DIR");
+        let progress = git::with_progress(&command);
+
+        assert_eq!(
+            progress.get_args().collect::<Vec<_>>(),
+            ["push", "--progress", "origin", "main"]
+        );
+        assert_eq!(progress.get_current_dir(), command.get_current_dir());
+        assert_eq!(
+            progress.get_envs().collect::<Vec<_>>(),
+            command.get_envs().collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_keeps_the_environment() {
+        // A script, rather than `sh -c`, so `--progress` is only an argument if it's added.
+        let scratch = crate::git_dir::tests::Scratch::new();
+        let script = scratch.0.join("env.sh");
+        let contents = r#"printf '%s %s' "$GIT_UPSTREAM_TEST" "${HOME-unset}""#;
+        std::fs::write(&script, contents).unwrap();
+        let mut command = Command::new("sh");
+        command
+            .arg(&script)
+            .env("GIT_UPSTREAM_TEST", "kept")
+            .env_remove("HOME");
+        let captured = run(&command, false, None).unwrap();
+        assert!(captured.status.success());
+        assert_eq!(captured.stdout, "kept unset");
+    }
+}
@@ -0,0 +1,136 @@
+//! Finding ticket IDs in branch names, like `PROJ-1234` in `feature/PROJ-1234-short-desc`,
+//! to reference them in pull requests.
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::Config;
+
+/// The text `--pr` and `--mr` add to the description, unless `ticket-template` is set.
+pub const DEFAULT_TEMPLATE: &str = "Refs {ticket}";
+
+/// A regex matching ticket IDs in branch names, from `ticket-pattern` in the configuration
+/// file. The ticket is the first capture group which matched.
+#[derive(Debug, Clone)]
+pub struct TicketPattern(Regex);
+
+impl<'de> Deserialize<'de> for TicketPattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+        let regex = Regex::new(&pattern).map_err(serde::de::Error::custom)?;
+        if regex.captures_len() < 2 {
+            return Err(serde::de::Error::custom(
+                "`ticket-pattern` needs a capture group for the ticket, like `(PROJ-[0-9]+)`",
+            ));
+        }
+        Ok(Self(regex))
+    }
+}
+
+impl TicketPattern {
+    /// Find the ticket in `branch`: the first capture group which matched, so patterns can
+    /// have alternatives like `([A-Z]+-[0-9]+)|issue-([0-9]+)`.
+    pub fn extract<'b>(&self, branch: &'b str) -> Option<&'b str> {
+        let captures = self.0.captures(branch)?;
+        captures
+            .iter()
+            .skip(1)
+            .flatten()
+            .map(|group| group.as_str())
+            .find(|ticket| !ticket.is_empty())
+    }
+}
+
+/// A ticket found in a branch name.
+#[derive(Debug, Clone)]
+pub struct Ticket {
+    /// The ticket's ID, like `PROJ-1234`.
+    pub id: String,
+    /// The text to add to the description, like `Refs PROJ-1234`.
+    pub reference: String,
+}
+
+impl Ticket {
+    /// Find `branch`'s ticket with `ticket-pattern`, formatted with `ticket-template`.
+    ///
+    /// Returns `None` if there's no `ticket-pattern` or it doesn't match.
+    pub fn of(config: &Config, branch: &str) -> Option<Self> {
+        let id = config.file.ticket_pattern.as_ref()?.extract(branch)?;
+        let template = config
+            .file
+            .ticket_template
+            .as_deref()
+            .unwrap_or(DEFAULT_TEMPLATE);
+        Some(Self {
+            id: id.to_owned(),
+            reference: template.replace("{ticket}", id),
+        })
+    }
+
+    /// Add the reference to a description, unless it already mentions the ticket.
+    pub fn add_to(&self, description: Option<&str>) -> String {
+        match description {
+            Some(description) if description.contains(&self.id) => description.to_owned(),
+            Some(description) => format!("{description}\n\n{}", self.reference),
+            None => self.reference.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::value::Error;
+    use serde::de::IntoDeserializer;
+
+    use super::*;
+
+    fn pattern(pattern: &str) -> Result<TicketPattern, Error> {
+        TicketPattern::deserialize(pattern.into_deserializer())
+    }
+
+    #[test]
+    fn extracts_tickets() {
+        let jira = pattern("([A-Z][A-Z0-9]+-[0-9]+)").unwrap();
+        let cases = [
+            ("ABC-123-foo", Some("ABC-123")),
+            ("feature/ABC-123", Some("ABC-123")),
+            ("feature/ABC-123-and-DEF-456", Some("ABC-123")),
+            ("abc-123-lowercase", None),
+            ("ABC-x", None),
+            ("main", None),
+            ("", None),
+        ];
+        for (branch, expected) in cases {
+            assert_eq!(jira.extract(branch), expected, "{branch}");
+        }
+
+        let case_insensitive = pattern("(?i)([a-z]+-[0-9]+)").unwrap();
+        assert_eq!(case_insensitive.extract("abc-123-foo"), Some("abc-123"));
+
+        // The first group which matched is the ticket.
+        let alternatives = pattern("([A-Z]+-[0-9]+)|issue-([0-9]+)").unwrap();
+        assert_eq!(alternatives.extract("fix/issue-42"), Some("42"));
+        assert_eq!(alternatives.extract("fix/ABC-1"), Some("ABC-1"));
+    }
+
+    #[test]
+    fn patterns_need_a_group() {
+        let err = pattern("[A-Z]+-[0-9]+").unwrap_err();
+        assert!(err.to_string().contains("needs a capture group"), "{err}");
+        assert!(pattern("(unclosed").is_err());
+    }
+
+    #[test]
+    fn adds_references() {
+        let ticket = Ticket {
+            id: "ABC-123".to_owned(),
+            reference: "Refs ABC-123".to_owned(),
+        };
+        assert_eq!(ticket.add_to(None), "Refs ABC-123");
+        assert_eq!(ticket.add_to(Some("Fix it")), "Fix it\n\nRefs ABC-123");
+        assert_eq!(ticket.add_to(Some("Fix ABC-123")), "Fix ABC-123");
+    }
+}
@@ -0,0 +1,128 @@
+//! `git upstream undo`, for reverting the last push `git-upstream` made in this repository.
+
+use std::process::Command;
+
+use command_error::CommandExt;
+use command_error::Utf8ProgramAndArgs;
+use miette::miette;
+use miette::IntoDiagnostic;
+
+use crate::git;
+use crate::journal;
+use crate::journal::Journal;
+use crate::journal::PushRecord;
+use crate::Config;
+
+/// Revert the last push `git-upstream` made in this repository.
+#[derive(Debug, Clone, clap::Args)]
+pub struct UndoArgs {
+    /// Print the commands that would be run without running them.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Run `git upstream undo`.
+pub fn undo(config: &Config, args: &UndoArgs) -> miette::Result<()> {
+    let mut journal = Journal::load(config)?;
+    let index = journal
+        .last_in(&journal::repo()?)
+        .ok_or_else(|| miette!("No pushes by git-upstream to undo in this repository"))?;
+    let record = &journal.pushes[index];
+    let PushRecord {
+        branch,
+        remote,
+        new,
+        ..
+    } = record;
+
+    let current = remote_commit(remote, branch)?;
+    if current.as_deref() != Some(new.as_str()) {
+        return Err(miette!(
+            help = "Someone else may have pushed to it; fetch and check before changing it",
+            "Refusing to undo the push of {branch} to {remote} because it has changed since; it's \
+             now {}",
+            current.as_deref().unwrap_or("deleted")
+        ));
+    }
+
+    let commands = undo_commands(record)?;
+    for mut command in commands {
+        if args.dry_run {
+            tracing::info!("Would run: {}", Utf8ProgramAndArgs::from(&command));
+            continue;
+        }
+        git::log_command(&command);
+        command.status_checked().into_diagnostic()?;
+    }
+
+    if args.dry_run {
+        return Ok(());
+    }
+
+    match &record.old {
+        Some(old) => tracing::info!("Reset {branch} on {remote} to {old}"),
+        None => tracing::info!("Deleted {branch} from {remote}"),
+    }
+    journal.pushes.remove(index);
+    journal.save(config)
+}
+
+/// The commands to revert a push and restore the branch's previous upstream configuration.
+fn undo_commands(record: &PushRecord) -> miette::Result<Vec<Command>> {
+    let PushRecord {
+        branch,
+        remote,
+        old,
+        new,
+        previous_remote,
+        previous_merge,
+        ..
+    } = record;
+    let mut commands = Vec::new();
+
+    // The lease makes sure the remote branch hasn't moved between checking it and pushing.
+    let mut push = git::command();
+    push.args([
+        "push",
+        &format!("--force-with-lease=refs/heads/{branch}:{new}"),
+        remote,
+    ]);
+    match old {
+        Some(old) => push.arg(format!("{old}:refs/heads/{branch}")),
+        None => push.args(["--delete", &format!("refs/heads/{branch}")]),
+    };
+    commands.push(push);
+
+    match (previous_remote, previous_merge) {
+        (Some(previous_remote), Some(previous_merge)) => {
+            for (key, value) in [("remote", previous_remote), ("merge", previous_merge)] {
+                let mut command = git::command();
+                command.args(["config", &format!("branch.{branch}.{key}"), value]);
+                commands.push(command);
+            }
+        }
+        _ => {
+            if git::config_get(&format!("branch.{branch}.merge"))?.is_some() {
+                let mut command = git::command();
+                command.args(["branch", "--unset-upstream", branch]);
+                commands.push(command);
+            }
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Get the commit `branch` points to on `remote` with `git ls-remote`, or `None` if it doesn't
+/// exist.
+fn remote_commit(remote: &str, branch: &str) -> miette::Result<Option<String>> {
+    let output = git::command()
+        .args(["ls-remote", remote, &format!("refs/heads/{branch}")])
+        .output_checked_utf8()
+        .into_diagnostic()?;
+    Ok(output
+        .stdout
+        .split_whitespace()
+        .next()
+        .map(ToOwned::to_owned))
+}
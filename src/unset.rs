@@ -0,0 +1,58 @@
+//! `git upstream unset`, for removing a branch's upstream.
+
+use command_error::CommandExt;
+use miette::IntoDiagnostic;
+
+use crate::git;
+use crate::git::Upstream;
+use crate::status::BranchStatus;
+
+/// Remove a branch's upstream, like `git branch --unset-upstream`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct UnsetArgs {
+    /// The branch to unset the upstream of. Defaults to the current branch.
+    #[arg(long)]
+    branch: Option<String>,
+
+    /// Unset the upstream of every branch whose upstream is gone.
+    #[arg(long, conflicts_with = "branch")]
+    all_gone: bool,
+}
+
+/// Run `git upstream unset`.
+pub fn unset(args: &UnsetArgs) -> miette::Result<()> {
+    if args.all_gone {
+        let gone = BranchStatus::list()?
+            .into_iter()
+            .filter(|status| status.gone)
+            .collect::<Vec<_>>();
+        if gone.is_empty() {
+            tracing::info!("No branches have an upstream which is gone");
+        }
+        for status in gone {
+            unset_branch(&status.branch)?;
+        }
+        return Ok(());
+    }
+
+    let branch = match &args.branch {
+        Some(branch) => branch.clone(),
+        None => git::current_branch()?,
+    };
+    unset_branch(&branch)
+}
+
+fn unset_branch(branch: &str) -> miette::Result<()> {
+    let Some(upstream) = Upstream::of(branch)? else {
+        tracing::info!("{branch} has no upstream");
+        return Ok(());
+    };
+
+    git::command()
+        .args(["branch", "--unset-upstream", branch])
+        .status_checked()
+        .into_diagnostic()?;
+
+    tracing::info!("Unset the upstream of {branch} (was tracking {upstream})");
+    Ok(())
+}
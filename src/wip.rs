@@ -0,0 +1,145 @@
+//! Refusing to push `fixup!` and work-in-progress commits, which are meant to be squashed or
+//! reworded first.
+
+use command_error::CommandExt;
+use miette::miette;
+use miette::IntoDiagnostic;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::git;
+use crate::Config;
+
+/// Subject prefixes `git commit --fixup` and `--squash` create, for `git rebase --autosquash`.
+const AUTOSQUASH_PREFIXES: &[&str] = &["fixup!", "squash!", "amend!"];
+
+/// The `wip-patterns` used if none are configured.
+pub const DEFAULT_PATTERNS: &[&str] = &[r"(?i)^wip\b"];
+
+/// A regex matching the subjects of work-in-progress commits, from `wip-patterns` in the
+/// configuration file.
+#[derive(Debug, Clone)]
+pub struct WipPattern(Regex);
+
+impl<'de> Deserialize<'de> for WipPattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(Self)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Refuse to push `branch` to `remote` if any commits which aren't on the remote yet look like
+/// work in progress, unless `--allow-wip` is given or `check-wip = false`.
+///
+/// The outgoing commits are the ones from [`Config::outgoing`].
+pub fn check(config: &Config, branch: &str, remote: &str) -> miette::Result<()> {
+    if config.cli.allow_wip || !config.file.check_wip.unwrap_or(true) {
+        return Ok(());
+    }
+
+    let Some(outgoing) = config.outgoing(branch, remote)? else {
+        return Ok(());
+    };
+    let log = git::command()
+        .args(["log", "--format=%h %s"])
+        .args(outgoing)
+        .output_checked_utf8()
+        .into_diagnostic()?
+        .stdout;
+
+    let patterns = match &config.file.wip_patterns {
+        Some(patterns) => patterns.clone(),
+        None => DEFAULT_PATTERNS
+            .iter()
+            .map(|pattern| WipPattern(Regex::new(pattern).expect("default patterns are valid")))
+            .collect(),
+    };
+    let wip = wip_commits(&log, &patterns);
+    if wip.is_empty() {
+        return Ok(());
+    }
+
+    Err(miette!(
+        help = "Squash them with `git rebase --interactive --autosquash`, or pass `--allow-wip` \
+                to push anyway",
+        "Refusing to push {branch} to {remote}, because it has work-in-progress commits:\n{}",
+        wip.join("\n")
+    ))
+}
+
+/// Get the lines of `git log --format='%h %s'` output for commits which look like work in
+/// progress: autosquash commits, and ones with subjects matching `patterns`.
+fn wip_commits<'a>(log: &'a str, patterns: &[WipPattern]) -> Vec<&'a str> {
+    log.lines()
+        .filter(|line| {
+            let subject = line.split_once(' ').map_or("", |(_, subject)| subject);
+            AUTOSQUASH_PREFIXES
+                .iter()
+                .any(|prefix| subject.starts_with(prefix))
+                || patterns.iter().any(|pattern| pattern.0.is_match(subject))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<WipPattern> {
+        patterns
+            .iter()
+            .map(|pattern| WipPattern(Regex::new(pattern).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn finds_wip_commits() {
+        let log = "1111111 fixup! Add login\n\
+                   2222222 squash! Add login\n\
+                   3333333 amend! Add login\n\
+                   4444444 WIP: half a parser\n\
+                   5555555 wip\n\
+                   6666666 Wipe the cache on logout\n\
+                   7777777 Add login\n\
+                   8888888 Mention fixup! in the docs\n";
+        assert_eq!(
+            wip_commits(log, &patterns(DEFAULT_PATTERNS)),
+            [
+                "1111111 fixup! Add login",
+                "2222222 squash! Add login",
+                "3333333 amend! Add login",
+                "4444444 WIP: half a parser",
+                "5555555 wip",
+            ]
+        );
+    }
+
+    #[test]
+    fn configured_patterns() {
+        let log = "1111111 fixup! Add login\n\
+                   2222222 WIP: half a parser\n\
+                   3333333 DO NOT MERGE: debugging\n";
+        // Configured patterns replace the defaults, but autosquash commits are always found.
+        assert_eq!(
+            wip_commits(log, &patterns(&["^DO NOT MERGE"])),
+            [
+                "1111111 fixup! Add login",
+                "3333333 DO NOT MERGE: debugging"
+            ]
+        );
+        assert_eq!(wip_commits(log, &[]), ["1111111 fixup! Add login"]);
+
+        #[derive(Debug, Deserialize)]
+        struct File {
+            wip_patterns: Vec<WipPattern>,
+        }
+        let file: File = toml::from_str("wip_patterns = ['^wip']").unwrap();
+        assert!(file.wip_patterns[0].0.is_match("wip: more"));
+        assert!(toml::from_str::<File>("wip_patterns = ['(unclosed']").is_err());
+    }
+}
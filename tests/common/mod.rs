@@ -0,0 +1,378 @@
+//! Helpers for the integration tests.
+
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Output;
+use std::process::Stdio;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+/// A temporary directory, deleted when dropped.
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "git-upstream-test-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+        // `/tmp` is a symlink on macOS, and Git reports the resolved path.
+        Self(path.canonicalize().unwrap())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Environment variables which keep Git and `git-upstream` from reading the user's
+/// configuration, pointed into `home`.
+pub fn scrubbed_env(home: &Path) -> Vec<(&'static str, PathBuf)> {
+    vec![
+        ("HOME", home.to_owned()),
+        ("XDG_CONFIG_HOME", home.join(".config")),
+        ("XDG_STATE_HOME", home.join(".local/state")),
+        ("XDG_DATA_HOME", home.join(".local/share")),
+        ("XDG_CACHE_HOME", home.join(".cache")),
+        ("USERPROFILE", home.to_owned()),
+        ("APPDATA", home.join("AppData/Roaming")),
+        ("LOCALAPPDATA", home.join("AppData/Local")),
+        ("GIT_CONFIG_GLOBAL", home.join(".gitconfig")),
+        ("GIT_CONFIG_NOSYSTEM", PathBuf::from("1")),
+    ]
+}
+
+/// Run `git` in `dir`, panicking if it fails, and get its stdout.
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "`git {}` failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap().trim().to_owned()
+}
+
+/// Create a repository at `dir` with one commit on `main`.
+pub fn init_repo(dir: &Path) {
+    std::fs::create_dir_all(dir).unwrap();
+    git(dir, &["init", "--quiet", "--initial-branch=main"]);
+    git(dir, &["config", "user.name", "Test"]);
+    git(dir, &["config", "user.email", "test@example.com"]);
+    git(
+        dir,
+        &[
+            "commit",
+            "--quiet",
+            "--allow-empty",
+            "--message=Initial commit",
+        ],
+    );
+}
+
+/// A work repository on `main`, with bare repositories next to it to add as remotes, and a
+/// `HOME` of its own.
+#[derive(Debug)]
+pub struct Fixture {
+    tmp: TempDir,
+    /// The work repository.
+    pub work: PathBuf,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        let tmp = TempDir::new();
+        let home = tmp.path().join("home");
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::write(
+            home.join(".gitconfig"),
+            "[user]\n\tname = Test\n\temail = test@example.com\n\
+             [init]\n\tdefaultBranch = main\n",
+        )
+        .unwrap();
+        let fixture = Self {
+            work: tmp.path().join("work"),
+            tmp,
+        };
+        std::fs::create_dir_all(&fixture.work).unwrap();
+        fixture.git(&["init", "--quiet"]);
+        fixture.git(&[
+            "commit",
+            "--quiet",
+            "--allow-empty",
+            "--message=Initial commit",
+        ]);
+        fixture
+    }
+
+    pub fn path(&self) -> &Path {
+        self.tmp.path()
+    }
+
+    /// The path of the user configuration file, which doesn't exist unless a test writes it.
+    pub fn config_file(&self) -> PathBuf {
+        if cfg!(windows) {
+            self.path()
+                .join("home/AppData/Roaming/git-upstream/config.toml")
+        } else {
+            self.path().join("home/.config/git-upstream/config.toml")
+        }
+    }
+
+    /// The directory state like the push journal is kept in.
+    pub fn state_dir(&self) -> PathBuf {
+        if cfg!(windows) {
+            self.path().join("home/AppData/Local/git-upstream")
+        } else {
+            self.path().join("home/.local/state/git-upstream")
+        }
+    }
+
+    /// Write the user configuration file.
+    pub fn write_config(&self, contents: &str) {
+        let path = self.config_file();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    /// Apply the scrubbed environment to `command`, keeping only `PATH` (and `SYSTEMROOT`, which
+    /// Windows programs need) from ours. Programs from [`Fixture::add_program`] come first on
+    /// `PATH`.
+    pub fn env<'c>(&self, command: &'c mut Command) -> &'c mut Command {
+        command.env_clear();
+        if let Some(value) = std::env::var_os("SYSTEMROOT") {
+            command.env("SYSTEMROOT", value);
+        }
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let path = std::iter::once(self.bin()).chain(std::env::split_paths(&path));
+        command.env("PATH", std::env::join_paths(path).unwrap());
+        command.envs(scrubbed_env(&self.path().join("home")))
+    }
+
+    /// The directory [`Fixture::add_program`] puts programs in.
+    fn bin(&self) -> PathBuf {
+        self.path().join("bin")
+    }
+
+    /// Put a shell script called `name` on `PATH`, to stand in for a program like `gh`.
+    pub fn add_program(&self, name: &str, script: &str) {
+        std::fs::create_dir_all(self.bin()).unwrap();
+        let path = self.bin().join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{script}")).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    /// Run `git` in the work repository, panicking if it fails, and get its stdout.
+    pub fn git(&self, args: &[&str]) -> String {
+        self.git_in(&self.work, args)
+    }
+
+    /// Run `git` in `dir`, panicking if it fails, and get its stdout.
+    pub fn git_in(&self, dir: &Path, args: &[&str]) -> String {
+        let output = self
+            .env(&mut Command::new("git"))
+            .current_dir(dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap().trim().to_owned()
+    }
+
+    /// Create a bare repository and add it as the remote `name`.
+    pub fn add_remote(&self, name: &str) -> PathBuf {
+        let bare = self.path().join(format!("{name}.git"));
+        self.git(&["init", "--quiet", "--bare", bare.to_str().unwrap()]);
+        self.git(&["remote", "add", name, bare.to_str().unwrap()]);
+        bare
+    }
+
+    /// Add the remote `name`, which refuses pushes like GitHub does when you don't have
+    /// access to a repository.
+    pub fn add_read_only_remote(&self, name: &str) -> PathBuf {
+        let bare = self.add_remote(name);
+        let script = self.path().join(format!("{name}-receive-pack"));
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\n\
+                 echo 'ERROR: Permission to owner/{name}.git denied to someone.' >&2\n\
+                 exit 1\n"
+            ),
+        )
+        .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        self.git(&[
+            "config",
+            &format!("remote.{name}.receivepack"),
+            script.to_str().unwrap(),
+        ]);
+        bare
+    }
+
+    /// Make a commit in the work repository.
+    pub fn commit(&self, message: &str) {
+        self.git(&["commit", "--quiet", "--allow-empty", "--message", message]);
+    }
+
+    /// Run `git-upstream` in the work repository.
+    pub fn run(&self, args: &[&str]) -> Run {
+        self.run_in(&self.work, args)
+    }
+
+    /// Run `git-upstream` in the work repository without `HOME`, `APPDATA`, or any `XDG_*`
+    /// variables set.
+    pub fn run_without_home(&self, args: &[&str]) -> Run {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_git-upstream"));
+        command.env_clear();
+        for name in ["PATH", "SYSTEMROOT"] {
+            if let Some(value) = std::env::var_os(name) {
+                command.env(name, value);
+            }
+        }
+        let output = command
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .current_dir(&self.work)
+            .args(args)
+            .output()
+            .unwrap();
+        Run::from(output)
+    }
+
+    /// Run `git-upstream` in `dir`.
+    pub fn run_in(&self, dir: &Path, args: &[&str]) -> Run {
+        let output = self
+            .env(&mut Command::new(env!("CARGO_BIN_EXE_git-upstream")))
+            .current_dir(dir)
+            .args(args)
+            .output()
+            .unwrap();
+        Run::from(output)
+    }
+
+    /// Run `git-upstream` in the work repository with the extra environment variables `vars`.
+    pub fn run_with_env(&self, args: &[&str], vars: &[(&str, &str)]) -> Run {
+        let output = self
+            .env(&mut Command::new(env!("CARGO_BIN_EXE_git-upstream")))
+            .envs(vars.iter().copied())
+            .current_dir(&self.work)
+            .args(args)
+            .output()
+            .unwrap();
+        Run::from(output)
+    }
+
+    /// Run `git-upstream` in the work repository, writing `stdin` to its stdin.
+    pub fn run_with_stdin(&self, args: &[&str], stdin: &str) -> Run {
+        let mut child = self
+            .env(&mut Command::new(env!("CARGO_BIN_EXE_git-upstream")))
+            .current_dir(&self.work)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.as_bytes())
+            .unwrap();
+        Run::from(child.wait_with_output().unwrap())
+    }
+
+    /// Get the commit `branch` points to in the bare repository `bare`.
+    pub fn remote_rev(&self, bare: &Path, branch: &str) -> Option<String> {
+        let output = self
+            .env(&mut Command::new("git"))
+            .arg("--git-dir")
+            .arg(bare)
+            .args(["rev-parse", "--verify", "--quiet"])
+            .arg(format!("refs/heads/{branch}"))
+            .output()
+            .unwrap();
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8(output.stdout).unwrap().trim().to_owned())
+    }
+
+    /// Get a configuration value in the work repository.
+    pub fn config(&self, key: &str) -> Option<String> {
+        let output = self
+            .env(&mut Command::new("git"))
+            .current_dir(&self.work)
+            .args(["config", "--get", key])
+            .output()
+            .unwrap();
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8(output.stdout).unwrap().trim().to_owned())
+    }
+}
+
+/// The result of running `git-upstream`.
+#[derive(Debug)]
+pub struct Run {
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl From<Output> for Run {
+    fn from(output: Output) -> Self {
+        Self {
+            code: output.status.code(),
+            stdout: String::from_utf8(output.stdout).unwrap(),
+            stderr: String::from_utf8(output.stderr).unwrap(),
+        }
+    }
+}
+
+impl Run {
+    /// Check the exit code, showing the output if it's wrong.
+    pub fn assert_code(&self, code: i32) -> &Self {
+        assert_eq!(
+            self.code,
+            Some(code),
+            "unexpected exit code\nstdout:\n{}\nstderr:\n{}",
+            self.stdout,
+            self.stderr
+        );
+        self
+    }
+}
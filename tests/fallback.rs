@@ -0,0 +1,152 @@
+//! Which remotes get tried, and in what order, against a [`FakeGit`].
+
+mod common;
+
+use std::sync::Arc;
+
+use clap::Parser;
+use git_upstream::Cli;
+use git_upstream::Config;
+use git_upstream::FailureKind;
+use git_upstream::FakeGit;
+use git_upstream::FakeOutcome;
+use git_upstream::FakeOutcome::*;
+
+struct Case {
+    name: &'static str,
+    remotes: &'static [(&'static str, FakeOutcome)],
+    args: &'static [&'static str],
+    /// The remotes pushed to, in order.
+    pushes: &'static [&'static str],
+    /// The remote the branch ended up on.
+    pushed_to: Option<&'static str>,
+    /// Why the last attempt failed.
+    failure: Option<FailureKind>,
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "first remote accepts",
+        remotes: &[("origin", Success), ("fork", Success)],
+        args: &[],
+        pushes: &["origin"],
+        pushed_to: Some("origin"),
+        failure: None,
+    },
+    Case {
+        name: "falls back after an auth failure",
+        remotes: &[("origin", AuthFailure), ("fork", Success)],
+        args: &[],
+        pushes: &["origin", "fork"],
+        pushed_to: Some("fork"),
+        failure: None,
+    },
+    Case {
+        name: "falls back after a timeout",
+        remotes: &[("origin", Hang), ("fork", Success)],
+        args: &[],
+        pushes: &["origin", "fork"],
+        pushed_to: Some("fork"),
+        failure: None,
+    },
+    Case {
+        name: "retries a timeout",
+        remotes: &[("origin", Hang), ("fork", Success)],
+        args: &["--retry=2", "--retry-delay=0s"],
+        pushes: &["origin", "origin", "origin", "fork"],
+        pushed_to: Some("fork"),
+        failure: None,
+    },
+    Case {
+        name: "stops after a non-fast-forward",
+        remotes: &[("origin", NonFastForward), ("fork", Success)],
+        args: &[],
+        pushes: &["origin"],
+        pushed_to: None,
+        failure: Some(FailureKind::NonFastForward),
+    },
+    Case {
+        name: "stops after the first failure with --fail-fast",
+        remotes: &[("origin", AuthFailure), ("fork", Success)],
+        args: &["--fail-fast"],
+        pushes: &["origin"],
+        pushed_to: None,
+        failure: Some(FailureKind::PermissionDenied),
+    },
+    Case {
+        name: "every remote fails",
+        remotes: &[("origin", AuthFailure), ("fork", AuthFailure)],
+        args: &[],
+        pushes: &["origin", "fork"],
+        pushed_to: None,
+        failure: Some(FailureKind::PermissionDenied),
+    },
+    Case {
+        name: "origin is tried before other remotes",
+        remotes: &[("alpha", Success), ("origin", AuthFailure)],
+        args: &[],
+        pushes: &["origin", "alpha"],
+        pushed_to: Some("alpha"),
+        failure: None,
+    },
+    Case {
+        name: "a remote on the command line is tried first",
+        remotes: &[("origin", Success), ("fork", Success)],
+        args: &["fork"],
+        pushes: &["fork"],
+        pushed_to: Some("fork"),
+        failure: None,
+    },
+];
+
+#[test]
+fn fallback() {
+    // The push journal and the repository-local configuration file still use the real
+    // repository, so run in a scratch one. This test is alone in its file because it changes
+    // the working directory.
+    let tmp = common::TempDir::new();
+    for (key, value) in common::scrubbed_env(&tmp.path().join("home")) {
+        std::env::set_var(key, value);
+    }
+    let repo = tmp.path().join("repo");
+    common::init_repo(&repo);
+    common::git(&repo, &["switch", "--quiet", "--create", "feature"]);
+    std::env::set_current_dir(&repo).unwrap();
+
+    for case in CASES {
+        let git = case
+            .remotes
+            .iter()
+            .fold(FakeGit::new("feature"), |git, (name, outcome)| {
+                git.remote(name, *outcome)
+            });
+        let git = Arc::new(git);
+        let mut args = vec!["git-upstream", "--no-auth-check"];
+        args.extend(case.args);
+        let config = Config::with_cli(Cli::parse_from(args))
+            .unwrap()
+            .with_git(git.clone());
+
+        let plan = config.plan(&config.branch().unwrap()).unwrap();
+        let report = git_upstream::push_upstream(&config, &plan).unwrap();
+
+        assert_eq!(
+            git.pushes(),
+            case.pushes,
+            "{}: remotes pushed to",
+            case.name
+        );
+        assert_eq!(
+            report.outcome.remote.as_deref(),
+            case.pushed_to,
+            "{}: remote pushed to",
+            case.name
+        );
+        assert_eq!(
+            report.attempts.last().and_then(|attempt| attempt.failure),
+            case.failure,
+            "{}: failure",
+            case.name
+        );
+    }
+}
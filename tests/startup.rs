@@ -0,0 +1,325 @@
+//! What happens before pushing.
+
+mod common;
+
+use common::Fixture;
+
+const CORRUPT_CONFIG: &str = "remotes = [\"origin\"\n";
+
+#[test]
+fn help_with_corrupt_config() {
+    let fixture = Fixture::new();
+    fixture.write_config(CORRUPT_CONFIG);
+
+    let run = fixture.run(&["--help"]);
+    run.assert_code(0);
+    assert!(run.stdout.contains("Usage:"), "{}", run.stdout);
+    assert_eq!(run.stderr, "");
+}
+
+#[test]
+fn version_with_corrupt_config() {
+    let fixture = Fixture::new();
+    fixture.write_config(CORRUPT_CONFIG);
+
+    let run = fixture.run(&["--version"]);
+    run.assert_code(0);
+    assert!(run.stdout.starts_with("git-upstream "), "{}", run.stdout);
+}
+
+#[test]
+fn completions_with_corrupt_config() {
+    let fixture = Fixture::new();
+    fixture.write_config(CORRUPT_CONFIG);
+
+    let run = fixture.run(&["completions", "bash"]);
+    run.assert_code(0);
+    assert!(run.stdout.contains("complete"), "{}", run.stdout);
+}
+
+#[test]
+fn push_with_corrupt_config() {
+    let fixture = Fixture::new();
+    fixture.add_remote("origin");
+    fixture.write_config(CORRUPT_CONFIG);
+
+    let run = fixture.run(&[]);
+    run.assert_code(5);
+    assert!(
+        run.stderr
+            .contains("Failed to deserialize configuration file"),
+        "{}",
+        run.stderr
+    );
+}
+
+#[test]
+fn push_without_home() {
+    let fixture = Fixture::new();
+    let origin = fixture.add_remote("origin");
+    fixture.git(&["switch", "--quiet", "--create", "feature"]);
+
+    let run = fixture.run_without_home(&[]);
+    run.assert_code(0);
+    assert_eq!(run.stdout, "origin/feature\n");
+    assert_eq!(
+        fixture.remote_rev(&origin, "feature"),
+        Some(fixture.git(&["rev-parse", "HEAD"]))
+    );
+}
+
+#[test]
+fn explicit_config_without_home() {
+    let fixture = Fixture::new();
+    fixture.add_remote("origin");
+    let fork = fixture.add_remote("fork");
+    fixture.git(&["switch", "--quiet", "--create", "feature"]);
+    let config = fixture.path().join("ci.toml");
+    std::fs::write(&config, "remotes = [\"fork\"]\n").unwrap();
+
+    let run = fixture.run_without_home(&["--config", config.to_str().unwrap()]);
+    run.assert_code(0);
+    assert_eq!(run.stdout, "fork/feature\n");
+    assert!(fixture.remote_rev(&fork, "feature").is_some());
+}
+
+#[test]
+fn missing_explicit_config() {
+    let fixture = Fixture::new();
+    fixture.add_remote("origin");
+
+    let run = fixture.run(&["--config", "nonexistent.toml"]);
+    run.assert_code(5);
+    assert!(
+        run.stderr
+            .contains("Configuration file nonexistent.toml doesn't exist"),
+        "{}",
+        run.stderr
+    );
+}
+
+#[test]
+fn directory_flag() {
+    let fixture = Fixture::new();
+    fixture.add_remote("origin");
+    let fork = fixture.add_remote("fork");
+    fixture.git(&["switch", "--quiet", "--create", "feature"]);
+    // The repository's configuration file is found through `-C`, too.
+    std::fs::write(
+        fixture.work.join(".git/git-upstream.toml"),
+        "remotes = [\"fork\"]\n",
+    )
+    .unwrap();
+    let work = fixture.work.file_name().unwrap().to_str().unwrap();
+
+    let run = fixture.run_in(fixture.path(), &["-C", work]);
+    run.assert_code(0);
+    assert_eq!(run.stdout, "fork/feature\n");
+    assert!(fixture.remote_rev(&fork, "feature").is_some());
+
+    let run = fixture.run_in(fixture.path(), &["-C", work, "status"]);
+    run.assert_code(0);
+    assert!(run.stdout.contains("feature"), "{}", run.stdout);
+}
+
+#[test]
+fn directory_flag_not_a_repository() {
+    let fixture = Fixture::new();
+    let empty = fixture.path().join("empty");
+    std::fs::create_dir(&empty).unwrap();
+
+    for path in ["empty", "nonexistent"] {
+        let run = fixture.run_in(fixture.path(), &["-C", path, "status"]);
+        run.assert_code(5);
+        assert!(
+            run.stderr
+                .contains(&format!("{path} isn't a Git repository")),
+            "{}",
+            run.stderr
+        );
+    }
+}
+
+#[test]
+fn user_directories() {
+    let fixture = Fixture::new();
+    fixture.add_remote("origin");
+    let fork = fixture.add_remote("fork");
+    fixture.git(&["switch", "--quiet", "--create", "feature"]);
+    fixture.write_config("remotes = [\"fork\"]\n");
+
+    let run = fixture.run(&[]);
+    run.assert_code(0);
+    assert_eq!(run.stdout, "fork/feature\n");
+    assert!(fixture.remote_rev(&fork, "feature").is_some());
+    assert!(fixture.state_dir().join("journal").is_file());
+
+    let run = fixture.run(&["doctor"]);
+    let state_dir = fixture.state_dir();
+    assert!(
+        run.stdout.contains(&state_dir.display().to_string()),
+        "{}",
+        run.stdout
+    );
+}
+
+/// Files in the XDG directories, where they were kept on Windows before, are copied to their
+/// new places.
+#[cfg(windows)]
+#[test]
+fn legacy_windows_config() {
+    let fixture = Fixture::new();
+    fixture.add_remote("origin");
+    let fork = fixture.add_remote("fork");
+    fixture.git(&["switch", "--quiet", "--create", "feature"]);
+    let legacy = fixture.path().join("home/.config/git-upstream/config.toml");
+    std::fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+    std::fs::write(&legacy, "remotes = [\"fork\"]\n").unwrap();
+
+    let run = fixture.run(&[]);
+    run.assert_code(0);
+    assert!(fixture.remote_rev(&fork, "feature").is_some());
+    assert!(fixture.config_file().is_file());
+}
+
+#[test]
+fn doctor_exit_code() {
+    let fixture = Fixture::new();
+
+    // Without any remotes, the remote check fails.
+    let run = fixture.run(&["doctor", "--json"]);
+    run.assert_code(9);
+    assert!(run.stdout.contains("\"fail\""), "{}", run.stdout);
+
+    // With a remote, `main` having no upstream is only worth a warning.
+    fixture.add_remote("origin");
+    let run = fixture.run(&["doctor", "--json"]);
+    run.assert_code(8);
+    assert!(run.stdout.contains("has no upstream"), "{}", run.stdout);
+    assert!(!run.stdout.contains("\"fail\""), "{}", run.stdout);
+
+    let run = fixture.run(&["doctor", "--help"]);
+    run.assert_code(0);
+    assert!(
+        run.stdout
+            .contains("8 if any check warned, and 9 if any check failed"),
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+fn remote_branch_argument_conflicts_with_branch() {
+    let fixture = Fixture::new();
+    let origin = fixture.add_remote("origin");
+
+    let run = fixture.run(&["origin/feature", "--branch", "other"]);
+    run.assert_code(5);
+    assert!(
+        run.stderr.contains("names a remote and a branch"),
+        "{}",
+        run.stderr
+    );
+    assert_eq!(fixture.remote_rev(&origin, "feature"), None);
+}
+
+#[test]
+fn logs_startup() {
+    let fixture = Fixture::new();
+    fixture.add_remote("origin");
+    fixture.write_config("remotes = [\"origin\"]\n");
+
+    let run = fixture.run(&["-v", "status"]);
+    run.assert_code(0);
+    assert!(run.stderr.contains("Using Git"), "{}", run.stderr);
+    assert!(
+        run.stderr.contains("Reading configuration file"),
+        "{}",
+        run.stderr
+    );
+
+    let log = fixture.path().join("cli.log");
+    fixture
+        .run(&["--log-file", log.to_str().unwrap(), "status"])
+        .assert_code(0);
+    let contents = std::fs::read_to_string(&log).unwrap();
+    assert!(contents.contains("Using Git"), "{contents}");
+
+    // A log file from the configuration file is only opened once it's been read.
+    let log = fixture.path().join("config.log");
+    fixture.write_config(&format!("log-file = '{}'\n", log.display()));
+    fixture.git(&["switch", "--quiet", "--create", "feature"]);
+    fixture.run(&[]).assert_code(0);
+    let contents = std::fs::read_to_string(&log).unwrap();
+    assert!(contents.contains("$ git push"), "{contents}");
+}
+
+#[test]
+fn not_a_work_tree() {
+    let fixture = Fixture::new();
+    let empty = fixture.path().join("empty");
+    std::fs::create_dir(&empty).unwrap();
+    let bare = fixture.path().join("bare.git");
+    fixture.git_in(
+        fixture.path(),
+        &["init", "--quiet", "--bare", bare.to_str().unwrap()],
+    );
+
+    for args in [&[][..], &["status"]] {
+        let run = fixture.run_in(&empty, args);
+        run.assert_code(1);
+        assert!(
+            run.stderr.contains("isn't inside a Git work tree"),
+            "{}",
+            run.stderr
+        );
+        assert!(run.stderr.contains("git init"), "{}", run.stderr);
+        assert!(!run.stderr.contains("fatal:"), "{}", run.stderr);
+    }
+
+    let run = fixture.run_in(&bare, &[]);
+    run.assert_code(1);
+    assert!(
+        run.stderr.contains("is a bare repository"),
+        "{}",
+        run.stderr
+    );
+
+    let run = fixture.run_in(&fixture.work.join(".git"), &[]);
+    run.assert_code(1);
+    assert!(
+        run.stderr.contains("inside a Git directory"),
+        "{}",
+        run.stderr
+    );
+}
+
+#[test]
+fn color() {
+    let fixture = Fixture::new();
+    // Fails with no remotes, so there's an error message to color.
+    let colored = |args: &[&str], vars: &[&str]| {
+        let vars = vars
+            .iter()
+            .map(|var| var.split_once('=').unwrap())
+            .collect::<Vec<_>>();
+        let run = fixture.run_with_env(args, &vars);
+        run.assert_code(2);
+        run.stderr.contains('\x1b')
+    };
+    let cases: &[(&[&str], &[&str], bool)] = &[
+        // Not a terminal.
+        (&[], &[], false),
+        (&["--color=always"], &[], true),
+        (&["--color=never"], &[], false),
+        (&["--color=never"], &["CLICOLOR_FORCE=1"], false),
+        (&["--color=always"], &["NO_COLOR=1"], true),
+        (&[], &["CLICOLOR_FORCE=1"], true),
+        (&[], &["CLICOLOR_FORCE=0"], false),
+        (&[], &["NO_COLOR=1", "CLICOLOR_FORCE=1"], false),
+        (&["--color=auto"], &["NO_COLOR=1"], false),
+    ];
+    for (args, vars, expected) in cases {
+        assert_eq!(colored(args, vars), *expected, "{args:?} {vars:?}");
+    }
+}